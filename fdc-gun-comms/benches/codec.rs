@@ -0,0 +1,118 @@
+//! Benchmarks for encoding and decoding [`FdcGunMessage`]s with the tokio-util codec.
+//!
+//! Each benchmark works through a batch of messages as a busy FDC link would see them, reusing one buffer
+//! throughout, so that per-message allocations show up in the results.
+use std::collections::HashMap;
+
+use bytes::BytesMut;
+use criterion::{black_box, criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use fdc_gun_comms::{
+    codec::{FdcGunMessageDecoder, FdcGunMessageEncoder},
+    Ammunition, Compliance, FdcGunMessage, Fuze, ProtocolVersion, Status, TargetLocation,
+};
+use tokio_util::codec::{Decoder, Encoder};
+
+/// The number of messages in each batch
+const BATCH: usize = 1000;
+
+/// A mix of the messages sent during a fire mission
+fn messages() -> Vec<(&'static str, FdcGunMessage)> {
+    vec![
+        (
+            "fire_report",
+            FdcGunMessage::FireReport {
+                command_id: 1,
+                shot: 2,
+                total_shots: 4,
+                ammunition: Ammunition::HighExplosive,
+                target_location: TargetLocation::new(8916, 411),
+                time_to_target: 29_097,
+            },
+        ),
+        (
+            "fire_command",
+            FdcGunMessage::FireCommand {
+                command_id: 1,
+                rounds: 4,
+                ammunition: Ammunition::HighExplosive,
+                fuze: Fuze::PointDetonating,
+                target_location: TargetLocation::new(8916, 411),
+            },
+        ),
+        (
+            "compliance_response",
+            FdcGunMessage::ComplianceResponse {
+                command_id: 1,
+                compliance: Compliance::WILLCO,
+            },
+        ),
+        (
+            "status_reply",
+            FdcGunMessage::StatusReply {
+                status: Status::Operational,
+                rounds: HashMap::from([
+                    (Ammunition::HighExplosive, 120),
+                    (Ammunition::Smoke, 30),
+                    (Ammunition::Illumination, 20),
+                    (Ammunition::WhitePhosphorus, 10),
+                ]),
+            },
+        ),
+    ]
+}
+
+/// Encodes a batch of each message into a single buffer
+fn bench_encode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("encode");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for (name, message) in messages() {
+        let batch: Vec<_> = std::iter::repeat_with(|| message.clone())
+            .take(BATCH)
+            .collect();
+        let mut encoder = FdcGunMessageEncoder::new(ProtocolVersion::LATEST);
+        let mut buffer = BytesMut::new();
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || batch.clone(),
+                |batch| {
+                    buffer.clear();
+                    for message in batch.drain(..) {
+                        encoder.encode(message, &mut buffer).unwrap();
+                    }
+                    black_box(&buffer);
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+/// Decodes a batch of each message from a single buffer
+fn bench_decode(c: &mut Criterion) {
+    let mut group = c.benchmark_group("decode");
+    group.throughput(Throughput::Elements(BATCH as u64));
+    for (name, message) in messages() {
+        let mut encoder = FdcGunMessageEncoder::new(ProtocolVersion::LATEST);
+        let mut encoded = BytesMut::new();
+        for _ in 0..BATCH {
+            encoder.encode(message.clone(), &mut encoded).unwrap();
+        }
+        let mut decoder = FdcGunMessageDecoder::new(ProtocolVersion::LATEST);
+        group.bench_function(name, |b| {
+            b.iter_batched_ref(
+                || encoded.clone(),
+                |buffer| {
+                    while let Some(message) = decoder.decode(buffer).unwrap() {
+                        black_box(message);
+                    }
+                },
+                BatchSize::SmallInput,
+            );
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_encode, bench_decode);
+criterion_main!(benches);
@@ -1,21 +1,44 @@
 //! Provides tokio-util definitions for a codec.
 
-use bytes::{Buf, BytesMut};
+use bytes::BytesMut;
 use tokio_util::codec::{Decoder, Encoder};
 
-use crate::FdcGunMessage;
+use crate::{error::Error, FdcGunMessage, ProtocolVersion, MESSAGE_HEADER_SIZE};
 
 const MAX_BYTES: usize = 8 * 1024; // 8 KB
 const MESSAGE_LEN_SIZE: usize = 4; // 4 Bytes for the message length marker.
 
 /// [`tokio_util::codec::Decoder`] for [`FdcGunMessage`]
+///
+/// Decodes with the layout of [`ProtocolVersion::V1`] until told otherwise.
 #[derive(Default)]
-pub struct FdcGunMessageDecoder {}
+pub struct FdcGunMessageDecoder {
+    version: ProtocolVersion,
+}
+
+impl FdcGunMessageDecoder {
+    /// Creates a decoder for the given protocol `version`.
+    #[must_use]
+    pub fn new(version: ProtocolVersion) -> Self {
+        Self { version }
+    }
+
+    /// The protocol version in use
+    #[must_use]
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Switches to the given protocol `version` for all following frames.
+    pub fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = version;
+    }
+}
 
 impl Decoder for FdcGunMessageDecoder {
     type Item = FdcGunMessage;
 
-    type Error = std::io::Error;
+    type Error = Error;
 
     fn decode(&mut self, src: &mut BytesMut) -> Result<Option<Self::Item>, Self::Error> {
         if src.len() < MESSAGE_LEN_SIZE {
@@ -30,47 +53,78 @@ impl Decoder for FdcGunMessageDecoder {
 
         // Check that the length is not too large to avoid a DOS attack
         if length > MAX_BYTES {
-            return Err(std::io::Error::new(
-                std::io::ErrorKind::InvalidData,
-                format!("Frame of length {} is too large.", length),
-            ));
+            return Err(Error::FrameTooLarge(length));
         }
 
-        if src.len() < MESSAGE_LEN_SIZE + length {
+        // The length marker does not count the header, which includes the message ID.
+        let frame_length = MESSAGE_HEADER_SIZE + length;
+
+        if src.len() < frame_length {
             // A full message has not yet arrived.
             //
-            // We reserve more space in th ebuffer. This is not strictly
+            // We reserve more space in the buffer. This is not strictly
             // necessary, but helps with performance.
-            src.reserve(MESSAGE_LEN_SIZE + length - src.len());
+            src.reserve(frame_length - src.len());
 
             // We inform the Framed that we need more bytes to form the next
             // frame.
             return Ok(None);
         }
 
-        // Use advance to modify src such that it no longer contains this frame.
-        // Get the data, from the last byte of the header (inclusive) to the end of the expected message
-        let data = src[..MESSAGE_LEN_SIZE + 1 + length].to_vec();
-        src.advance(MESSAGE_LEN_SIZE + 1 + length);
+        // Split the frame off of src, from the first byte of the header to the end of the expected message,
+        // without copying it.
+        let frame = src.split_to(frame_length).freeze();
 
-        // Convert the data into an FdcGunMessage, or fail if invalid.
-        match FdcGunMessage::deserialize(data.as_slice()) {
-            Ok(message) => Ok(Some(message)),
-            Err(io_error) => Err(io_error),
-        }
+        // Convert the frame into an FdcGunMessage, or fail if invalid.
+        FdcGunMessage::deserialize(self.version, &frame[..]).map(Some)
     }
 }
 
 /// [`tokio_util::codec::Encoder`] for [`FdcGunMessage`]
+///
+/// Encodes with the layout of [`ProtocolVersion::V1`] until told otherwise.
 #[derive(Default)]
-pub struct FdcGunMessageEncoder {}
+pub struct FdcGunMessageEncoder {
+    version: ProtocolVersion,
+}
+
+impl FdcGunMessageEncoder {
+    /// Creates an encoder for the given protocol `version`.
+    #[must_use]
+    pub fn new(version: ProtocolVersion) -> Self {
+        Self { version }
+    }
+
+    /// The protocol version in use
+    #[must_use]
+    pub fn version(&self) -> ProtocolVersion {
+        self.version
+    }
+
+    /// Switches to the given protocol `version` for all following frames.
+    pub fn set_version(&mut self, version: ProtocolVersion) {
+        self.version = version;
+    }
+}
 
 impl Encoder<FdcGunMessage> for FdcGunMessageEncoder {
-    type Error = std::io::Error;
+    type Error = Error;
 
     fn encode(&mut self, item: FdcGunMessage, dst: &mut BytesMut) -> Result<(), Self::Error> {
+        let start = dst.len();
         dst.reserve(MESSAGE_LEN_SIZE + 30);
-        item.serialize(dst)?;
+        // Serialize straight into dst, and drop anything half-written on failure.
+        if let Err(err) = item.serialize(self.version, dst) {
+            dst.truncate(start);
+            return Err(err);
+        }
+
+        // Refuse to send anything the other side's decoder would refuse to receive.
+        let length = dst.len() - start - MESSAGE_HEADER_SIZE;
+        if length > MAX_BYTES {
+            dst.truncate(start);
+            return Err(Error::FrameTooLarge(length));
+        }
         Ok(())
     }
 }
@@ -83,18 +137,137 @@ mod tests {
     proptest! {
 
         #[test]
-        fn test_codec(message in any::<FdcGunMessage>()) {
+        fn test_codec(message in any::<FdcGunMessage>(), version in any::<ProtocolVersion>()) {
+            let mut buffer = BytesMut::new();
+
+            let mut encoder = FdcGunMessageEncoder::new(version);
+
+            let mut decoder = FdcGunMessageDecoder::new(version);
+
+            match encoder.encode(message.clone(), &mut buffer) {
+                Ok(()) => {
+                    let decoded = decoder.decode(&mut buffer).unwrap().unwrap();
+
+                    assert_eq!(message, decoded);
+                }
+                // Nothing half-written is left behind for the next frame.
+                Err(Error::Unrepresentable { .. }) => prop_assert!(buffer.is_empty()),
+                Err(err) => panic!("unexpected error {err}"),
+            }
+        }
+
+        #[test]
+        fn test_codec_partial_frames(message in any::<FdcGunMessage>()) {
+            let mut encoded = BytesMut::new();
+            FdcGunMessageEncoder::new(ProtocolVersion::LATEST).encode(message.clone(), &mut encoded).unwrap();
+
+            let mut decoder = FdcGunMessageDecoder::new(ProtocolVersion::LATEST);
             let mut buffer = BytesMut::new();
+            let (last, partial) = encoded.split_last().unwrap();
+
+            // Every prefix of a frame is an incomplete frame, not an error.
+            for byte in partial {
+                buffer.extend_from_slice(&[*byte]);
+                prop_assert_eq!(decoder.decode(&mut buffer).unwrap(), None);
+            }
 
-            let mut encoder = FdcGunMessageEncoder::default();
+            buffer.extend_from_slice(&[*last]);
+            prop_assert_eq!(decoder.decode(&mut buffer).unwrap(), Some(message));
+            prop_assert!(buffer.is_empty());
+        }
+
+        #[test]
+        fn test_codec_back_to_back(first in any::<FdcGunMessage>(), second in any::<FdcGunMessage>()) {
+            let mut buffer = BytesMut::new();
+            let mut encoder = FdcGunMessageEncoder::new(ProtocolVersion::LATEST);
+            encoder.encode(first.clone(), &mut buffer).unwrap();
+            encoder.encode(second.clone(), &mut buffer).unwrap();
+
+            let mut decoder = FdcGunMessageDecoder::new(ProtocolVersion::LATEST);
+            prop_assert_eq!(decoder.decode(&mut buffer).unwrap(), Some(first));
+            prop_assert_eq!(decoder.decode(&mut buffer).unwrap(), Some(second));
+            prop_assert_eq!(decoder.decode(&mut buffer).unwrap(), None);
+        }
 
+        #[test]
+        fn test_decode_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..256)) {
+            let mut buffer = BytesMut::from(bytes.as_slice());
             let mut decoder = FdcGunMessageDecoder::default();
 
-            encoder.encode(message.clone(), &mut buffer).unwrap();
+            // Whatever comes in, the decoder must return rather than panic, and must make progress on every frame.
+            loop {
+                let before = buffer.len();
+                match decoder.decode(&mut buffer) {
+                    Ok(Some(_)) => prop_assert!(buffer.len() < before),
+                    Ok(None) | Err(_) => break,
+                }
+            }
+        }
 
-            let decoded = decoder.decode(&mut buffer).unwrap().unwrap();
+        #[test]
+        fn test_decode_framed_arbitrary_bodies(
+            id in any::<u8>(),
+            body in prop::collection::vec(any::<u8>(), 0..64),
+            version in any::<ProtocolVersion>(),
+        ) {
+            // Well-formed frames with garbage contents reach every message parser.
+            let mut buffer = BytesMut::new();
+            buffer.extend_from_slice(&(body.len() as u32).to_be_bytes());
+            buffer.extend_from_slice(&[id]);
+            buffer.extend_from_slice(&body);
 
-            assert_eq!(message, decoded);
+            let mut decoder = FdcGunMessageDecoder::new(version);
+            let _ = decoder.decode(&mut buffer);
+            prop_assert!(buffer.is_empty());
         }
     }
+
+    #[test]
+    fn test_codec_version_switch() {
+        // The handshake is read in V1, and everything after it in the negotiated version.
+        let report = FdcGunMessage::FireReport {
+            command_id: crate::NO_COMMAND_ID,
+            shot: 300,
+            total_shots: 300,
+            ammunition: crate::Ammunition::HighExplosive,
+            target_location: crate::TargetLocation::new(4000, 1600),
+            time_to_target: 13_333,
+        };
+        let mut buffer = BytesMut::new();
+        let mut encoder = FdcGunMessageEncoder::default();
+        let mut decoder = FdcGunMessageDecoder::default();
+
+        assert!(matches!(
+            encoder.encode(report.clone(), &mut buffer),
+            Err(Error::Unrepresentable {
+                field: "shot",
+                value: 300,
+                version: ProtocolVersion::V1
+            })
+        ));
+        assert!(buffer.is_empty());
+
+        let accept = FdcGunMessage::VersionAccept {
+            version: ProtocolVersion::V2,
+        };
+        encoder.encode(accept.clone(), &mut buffer).unwrap();
+        encoder.set_version(ProtocolVersion::V2);
+        encoder.encode(report.clone(), &mut buffer).unwrap();
+
+        assert_eq!(decoder.decode(&mut buffer).unwrap(), Some(accept));
+        decoder.set_version(ProtocolVersion::V2);
+        assert_eq!(decoder.decode(&mut buffer).unwrap(), Some(report));
+    }
+
+    #[test]
+    fn test_decode_frame_too_large() {
+        let mut buffer = BytesMut::new();
+        buffer.extend_from_slice(&((MAX_BYTES + 1) as u32).to_be_bytes());
+
+        let mut decoder = FdcGunMessageDecoder::default();
+        assert!(matches!(
+            decoder.decode(&mut buffer),
+            Err(Error::FrameTooLarge(length)) if length == MAX_BYTES + 1
+        ));
+    }
 }
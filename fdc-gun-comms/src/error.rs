@@ -0,0 +1,78 @@
+//! Provides the error type for serializing, deserializing, and framing [`FdcGunMessage`]s.
+//!
+//! [`FdcGunMessage`]: crate::FdcGunMessage
+use std::io;
+
+use thiserror::Error;
+
+use crate::{Ammunition, ProtocolVersion};
+
+/// Everything that can go wrong converting between raw bytes and an [`FdcGunMessage`].
+///
+/// [`FdcGunMessage`]: crate::FdcGunMessage
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The message ID is not a known message type
+    #[error("unknown message ID {0:#04x}")]
+    UnknownMessageId(u8),
+    /// An ammunition byte is not a known [`Ammunition`]
+    #[error("invalid ammunition {0:#04x}")]
+    InvalidAmmunition(u8),
+    /// A fuze byte is not a known [`Fuze`](crate::Fuze)
+    #[error("invalid fuze {0:#04x}")]
+    InvalidFuze(u8),
+    /// A fault byte is not a known [`Fault`](crate::Fault)
+    #[error("invalid fault {0:#04x}")]
+    InvalidFault(u8),
+    /// A status byte is not a known [`Status`](crate::Status)
+    #[error("invalid status {0:#04x}")]
+    InvalidStatus(u8),
+    /// A compliance byte is not a known [`Compliance`](crate::Compliance)
+    #[error("invalid compliance {0:#04x}")]
+    InvalidCompliance(u8),
+    /// A version byte is not a known [`ProtocolVersion`]
+    #[error("invalid protocol version {0:#04x}")]
+    InvalidVersion(u8),
+    /// A boolean flag is neither 0x00 nor 0x01
+    #[error("invalid flag {0:#04x}")]
+    InvalidFlag(u8),
+    /// The same ammunition was counted twice in a status reply
+    #[error("duplicate count for {0:?}")]
+    DuplicateAmmunition(Ammunition),
+    /// There were fewer bytes than the message needs
+    #[error("truncated message: needed {needed} bytes, got {got}")]
+    Truncated {
+        /// The number of bytes needed
+        needed: usize,
+        /// The number of bytes available
+        got: usize,
+    },
+    /// There were more bytes than the header declares
+    #[error("header declares {declared} bytes, but {got} were given")]
+    LengthMismatch {
+        /// The number of bytes declared by the header
+        declared: usize,
+        /// The number of bytes given
+        got: usize,
+    },
+    /// The header declares more bytes than the message type uses
+    #[error("{0} trailing bytes after the message")]
+    TrailingBytes(usize),
+    /// A field's value does not fit the layout of the protocol version in use
+    #[error("{field} value {value} does not fit protocol version {version:?}")]
+    Unrepresentable {
+        /// The name of the field
+        field: &'static str,
+        /// The value that does not fit
+        value: u32,
+        /// The protocol version in use
+        version: ProtocolVersion,
+    },
+    /// The frame is larger than the interface allows
+    #[error("frame of length {0} is too large")]
+    FrameTooLarge(usize),
+    /// The underlying transport failed
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
@@ -10,23 +10,35 @@
 //! Since the interface is over TCP, the message model is as follows:
 //! Raw Bytes (`Vec<u8>`) <-> [`FdcGunMessage`] with bytes and a message ID,
 //! and finally specific message instances with respective strong types.
+//!
+//! With the `serde` feature, every message can also be written as JSON, for inspecting and crafting captures.
 
-use std::{collections::HashMap, io};
+use std::collections::HashMap;
 
 use bytes::{Buf, BufMut};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
+#[cfg(feature = "serde")]
+use serde::{Deserialize, Serialize};
 
 pub mod codec;
+pub mod error;
+pub mod liveness;
+
+use error::Error;
 
 /// High-level message definition.
 ///
 /// Intended to be used as an intermediate between raw bytes and a specific strongly typed message
-#[derive(Debug, PartialEq)]
-#[cfg_attr(test, derive(Clone))]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub enum FdcGunMessage {
     /// A request for status
     StatusRequest,
@@ -41,16 +53,24 @@ pub enum FdcGunMessage {
 
     /// A Report of gun fires
     FireReport {
+        /// The ID of the [`FdcGunMessage::FireCommand`] being fired
+        ///
+        /// Always [`NO_COMMAND_ID`] before [`ProtocolVersion::V4`]
+        command_id: u32,
         /// The shot number for this report
-        shot: u8,
+        ///
+        /// Limited to 8 bits in [`ProtocolVersion::V1`]
+        shot: u32,
         /// The total number of shots that the gun issuing the report
         /// will fire
-        total_shots: u8,
+        ///
+        /// Limited to 8 bits in [`ProtocolVersion::V1`]
+        total_shots: u32,
         /// The Ammunition in use
         ammunition: Ammunition,
         /// The location of this shot
         target_location: TargetLocation,
-        /// The time in seconds from the issue of this
+        /// The time in milliseconds from the issue of this
         /// [`FdcGunMessage::FireReport`] until the round is expected
         /// to land
         time_to_target: u32,
@@ -58,10 +78,19 @@ pub enum FdcGunMessage {
 
     /// A Command from the FDC to fire
     FireCommand {
+        /// The FDC's ID for this command, echoed back in the [`FdcGunMessage::ComplianceResponse`] and each
+        /// [`FdcGunMessage::FireReport`] for it
+        ///
+        /// Always [`NO_COMMAND_ID`] before [`ProtocolVersion::V4`]
+        command_id: u32,
         /// Number of rounds to fire
         rounds: u32,
         /// Type of ammunition for fires
         ammunition: Ammunition,
+        /// Fuze setting for the rounds
+        ///
+        /// Always [`Fuze::PointDetonating`] before [`ProtocolVersion::V3`]
+        fuze: Fuze,
         /// Location of target
         target_location: TargetLocation,
     },
@@ -69,11 +98,73 @@ pub enum FdcGunMessage {
     /// A Check Fire command to a gun, to stop any active fires
     CheckFire,
 
-    /// A Compliance response to a CheckFire, FireCommand
+    /// A Compliance response to a [`FdcGunMessage::CheckFire`] or [`FdcGunMessage::FireCommand`]
     ComplianceResponse {
+        /// The ID of the [`FdcGunMessage::FireCommand`] being responded to, or for a [`FdcGunMessage::CheckFire`],
+        /// of the last one the gun was given
+        ///
+        /// Always [`NO_COMMAND_ID`] before [`ProtocolVersion::V4`]
+        command_id: u32,
         /// The specific compliance type
         compliance: Compliance,
     },
+
+    /// A registration from a gun, identifying itself to the FDC
+    Registration {
+        /// The gun's unique identifier
+        gun_id: u32,
+        /// Where the gun is emplaced
+        position: GunPosition,
+        /// What the gun can do
+        capabilities: Capabilities,
+    },
+
+    /// The FDC's acknowledgement of a [`FdcGunMessage::Registration`]
+    RegistrationAck {
+        /// The identifier of the registering gun
+        gun_id: u32,
+        /// Whether the FDC accepted the registration
+        accepted: bool,
+    },
+
+    /// An offer from a gun to speak the protocol up to a version, sent before registering
+    VersionOffer {
+        /// The highest protocol version the gun supports
+        max_version: u8,
+    },
+
+    /// The FDC's choice of protocol version, in response to a [`FdcGunMessage::VersionOffer`]
+    VersionAccept {
+        /// The protocol version both sides use from here on
+        version: ProtocolVersion,
+    },
+
+    /// A report from a gun that a fault stopped it firing a [`FdcGunMessage::FireCommand`]
+    ///
+    /// No more [`FdcGunMessage::FireReport`]s follow for the command. Until the fault is cleared, the gun's
+    /// [`FdcGunMessage::StatusReply`] reports no better than [`Fault::status`].
+    Malfunction {
+        /// The ID of the [`FdcGunMessage::FireCommand`] being fired
+        ///
+        /// Always [`NO_COMMAND_ID`] before [`ProtocolVersion::V4`]
+        command_id: u32,
+        /// What went wrong
+        fault: Fault,
+        /// The shot number the fault affected, which was not fired
+        shot: u32,
+    },
+
+    /// A delivery of ammunition to a gun, announced by the FDC
+    Resupply {
+        /// Rounds delivered
+        rounds: HashMap<Ammunition, u32>,
+    },
+
+    /// A gun's acknowledgement of a [`FdcGunMessage::Resupply`]
+    ResupplyAck {
+        /// Rounds available, now that the delivery is stowed
+        rounds: HashMap<Ammunition, u32>,
+    },
 }
 
 impl From<&FdcGunMessage> for u8 {
@@ -85,227 +176,465 @@ impl From<&FdcGunMessage> for u8 {
             FdcGunMessage::StatusRequest => 0x02,
             FdcGunMessage::StatusReply { .. } => 0x03,
             FdcGunMessage::FireCommand { .. } => 0x05,
+            FdcGunMessage::Registration { .. } => 0x04,
             FdcGunMessage::CheckFire => 0x06,
+            FdcGunMessage::RegistrationAck { .. } => 0x07,
+            FdcGunMessage::VersionOffer { .. } => 0x08,
+            FdcGunMessage::VersionAccept { .. } => 0x09,
+            FdcGunMessage::Malfunction { .. } => 0x0A,
+            FdcGunMessage::Resupply { .. } => 0x0B,
+            FdcGunMessage::ResupplyAck { .. } => 0x0C,
         }
     }
 }
 
 impl FdcGunMessage {
-    /// Serializes an [`FdcGunMessage`] to the supplied buffer.
+    /// Serializes an [`FdcGunMessage`] to the end of the supplied buffer, using the layout of the given protocol
+    /// `version`.
+    ///
+    /// The header and contents are written straight into `buf`, and the length marker is filled in once the
+    /// contents are written, so no intermediate buffer is needed.
     ///
     /// # Errors
     ///
-    /// This method returns a [`std::io::Error`] if there is data missing,
-    /// or if any data is otherwise invalid
-    pub fn serialize(&self, buf: &mut impl BufMut) -> io::Result<()> {
-        let message_contents = {
-            let mut message_contents = Vec::new();
-            match self {
-                FdcGunMessage::StatusRequest => (),
-                FdcGunMessage::StatusReply { status, rounds } => {
-                    serialize_status_reply(&mut message_contents, status, rounds)?;
-                }
-                FdcGunMessage::FireReport {
-                    shot,
-                    total_shots,
-                    ammunition,
-                    target_location,
-                    time_to_target,
-                } => serialize_fire_report(
-                    &mut message_contents,
-                    shot,
-                    total_shots,
-                    ammunition,
-                    target_location,
-                    time_to_target,
-                )?,
-                FdcGunMessage::FireCommand {
+    /// This method returns an [`Error::FrameTooLarge`] if the message contents
+    /// do not fit in the header's length marker, or an [`Error::Unrepresentable`]
+    /// if a field does not fit the layout of `version`. On error, `buf` may be left holding part of the message.
+    pub fn serialize<B: BufMut + AsMut<[u8]>>(
+        &self,
+        version: ProtocolVersion,
+        buf: &mut B,
+    ) -> Result<(), Error> {
+        // Write the header, with the length marker to be filled in once the contents are written
+        let start = buf.as_mut().len();
+        buf.put_u32(0);
+        buf.put_u8(self.into());
+
+        // Write data
+        match self {
+            FdcGunMessage::StatusRequest => (),
+            FdcGunMessage::StatusReply { status, rounds } => {
+                serialize_status_reply(buf, status, rounds)?;
+            }
+            FdcGunMessage::FireReport {
+                command_id,
+                shot,
+                total_shots,
+                ammunition,
+                target_location,
+                time_to_target,
+            } => serialize_fire_report(
+                buf,
+                version,
+                command_id,
+                shot,
+                total_shots,
+                ammunition,
+                target_location,
+                time_to_target,
+            )?,
+            FdcGunMessage::FireCommand {
+                command_id,
+                rounds,
+                ammunition,
+                fuze,
+                target_location,
+            } => {
+                serialize_fire_command(
+                    buf,
+                    version,
+                    command_id,
                     rounds,
                     ammunition,
+                    fuze,
                     target_location,
-                } => {
-                    serialize_fire_command(
-                        &mut message_contents,
-                        rounds,
-                        ammunition,
-                        target_location,
-                    )?;
-                }
-                FdcGunMessage::CheckFire => (),
-                FdcGunMessage::ComplianceResponse { compliance } => {
-                    serialize_compliance_response(&mut message_contents, compliance)?;
-                }
+                )?;
             }
-            message_contents
-        };
+            FdcGunMessage::CheckFire => (),
+            FdcGunMessage::ComplianceResponse {
+                command_id,
+                compliance,
+            } => {
+                serialize_compliance_response(buf, version, command_id, compliance)?;
+            }
+            FdcGunMessage::Registration {
+                gun_id,
+                position,
+                capabilities,
+            } => {
+                serialize_registration(buf, gun_id, position, capabilities)?;
+            }
+            FdcGunMessage::RegistrationAck { gun_id, accepted } => {
+                serialize_registration_ack(buf, gun_id, accepted)?;
+            }
+            FdcGunMessage::VersionOffer { max_version } => {
+                buf.put_u8(*max_version);
+            }
+            FdcGunMessage::VersionAccept { version } => {
+                buf.put_u8((*version).into());
+            }
+            FdcGunMessage::Malfunction {
+                command_id,
+                fault,
+                shot,
+            } => {
+                serialize_malfunction(buf, version, command_id, fault, shot)?;
+            }
+            FdcGunMessage::Resupply { rounds } | FdcGunMessage::ResupplyAck { rounds } => {
+                serialize_rounds(buf, rounds);
+            }
+        }
 
-        // Write header
-        let message_length = message_contents
-            .len()
+        // Fill in the length marker
+        let message_length = buf.as_mut().len() - start - MESSAGE_HEADER_SIZE;
+        let length_marker: u32 = message_length
             .try_into()
-            .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
-
-        buf.put_u32(message_length);
-        buf.put_u8(self.into());
-
-        // Write data
-        buf.put(message_contents.as_slice());
+            .map_err(|_| Error::FrameTooLarge(message_length))?;
+        buf.as_mut()[start..start + 4].copy_from_slice(&length_marker.to_be_bytes());
 
         Ok(())
     }
 
-    /// Deserializes an [`FdcGunMessage`] from the supplied buffer.
+    /// Deserializes an [`FdcGunMessage`] from the supplied buffer, using the layout of the given protocol `version`.
+    ///
+    /// The buffer must hold exactly one message, header included.
     ///
     /// # Errors
     ///
-    /// This method returns a [`std::io::Error`] if there is data missing,
-    /// or if any data is otherwise invalid
-    pub fn deserialize(mut buf: impl Buf) -> io::Result<Self> {
-        let _message_len = buf.get_u32();
+    /// This method returns an [`Error::Truncated`] if there is data missing,
+    /// an [`Error::LengthMismatch`] or [`Error::TrailingBytes`] if the header's length does not match the message contents,
+    /// or another [`Error`] variant if any data is otherwise invalid
+    pub fn deserialize(version: ProtocolVersion, mut buf: impl Buf) -> Result<Self, Error> {
+        ensure_remaining(&buf, MESSAGE_HEADER_SIZE)?;
+        let message_len = buf.get_u32() as usize;
+        let message_id = buf.get_u8();
 
-        match buf.get_u8() {
+        ensure_remaining(&buf, message_len)?;
+        if buf.remaining() > message_len {
+            return Err(Error::LengthMismatch {
+                declared: message_len,
+                got: buf.remaining(),
+            });
+        }
+
+        let message = match message_id {
             // ComplianceResponse
-            0x00 => deserialize_compliance_response(buf),
+            0x00 => deserialize_compliance_response(version, &mut buf),
             // FireReport
-            0x01 => deserialize_fire_report(buf),
+            0x01 => deserialize_fire_report(version, &mut buf),
             // StatusRequest
             0x02 => Ok(FdcGunMessage::StatusRequest),
             // StatusReply
-            0x03 => deserialize_status_reply(buf),
+            0x03 => deserialize_status_reply(&mut buf),
+            // Registration
+            0x04 => deserialize_registration(&mut buf),
             // FireCommand
-            0x05 => deserialize_fire_command(buf),
+            0x05 => deserialize_fire_command(version, &mut buf),
             // CheckFire
             0x06 => Ok(FdcGunMessage::CheckFire),
+            // RegistrationAck
+            0x07 => deserialize_registration_ack(&mut buf),
+            // VersionOffer
+            0x08 => deserialize_version_offer(&mut buf),
+            // VersionAccept
+            0x09 => deserialize_version_accept(&mut buf),
+            // Malfunction
+            0x0A => deserialize_malfunction(version, &mut buf),
+            // Resupply
+            0x0B => Ok(FdcGunMessage::Resupply {
+                rounds: deserialize_rounds(&mut buf)?,
+            }),
+            // ResupplyAck
+            0x0C => Ok(FdcGunMessage::ResupplyAck {
+                rounds: deserialize_rounds(&mut buf)?,
+            }),
 
             // Unsupported types
-            _ => Err(io::Error::new(
-                io::ErrorKind::InvalidData,
-                "Invalid Message Type",
-            )),
+            _ => Err(Error::UnknownMessageId(message_id)),
+        }?;
+
+        // Every message type has a known layout, so anything left over means the length was wrong for the type.
+        if buf.has_remaining() {
+            return Err(Error::TrailingBytes(buf.remaining()));
+        }
+
+        Ok(message)
+    }
+}
+
+/// The size of the message header: the length marker and the message ID
+const MESSAGE_HEADER_SIZE: usize = 5;
+
+/// Checks that `buf` has at least `needed` bytes left to read, so the `Buf::get_*` methods will not panic.
+fn ensure_remaining(buf: &impl Buf, needed: usize) -> Result<(), Error> {
+    if buf.remaining() < needed {
+        Err(Error::Truncated {
+            needed,
+            got: buf.remaining(),
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Serializes the command ID of a [`FdcGunMessage::FireCommand`], [`FdcGunMessage::ComplianceResponse`], or
+/// [`FdcGunMessage::FireReport`], which `version` may have no room for
+fn serialize_command_id(
+    buf: &mut impl BufMut,
+    version: ProtocolVersion,
+    command_id: &u32,
+) -> Result<(), Error> {
+    match (version, *command_id) {
+        (ProtocolVersion::V1 | ProtocolVersion::V2 | ProtocolVersion::V3, NO_COMMAND_ID) => Ok(()),
+        (ProtocolVersion::V1 | ProtocolVersion::V2 | ProtocolVersion::V3, _) => {
+            Err(Error::Unrepresentable {
+                field: "command_id",
+                value: *command_id,
+                version,
+            })
+        }
+        (ProtocolVersion::V4, _) => {
+            buf.put_u32(*command_id);
+            Ok(())
         }
     }
 }
 
 /// Serializes the fields of a [`FdcGunMessage::ComplianceResponse`]
 fn serialize_compliance_response(
-    message_contents: &mut Vec<u8>,
+    buf: &mut impl BufMut,
+    version: ProtocolVersion,
+    command_id: &u32,
     compliance: &Compliance,
-) -> Result<(), io::Error> {
-    message_contents.put_u8((*compliance).into());
+) -> Result<(), Error> {
+    serialize_command_id(buf, version, command_id)?;
+    buf.put_u8((*compliance).into());
     Ok(())
 }
 
 /// Serializes the fields of a [`FdcGunMessage::FireCommand`]
 fn serialize_fire_command(
-    message_contents: &mut Vec<u8>,
+    buf: &mut impl BufMut,
+    version: ProtocolVersion,
+    command_id: &u32,
     rounds: &u32,
     ammunition: &Ammunition,
+    fuze: &Fuze,
     target_location: &TargetLocation,
-) -> Result<(), io::Error> {
-    message_contents.put_u32(*rounds);
-    message_contents.put_u8((*ammunition).into());
-    target_location.serialize(message_contents);
+) -> Result<(), Error> {
+    serialize_command_id(buf, version, command_id)?;
+    buf.put_u32(*rounds);
+    buf.put_u8((*ammunition).into());
+    match version {
+        _ if !version.carries(*fuze) => {
+            return Err(Error::Unrepresentable {
+                field: "fuze",
+                value: u8::from(*fuze).into(),
+                version,
+            })
+        }
+        // The earlier layouts have no room for a fuze, so every round is Point Detonating.
+        ProtocolVersion::V1 | ProtocolVersion::V2 => (),
+        ProtocolVersion::V3 | ProtocolVersion::V4 => buf.put_u8((*fuze).into()),
+    }
+    target_location.serialize(buf);
     Ok(())
 }
 
 /// Serializes the fields of a [`FdcGunMessage::StatusReply`]
 fn serialize_status_reply(
-    message_contents: &mut Vec<u8>,
+    buf: &mut impl BufMut,
     status: &Status,
     rounds: &HashMap<Ammunition, u32>,
-) -> Result<(), io::Error> {
-    message_contents.put_u8((*status).into());
-    message_contents.reserve(5 * rounds.len());
+) -> Result<(), Error> {
+    buf.put_u8((*status).into());
+    serialize_rounds(buf, rounds);
+    Ok(())
+}
+
+/// Serializes a count of each ammunition, as in a [`FdcGunMessage::StatusReply`] or [`FdcGunMessage::Resupply`]
+fn serialize_rounds(buf: &mut impl BufMut, rounds: &HashMap<Ammunition, u32>) {
     for (ammo_type, ammo_count) in rounds {
-        message_contents.put_u8((*ammo_type).into());
-        message_contents.put_u32(*ammo_count);
+        buf.put_u8((*ammo_type).into());
+        buf.put_u32(*ammo_count);
     }
-    Ok(())
 }
 
 /// Serializes the fields of a [`FdcGunMessage::FireReport`]
+#[allow(clippy::too_many_arguments)]
 fn serialize_fire_report(
-    message_contents: &mut Vec<u8>,
-    shot: &u8,
-    total_shots: &u8,
+    buf: &mut impl BufMut,
+    version: ProtocolVersion,
+    command_id: &u32,
+    shot: &u32,
+    total_shots: &u32,
     ammunition: &Ammunition,
     target_location: &TargetLocation,
     time_to_target: &u32,
-) -> Result<(), io::Error> {
-    message_contents.put_u8(*shot);
-    message_contents.put_u8(*total_shots);
-    message_contents.put_u8((*ammunition).into());
-    target_location.serialize(message_contents);
-    message_contents.put_u32(*time_to_target);
+) -> Result<(), Error> {
+    serialize_command_id(buf, version, command_id)?;
+    match version {
+        ProtocolVersion::V1 => {
+            buf.put_u8(narrow(version, "shot", *shot)?);
+            buf.put_u8(narrow(version, "total_shots", *total_shots)?);
+        }
+        ProtocolVersion::V2 | ProtocolVersion::V3 | ProtocolVersion::V4 => {
+            buf.put_u32(*shot);
+            buf.put_u32(*total_shots);
+        }
+    }
+    buf.put_u8((*ammunition).into());
+    target_location.serialize(buf);
+    buf.put_u32(*time_to_target);
+    Ok(())
+}
+
+/// Serializes the fields of a [`FdcGunMessage::Registration`]
+fn serialize_registration(
+    buf: &mut impl BufMut,
+    gun_id: &u32,
+    position: &GunPosition,
+    capabilities: &Capabilities,
+) -> Result<(), Error> {
+    buf.put_u32(*gun_id);
+    position.serialize(buf);
+    capabilities.serialize(buf);
+    Ok(())
+}
+
+/// Serializes the fields of a [`FdcGunMessage::RegistrationAck`]
+fn serialize_registration_ack(
+    buf: &mut impl BufMut,
+    gun_id: &u32,
+    accepted: &bool,
+) -> Result<(), Error> {
+    buf.put_u32(*gun_id);
+    buf.put_u8((*accepted).into());
+    Ok(())
+}
+
+/// Serializes the fields of a [`FdcGunMessage::Malfunction`]
+fn serialize_malfunction(
+    buf: &mut impl BufMut,
+    version: ProtocolVersion,
+    command_id: &u32,
+    fault: &Fault,
+    shot: &u32,
+) -> Result<(), Error> {
+    serialize_command_id(buf, version, command_id)?;
+    buf.put_u8((*fault).into());
+    buf.put_u32(*shot);
     Ok(())
 }
 
 /// Deserializes to a [`FdcGunMessage::FireCommand`]
-fn deserialize_fire_command(mut buf: impl Buf) -> Result<FdcGunMessage, io::Error> {
+fn deserialize_fire_command(
+    version: ProtocolVersion,
+    buf: &mut impl Buf,
+) -> Result<FdcGunMessage, Error> {
+    let command_id = deserialize_command_id(version, buf)?;
+    ensure_remaining(buf, 5)?;
     let rounds = buf.get_u32();
-    let ammunition: Ammunition = buf
-        .get_u8()
-        .try_into()
-        .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
-    let target_location = TargetLocation::deserialize(&mut buf);
+    let ammunition = Ammunition::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidAmmunition(conv_err.number))?;
+    let fuze = match version {
+        ProtocolVersion::V1 | ProtocolVersion::V2 => Fuze::PointDetonating,
+        ProtocolVersion::V3 | ProtocolVersion::V4 => {
+            ensure_remaining(buf, 1)?;
+            Fuze::try_from_primitive(buf.get_u8())
+                .map_err(|conv_err| Error::InvalidFuze(conv_err.number))?
+        }
+    };
+    let target_location = TargetLocation::deserialize(buf)?;
     Ok(FdcGunMessage::FireCommand {
+        command_id,
         rounds,
         ammunition,
+        fuze,
         target_location,
     })
 }
 
 /// Deserializes to a [`FdcGunMessage::StatusReply`]
-fn deserialize_status_reply(mut buf: impl Buf) -> Result<FdcGunMessage, io::Error> {
-    let status = buf
-        .get_u8()
-        .try_into()
-        .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
+fn deserialize_status_reply(buf: &mut impl Buf) -> Result<FdcGunMessage, Error> {
+    ensure_remaining(buf, 1)?;
+    let status = Status::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidStatus(conv_err.number))?;
+    let rounds = deserialize_rounds(buf)?;
 
-    let rounds_chunks = buf.chunk().chunks(5);
+    Ok(FdcGunMessage::StatusReply { status, rounds })
+}
 
+/// Deserializes a count of each ammunition from the rest of the message, as in a [`FdcGunMessage::StatusReply`] or
+/// [`FdcGunMessage::Resupply`]
+fn deserialize_rounds(buf: &mut impl Buf) -> Result<HashMap<Ammunition, u32>, Error> {
     let mut rounds: HashMap<Ammunition, u32> = HashMap::new();
-    for chunk in rounds_chunks {
-        let (ammo_byte, count_bytes) = chunk
-            .split_first()
-            .ok_or_else(|| io::Error::from(io::ErrorKind::InvalidData))?;
+    while buf.has_remaining() {
+        ensure_remaining(buf, 5)?;
+        let ammunition = Ammunition::try_from_primitive(buf.get_u8())
+            .map_err(|conv_err| Error::InvalidAmmunition(conv_err.number))?;
+        let count = buf.get_u32();
 
-        let ammunition = (*ammo_byte)
-            .try_into()
-            .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
-
-        // let count = chunk.collect::<Result<Vec<u8>, _>>()?;
-        let count: [u8; 4] = count_bytes
-            .try_into()
-            .map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "Not enough data"))?;
-        let count = u32::from_be_bytes(count);
-        rounds.insert(ammunition, count);
+        if rounds.insert(ammunition, count).is_some() {
+            return Err(Error::DuplicateAmmunition(ammunition));
+        }
     }
 
-    Ok(FdcGunMessage::StatusReply { status, rounds })
+    Ok(rounds)
+}
+
+/// Deserializes the command ID of a [`FdcGunMessage::FireCommand`], [`FdcGunMessage::ComplianceResponse`], or
+/// [`FdcGunMessage::FireReport`], which is [`NO_COMMAND_ID`] if `version` has no room for it
+fn deserialize_command_id(version: ProtocolVersion, buf: &mut impl Buf) -> Result<u32, Error> {
+    match version {
+        ProtocolVersion::V1 | ProtocolVersion::V2 | ProtocolVersion::V3 => Ok(NO_COMMAND_ID),
+        ProtocolVersion::V4 => {
+            ensure_remaining(buf, 4)?;
+            Ok(buf.get_u32())
+        }
+    }
 }
 
 /// Deserializes to a [`FdcGunMessage::ComplianceResponse`]
-fn deserialize_compliance_response(mut buf: impl Buf) -> Result<FdcGunMessage, io::Error> {
-    let compliance = buf
-        .get_u8()
-        .try_into()
-        .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
-    Ok(FdcGunMessage::ComplianceResponse { compliance })
+fn deserialize_compliance_response(
+    version: ProtocolVersion,
+    buf: &mut impl Buf,
+) -> Result<FdcGunMessage, Error> {
+    let command_id = deserialize_command_id(version, buf)?;
+    ensure_remaining(buf, 1)?;
+    let compliance = Compliance::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidCompliance(conv_err.number))?;
+    Ok(FdcGunMessage::ComplianceResponse {
+        command_id,
+        compliance,
+    })
 }
 
 /// Deserializes to a [`FdcGunMessage::FireReport`]
-fn deserialize_fire_report(mut buf: impl Buf) -> Result<FdcGunMessage, io::Error> {
-    let shot = buf.get_u8();
-    let total_shots = buf.get_u8();
-    let ammunition = buf
-        .get_u8()
-        .try_into()
-        .map_err(|conv_err| io::Error::new(io::ErrorKind::InvalidData, conv_err))?;
-    let target_location = TargetLocation::deserialize(&mut buf);
+fn deserialize_fire_report(
+    version: ProtocolVersion,
+    buf: &mut impl Buf,
+) -> Result<FdcGunMessage, Error> {
+    let command_id = deserialize_command_id(version, buf)?;
+    let (shot, total_shots) = match version {
+        ProtocolVersion::V1 => {
+            ensure_remaining(buf, 2)?;
+            (buf.get_u8().into(), buf.get_u8().into())
+        }
+        ProtocolVersion::V2 | ProtocolVersion::V3 | ProtocolVersion::V4 => {
+            ensure_remaining(buf, 8)?;
+            (buf.get_u32(), buf.get_u32())
+        }
+    };
+    ensure_remaining(buf, 1)?;
+    let ammunition = Ammunition::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidAmmunition(conv_err.number))?;
+    let target_location = TargetLocation::deserialize(buf)?;
+    ensure_remaining(buf, 4)?;
     let time_to_target = buf.get_u32();
 
     Ok(FdcGunMessage::FireReport {
+        command_id,
         shot,
         total_shots,
         ammunition,
@@ -314,18 +643,190 @@ fn deserialize_fire_report(mut buf: impl Buf) -> Result<FdcGunMessage, io::Error
     })
 }
 
+/// Deserializes to a [`FdcGunMessage::Registration`]
+fn deserialize_registration(buf: &mut impl Buf) -> Result<FdcGunMessage, Error> {
+    ensure_remaining(buf, 4)?;
+    let gun_id = buf.get_u32();
+    let position = GunPosition::deserialize(buf)?;
+    let capabilities = Capabilities::deserialize(buf)?;
+
+    Ok(FdcGunMessage::Registration {
+        gun_id,
+        position,
+        capabilities,
+    })
+}
+
+/// Deserializes to a [`FdcGunMessage::RegistrationAck`]
+fn deserialize_registration_ack(buf: &mut impl Buf) -> Result<FdcGunMessage, Error> {
+    ensure_remaining(buf, 5)?;
+    let gun_id = buf.get_u32();
+    let accepted = match buf.get_u8() {
+        0x00 => false,
+        0x01 => true,
+        flag => return Err(Error::InvalidFlag(flag)),
+    };
+
+    Ok(FdcGunMessage::RegistrationAck { gun_id, accepted })
+}
+
+/// Deserializes to a [`FdcGunMessage::VersionOffer`]
+fn deserialize_version_offer(buf: &mut impl Buf) -> Result<FdcGunMessage, Error> {
+    ensure_remaining(buf, 1)?;
+    let max_version = buf.get_u8();
+
+    Ok(FdcGunMessage::VersionOffer { max_version })
+}
+
+/// Deserializes to a [`FdcGunMessage::VersionAccept`]
+fn deserialize_version_accept(buf: &mut impl Buf) -> Result<FdcGunMessage, Error> {
+    ensure_remaining(buf, 1)?;
+    let version = ProtocolVersion::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidVersion(conv_err.number))?;
+
+    Ok(FdcGunMessage::VersionAccept { version })
+}
+
+/// The command ID of a [`FdcGunMessage::FireCommand`] that carries none, as in versions before
+/// [`ProtocolVersion::V4`]
+///
+/// An FDC numbering its commands never gives out this ID, so a gun cannot tell commands carrying it apart.
+pub const NO_COMMAND_ID: u32 = 0;
+
+/// Deserializes to a [`FdcGunMessage::Malfunction`]
+fn deserialize_malfunction(
+    version: ProtocolVersion,
+    buf: &mut impl Buf,
+) -> Result<FdcGunMessage, Error> {
+    let command_id = deserialize_command_id(version, buf)?;
+    ensure_remaining(buf, 5)?;
+    let fault = Fault::try_from_primitive(buf.get_u8())
+        .map_err(|conv_err| Error::InvalidFault(conv_err.number))?;
+    let shot = buf.get_u32();
+
+    Ok(FdcGunMessage::Malfunction {
+        command_id,
+        fault,
+        shot,
+    })
+}
+
+/// Narrows a field to the 8 bits that `version` has room for.
+fn narrow(version: ProtocolVersion, field: &'static str, value: u32) -> Result<u8, Error> {
+    value.try_into().map_err(|_| Error::Unrepresentable {
+        field,
+        value,
+        version,
+    })
+}
+
+/// Versions of the FDC - Gun interface
+///
+/// A connection starts out speaking [`ProtocolVersion::V1`]. A gun that supports newer versions sends a
+/// [`FdcGunMessage::VersionOffer`] before registering, and both sides switch to the version the FDC picks in
+/// its [`FdcGunMessage::VersionAccept`]. The version messages themselves are laid out the same in every version.
+#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, PartialOrd, Ord)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum ProtocolVersion {
+    /// The original layout
+    V1 = 0x01,
+    /// Widens the shot counts of a [`FdcGunMessage::FireReport`] to 32 bits, matching the rounds of a
+    /// [`FdcGunMessage::FireCommand`]
+    V2 = 0x02,
+    /// Adds a [`Fuze`] to each [`FdcGunMessage::FireCommand`]
+    V3 = 0x03,
+    /// Adds a command ID to each [`FdcGunMessage::FireCommand`], and to the [`FdcGunMessage::ComplianceResponse`]
+    /// and [`FdcGunMessage::FireReport`]s answering it
+    V4 = 0x04,
+}
+
+// Not derived, since `num_enum` would then decode every unknown version byte as the default.
+#[allow(clippy::derivable_impls)]
+impl Default for ProtocolVersion {
+    fn default() -> Self {
+        Self::V1
+    }
+}
+
+impl ProtocolVersion {
+    /// The newest version this crate speaks
+    pub const LATEST: Self = Self::V4;
+
+    /// Picks the version to use with a peer that supports up to `max_version`.
+    ///
+    /// Returns [`None`] if the peer's versions and ours do not overlap.
+    #[must_use]
+    pub fn negotiate(max_version: u8) -> Option<Self> {
+        Self::try_from_primitive(max_version.min(Self::LATEST.into())).ok()
+    }
+
+    /// Whether a [`FdcGunMessage::FireCommand`] in this version can carry `fuze`.
+    ///
+    /// Versions before [`ProtocolVersion::V3`] have no room for a fuze, so only carry [`Fuze::PointDetonating`].
+    #[must_use]
+    pub fn carries(self, fuze: Fuze) -> bool {
+        self >= Self::V3 || fuze == Fuze::PointDetonating
+    }
+}
+
 /// Ammunition types
 #[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, Hash)]
 #[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum Ammunition {
     /// HE rounds
     HighExplosive = 0x00,
+    /// Smoke rounds, for screening
+    Smoke = 0x01,
+    /// Illumination rounds
+    Illumination = 0x02,
+    /// White Phosphorus (WP) rounds, for marking and incendiary effects
+    WhitePhosphorus = 0x03,
+    /// Dual-Purpose Improved Conventional Munitions (DPICM), carrying submunitions
+    Dpicm = 0x04,
+    /// Precision-guided rounds
+    PrecisionGuided = 0x05,
 }
 
-/// Gun status
-#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq)]
+/// Fuze types
+#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum Fuze {
+    /// Point Detonating (PD), bursting on impact
+    PointDetonating = 0x00,
+    /// Variable Time (VT), bursting at a set height above the target
+    VariableTime = 0x01,
+    /// Delay, bursting shortly after impact
+    Delay = 0x02,
+    /// Time, bursting a set time after firing
+    Time = 0x03,
+}
+
+/// Gun status, ordered from worst to best
+#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, PartialOrd, Ord)]
 #[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum Status {
     /// Non-operational if no ammunition or other mission-critical fault
@@ -336,10 +837,48 @@ pub enum Status {
     Operational = 0x02,
 }
 
+/// Gun faults, each value being the reason code sent in a [`FdcGunMessage::Malfunction`]
+#[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq, Eq, Hash)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+#[repr(u8)]
+pub enum Fault {
+    /// The round did not fire, and is being cleared from the tube
+    Misfire = 0x00,
+    /// The round did not fire, and the crew is waiting out a delayed ignition before clearing it
+    HangFire = 0x01,
+    /// The hydraulics failed, so the gun cannot be laid or absorb recoil
+    HydraulicFailure = 0x02,
+    /// The gun has none of the ammunition being fired left
+    OutOfAmmunition = 0x03,
+}
+
+impl Fault {
+    /// The best [`Status`] a gun with this fault can report, until the fault is cleared
+    ///
+    /// A gun that misfired or ran out of one ammunition can still fire once the tube is cleared or with other
+    /// ammunition, but one with a hang-fire or hydraulic failure cannot fire at all.
+    #[must_use]
+    pub fn status(self) -> Status {
+        match self {
+            Fault::Misfire | Fault::OutOfAmmunition => Status::PartialOperational,
+            Fault::HangFire | Fault::HydraulicFailure => Status::NonOperational,
+        }
+    }
+}
+
 /// A gun's aim
-#[derive(Debug, PartialEq)]
-#[cfg_attr(test, derive(Clone))]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 pub struct TargetLocation {
     /// Range in meters
     range: u32,
@@ -348,6 +887,28 @@ pub struct TargetLocation {
 }
 
 impl TargetLocation {
+    /// Constructs a new [`TargetLocation`]
+    ///
+    /// # Arguments
+    /// * `range` - Range in meters
+    /// * `direction` - Direction in mils
+    #[must_use]
+    pub fn new(range: u32, direction: u32) -> Self {
+        Self { range, direction }
+    }
+
+    /// Range in meters
+    #[must_use]
+    pub fn range(&self) -> u32 {
+        self.range
+    }
+
+    /// Direction in mils
+    #[must_use]
+    pub fn direction(&self) -> u32 {
+        self.direction
+    }
+
     /// Serializes a [`TargetLocation`] to the supplied buffer
     fn serialize(&self, buf: &mut impl BufMut) {
         buf.put_u32(self.range);
@@ -355,17 +916,90 @@ impl TargetLocation {
     }
 
     /// Deserializes a [`TargetLocation`] from the supplied buffer
-    fn deserialize(buf: &mut impl Buf) -> Self {
+    fn deserialize(buf: &mut impl Buf) -> Result<Self, Error> {
+        ensure_remaining(buf, 8)?;
         let range = buf.get_u32();
         let direction = buf.get_u32();
 
-        Self { range, direction }
+        Ok(Self { range, direction })
+    }
+}
+
+/// Where a gun is emplaced, as a grid position in meters
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct GunPosition {
+    /// Easting in meters
+    pub easting: u32,
+    /// Northing in meters
+    pub northing: u32,
+}
+
+impl GunPosition {
+    /// Serializes a [`GunPosition`] to the supplied buffer
+    fn serialize(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.easting);
+        buf.put_u32(self.northing);
+    }
+
+    /// Deserializes a [`GunPosition`] from the supplied buffer
+    fn deserialize(buf: &mut impl Buf) -> Result<Self, Error> {
+        ensure_remaining(buf, 8)?;
+        let easting = buf.get_u32();
+        let northing = buf.get_u32();
+
+        Ok(Self { easting, northing })
+    }
+}
+
+/// What a gun is able to do, as announced during registration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
+pub struct Capabilities {
+    /// Maximum range in meters
+    pub max_range: u32,
+    /// Sustained rate of fire in rounds per minute
+    pub rounds_per_minute: u8,
+}
+
+impl Capabilities {
+    /// Serializes a [`Capabilities`] to the supplied buffer
+    fn serialize(&self, buf: &mut impl BufMut) {
+        buf.put_u32(self.max_range);
+        buf.put_u8(self.rounds_per_minute);
+    }
+
+    /// Deserializes a [`Capabilities`] from the supplied buffer
+    fn deserialize(buf: &mut impl Buf) -> Result<Self, Error> {
+        ensure_remaining(buf, 5)?;
+        let max_range = buf.get_u32();
+        let rounds_per_minute = buf.get_u8();
+
+        Ok(Self {
+            max_range,
+            rounds_per_minute,
+        })
     }
 }
 
 /// Compliance types
 #[derive(Debug, Clone, Copy, IntoPrimitive, TryFromPrimitive, PartialEq)]
 #[cfg_attr(test, derive(Arbitrary))]
+#[cfg_attr(
+    feature = "serde",
+    derive(Serialize, Deserialize),
+    serde(rename_all = "snake_case")
+)]
 #[repr(u8)]
 pub enum Compliance {
     /// Indicates that the system cannot comply with the command
@@ -384,13 +1018,423 @@ mod tests {
 
     proptest! {
         #[test]
-        fn test_serialize_deserialize(message in any::<FdcGunMessage>()) {
+        fn test_serialize_deserialize(message in any::<FdcGunMessage>(), version in any::<ProtocolVersion>()) {
             let mut bytes = Vec::new();
-            message.serialize(&mut bytes).unwrap();
+            match message.serialize(version, &mut bytes) {
+                Ok(()) => {
+                    let output = FdcGunMessage::deserialize(version, bytes.as_slice()).unwrap();
 
-            let output = FdcGunMessage::deserialize(bytes.as_slice()).unwrap();
+                    assert_eq!(message, output);
+                }
+                // Only the V1 shot counts are narrower than their fields
+                Err(Error::Unrepresentable { version: ProtocolVersion::V1, field: "shot" | "total_shots", .. }) => {
+                    let too_wide = matches!(
+                        message,
+                        FdcGunMessage::FireReport { shot, total_shots, .. } if shot > 0xFF || total_shots > 0xFF
+                    );
+                    prop_assert!(too_wide);
+                }
+                // Fuzes other than Point Detonating only have room from V3
+                Err(Error::Unrepresentable { version: ProtocolVersion::V1 | ProtocolVersion::V2, field: "fuze", .. }) => {
+                    let fuzed = matches!(
+                        message,
+                        FdcGunMessage::FireCommand { fuze, .. } if fuze != Fuze::PointDetonating
+                    );
+                    prop_assert!(fuzed);
+                }
+                // Command IDs only have room from V4
+                Err(Error::Unrepresentable { version: ProtocolVersion::V1 | ProtocolVersion::V2 | ProtocolVersion::V3, field: "command_id", value, .. }) => {
+                    prop_assert_ne!(value, NO_COMMAND_ID);
+                }
+                Err(err) => panic!("unexpected error {err}"),
+            }
+        }
 
-            assert_eq!(message, output);
+        #[test]
+        fn test_version_messages_same_in_all_versions(
+            message in prop_oneof![
+                any::<u8>().prop_map(|max_version| FdcGunMessage::VersionOffer { max_version }),
+                any::<ProtocolVersion>().prop_map(|version| FdcGunMessage::VersionAccept { version }),
+            ]
+        ) {
+            let mut v1 = Vec::new();
+            message.serialize(ProtocolVersion::V1, &mut v1).unwrap();
+            let mut v2 = Vec::new();
+            message.serialize(ProtocolVersion::V2, &mut v2).unwrap();
+            let mut v3 = Vec::new();
+            message.serialize(ProtocolVersion::V3, &mut v3).unwrap();
+            let mut v4 = Vec::new();
+            message.serialize(ProtocolVersion::V4, &mut v4).unwrap();
+
+            prop_assert_eq!(&v1, &v2);
+            prop_assert_eq!(&v1, &v3);
+            prop_assert_eq!(&v1, &v4);
+        }
+
+        #[test]
+        fn test_negotiate(max_version in any::<u8>()) {
+            match ProtocolVersion::negotiate(max_version) {
+                Some(version) => {
+                    prop_assert!(u8::from(version) <= max_version);
+                    prop_assert!(version <= ProtocolVersion::LATEST);
+                }
+                None => prop_assert!(max_version < ProtocolVersion::V1.into()),
+            }
         }
+
+        #[test]
+        fn test_carries(version in any::<ProtocolVersion>(), fuze in any::<Fuze>(), target_location in any::<TargetLocation>()) {
+            let message = FdcGunMessage::FireCommand {
+                command_id: NO_COMMAND_ID,
+                rounds: 4,
+                ammunition: Ammunition::HighExplosive,
+                fuze,
+                target_location,
+            };
+            let mut bytes = Vec::new();
+
+            prop_assert_eq!(message.serialize(version, &mut bytes).is_ok(), version.carries(fuze));
+        }
+
+        #[cfg(feature = "serde")]
+        #[test]
+        fn test_serde(message in any::<FdcGunMessage>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: FdcGunMessage = serde_json::from_str(&json).unwrap();
+
+            prop_assert_eq!(message, verified);
+        }
+
+        #[test]
+        fn test_deserialize_truncated(message in any::<FdcGunMessage>(), cut in 1usize..32) {
+            let mut bytes = Vec::new();
+            message.serialize(ProtocolVersion::LATEST, &mut bytes).unwrap();
+            let cut = cut.min(bytes.len());
+            bytes.truncate(bytes.len() - cut);
+
+            prop_assert!(FdcGunMessage::deserialize(ProtocolVersion::LATEST, bytes.as_slice()).is_err());
+        }
+
+        #[test]
+        fn test_deserialize_trailing_bytes(message in any::<FdcGunMessage>(), extra in prop::collection::vec(any::<u8>(), 1..5)) {
+            let mut bytes = Vec::new();
+            message.serialize(ProtocolVersion::LATEST, &mut bytes).unwrap();
+            bytes.extend_from_slice(&extra);
+
+            // Extra bytes beyond the declared length
+            prop_assert!(FdcGunMessage::deserialize(ProtocolVersion::LATEST, bytes.as_slice()).is_err());
+
+            // Extra bytes within the declared length, but beyond what the message type consumes
+            let length = u32::from_be_bytes(bytes[..4].try_into().unwrap()) + extra.len() as u32;
+            bytes[..4].copy_from_slice(&length.to_be_bytes());
+            prop_assert!(FdcGunMessage::deserialize(ProtocolVersion::LATEST, bytes.as_slice()).is_err());
+        }
+
+        #[test]
+        fn test_deserialize_unknown_message_id(id in 0x0Du8.., version in any::<ProtocolVersion>()) {
+            let bytes = [0x00, 0x00, 0x00, 0x00, id];
+
+            prop_assert!(matches!(
+                FdcGunMessage::deserialize(version, bytes.as_slice()),
+                Err(Error::UnknownMessageId(unknown)) if unknown == id
+            ));
+        }
+
+        #[test]
+        fn test_deserialize_arbitrary_bytes(bytes in prop::collection::vec(any::<u8>(), 0..64), version in any::<ProtocolVersion>()) {
+            // Must return, never panic
+            let _ = FdcGunMessage::deserialize(version, bytes.as_slice());
+        }
+    }
+
+    #[test]
+    fn test_deserialize_error_classes() {
+        // ComplianceResponse with a bad compliance
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0x00, 0xFF];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::InvalidCompliance(0xFF))
+        ));
+
+        // FireCommand with a bad ammunition
+        let bytes = [
+            0x00, 0x00, 0x00, 0x0D, 0x05, 0x00, 0x00, 0x00, 0x04, 0xFF, 0x00, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::InvalidAmmunition(0xFF))
+        ));
+
+        // StatusReply declaring more than was given
+        let bytes = [0x00, 0x00, 0x00, 0x06, 0x03, 0x02, 0x00];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::Truncated { needed: 6, got: 2 })
+        ));
+
+        // StatusRequest with a body
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0x02, 0x00];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::TrailingBytes(1))
+        ));
+
+        // CheckFire with more data than declared
+        let bytes = [0x00, 0x00, 0x00, 0x00, 0x06, 0x00];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::LengthMismatch {
+                declared: 0,
+                got: 1
+            })
+        ));
+
+        // FireCommand with a bad fuze
+        let bytes = [
+            0x00, 0x00, 0x00, 0x0E, 0x05, 0x00, 0x00, 0x00, 0x04, 0x00, 0xFF, 0x00, 0x00, 0x00,
+            0x00, 0x00, 0x00, 0x00, 0x00,
+        ];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V3, bytes.as_slice()),
+            Err(Error::InvalidFuze(0xFF))
+        ));
+
+        // ResupplyAck counting the same ammunition twice
+        let bytes = [
+            0x00, 0x00, 0x00, 0x0A, 0x0C, 0x01, 0x00, 0x00, 0x00, 0x10, 0x01, 0x00, 0x00, 0x00,
+            0x20,
+        ];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::DuplicateAmmunition(Ammunition::Smoke))
+        ));
+
+        // Malfunction with a bad fault
+        let bytes = [
+            0x00, 0x00, 0x00, 0x09, 0x0A, 0x00, 0x00, 0x00, 0x01, 0xFF, 0x00, 0x00, 0x00, 0x02,
+        ];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V4, bytes.as_slice()),
+            Err(Error::InvalidFault(0xFF))
+        ));
+
+        // VersionAccept with an unknown version
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0x09, 0x05];
+        assert!(matches!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()),
+            Err(Error::InvalidVersion(0x05))
+        ));
+    }
+
+    #[test]
+    fn test_fire_report_layouts() {
+        let v1 = [
+            0x00, 0x00, 0x00, 0x0F, 0x01, 0x02, 0x04, 0x00, 0x00, 0x00, 0x0F, 0xA0, 0x00, 0x00,
+            0x06, 0x40, 0x00, 0x00, 0x34, 0x15,
+        ];
+        let v2 = [
+            0x00, 0x00, 0x00, 0x15, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x04, 0x00,
+            0x00, 0x00, 0x0F, 0xA0, 0x00, 0x00, 0x06, 0x40, 0x00, 0x00, 0x34, 0x15,
+        ];
+        let report = FdcGunMessage::FireReport {
+            command_id: NO_COMMAND_ID,
+            shot: 2,
+            total_shots: 4,
+            ammunition: Ammunition::HighExplosive,
+            target_location: TargetLocation::new(4000, 1600),
+            time_to_target: 13_333,
+        };
+
+        for (version, layout) in [
+            (ProtocolVersion::V1, &v1[..]),
+            (ProtocolVersion::V2, &v2[..]),
+        ] {
+            let mut bytes = Vec::new();
+            report.serialize(version, &mut bytes).unwrap();
+            assert_eq!(bytes, layout);
+            assert_eq!(FdcGunMessage::deserialize(version, layout).unwrap(), report);
+        }
+
+        // Each version rejects the other's layout
+        assert!(FdcGunMessage::deserialize(ProtocolVersion::V1, &v2[..]).is_err());
+        assert!(FdcGunMessage::deserialize(ProtocolVersion::V2, &v1[..]).is_err());
+    }
+
+    #[test]
+    fn test_fire_command_layouts() {
+        let v1 = [
+            0x00, 0x00, 0x00, 0x0D, 0x05, 0x00, 0x00, 0x00, 0x04, 0x01, 0x00, 0x00, 0x0F, 0xA0,
+            0x00, 0x00, 0x06, 0x40,
+        ];
+        let v3 = [
+            0x00, 0x00, 0x00, 0x0E, 0x05, 0x00, 0x00, 0x00, 0x04, 0x01, 0x00, 0x00, 0x00, 0x0F,
+            0xA0, 0x00, 0x00, 0x06, 0x40,
+        ];
+        let command = FdcGunMessage::FireCommand {
+            command_id: NO_COMMAND_ID,
+            rounds: 4,
+            ammunition: Ammunition::Smoke,
+            fuze: Fuze::PointDetonating,
+            target_location: TargetLocation::new(4000, 1600),
+        };
+
+        // The fuze has no room until V3
+        for (version, layout) in [
+            (ProtocolVersion::V1, &v1[..]),
+            (ProtocolVersion::V2, &v1[..]),
+            (ProtocolVersion::V3, &v3[..]),
+        ] {
+            let mut bytes = Vec::new();
+            command.serialize(version, &mut bytes).unwrap();
+            assert_eq!(bytes, layout);
+            assert_eq!(
+                FdcGunMessage::deserialize(version, layout).unwrap(),
+                command
+            );
+        }
+
+        // Earlier versions have nowhere to put any other fuze
+        let command = FdcGunMessage::FireCommand {
+            command_id: NO_COMMAND_ID,
+            rounds: 4,
+            ammunition: Ammunition::Smoke,
+            fuze: Fuze::VariableTime,
+            target_location: TargetLocation::new(4000, 1600),
+        };
+        for version in [ProtocolVersion::V1, ProtocolVersion::V2] {
+            assert!(matches!(
+                command.serialize(version, &mut Vec::new()),
+                Err(Error::Unrepresentable {
+                    field: "fuze",
+                    value: 0x01,
+                    version: unrepresentable_in
+                }) if unrepresentable_in == version
+            ));
+        }
+    }
+
+    #[test]
+    fn test_command_id_layouts() {
+        let command = FdcGunMessage::FireCommand {
+            command_id: 0x0102_0304,
+            rounds: 4,
+            ammunition: Ammunition::Smoke,
+            fuze: Fuze::VariableTime,
+            target_location: TargetLocation::new(4000, 1600),
+        };
+        let command_v4 = [
+            0x00, 0x00, 0x00, 0x12, 0x05, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x04, 0x01,
+            0x01, 0x00, 0x00, 0x0F, 0xA0, 0x00, 0x00, 0x06, 0x40,
+        ];
+        let response = FdcGunMessage::ComplianceResponse {
+            command_id: 0x0102_0304,
+            compliance: Compliance::WILLCO,
+        };
+        let response_v4 = [0x00, 0x00, 0x00, 0x05, 0x00, 0x01, 0x02, 0x03, 0x04, 0x02];
+        let malfunction = FdcGunMessage::Malfunction {
+            command_id: 0x0102_0304,
+            fault: Fault::HangFire,
+            shot: 3,
+        };
+        let malfunction_v4 = [
+            0x00, 0x00, 0x00, 0x09, 0x0A, 0x01, 0x02, 0x03, 0x04, 0x01, 0x00, 0x00, 0x00, 0x03,
+        ];
+        let report = FdcGunMessage::FireReport {
+            command_id: 0x0102_0304,
+            shot: 2,
+            total_shots: 4,
+            ammunition: Ammunition::HighExplosive,
+            target_location: TargetLocation::new(4000, 1600),
+            time_to_target: 13_333,
+        };
+        let report_v4 = [
+            0x00, 0x00, 0x00, 0x19, 0x01, 0x01, 0x02, 0x03, 0x04, 0x00, 0x00, 0x00, 0x02, 0x00,
+            0x00, 0x00, 0x04, 0x00, 0x00, 0x00, 0x0F, 0xA0, 0x00, 0x00, 0x06, 0x40, 0x00, 0x00,
+            0x34, 0x15,
+        ];
+
+        for (message, layout) in [
+            (&command, &command_v4[..]),
+            (&response, &response_v4[..]),
+            (&malfunction, &malfunction_v4[..]),
+            (&report, &report_v4[..]),
+        ] {
+            let mut bytes = Vec::new();
+            message.serialize(ProtocolVersion::V4, &mut bytes).unwrap();
+            assert_eq!(bytes, layout);
+            assert_eq!(
+                &FdcGunMessage::deserialize(ProtocolVersion::V4, layout).unwrap(),
+                message
+            );
+
+            // Earlier versions have nowhere to put the ID
+            assert!(matches!(
+                message.serialize(ProtocolVersion::V3, &mut Vec::new()),
+                Err(Error::Unrepresentable {
+                    field: "command_id",
+                    value: 0x0102_0304,
+                    version: ProtocolVersion::V3
+                })
+            ));
+        }
+
+        // Earlier versions decode every command as carrying no ID
+        let bytes = [0x00, 0x00, 0x00, 0x01, 0x00, 0x02];
+        assert_eq!(
+            FdcGunMessage::deserialize(ProtocolVersion::V3, bytes.as_slice()).unwrap(),
+            FdcGunMessage::ComplianceResponse {
+                command_id: NO_COMMAND_ID,
+                compliance: Compliance::WILLCO
+            }
+        );
+    }
+
+    #[test]
+    fn test_fault_status() {
+        // A faulted gun can never report better than partial operation.
+        for fault in [
+            Fault::Misfire,
+            Fault::HangFire,
+            Fault::HydraulicFailure,
+            Fault::OutOfAmmunition,
+        ] {
+            assert!(fault.status() < Status::Operational);
+        }
+        assert_eq!(Fault::HangFire.status(), Status::NonOperational);
+        assert_eq!(Fault::Misfire.status(), Status::PartialOperational);
+        assert!(Status::NonOperational < Status::PartialOperational);
+    }
+
+    #[test]
+    fn test_resupply_layout() {
+        let resupply = FdcGunMessage::Resupply {
+            rounds: HashMap::from([(Ammunition::HighExplosive, 40)]),
+        };
+        let layout = [0x00, 0x00, 0x00, 0x05, 0x0B, 0x00, 0x00, 0x00, 0x00, 0x28];
+
+        for version in [
+            ProtocolVersion::V1,
+            ProtocolVersion::V2,
+            ProtocolVersion::V3,
+        ] {
+            let mut bytes = Vec::new();
+            resupply.serialize(version, &mut bytes).unwrap();
+            assert_eq!(bytes, layout);
+            assert_eq!(
+                FdcGunMessage::deserialize(version, &layout[..]).unwrap(),
+                resupply
+            );
+        }
+
+        // An empty delivery is just the header
+        let bytes = [0x00, 0x00, 0x00, 0x00, 0x0C];
+        assert_eq!(
+            FdcGunMessage::deserialize(ProtocolVersion::V1, bytes.as_slice()).unwrap(),
+            FdcGunMessage::ResupplyAck {
+                rounds: HashMap::new()
+            }
+        );
     }
 }
@@ -0,0 +1,139 @@
+//! Provides a liveness tracker, for noticing a peer that has stopped answering without closing its connection.
+//!
+//! The tracker is driven by polls, such as a periodic [`FdcGunMessage::StatusRequest`](crate::FdcGunMessage::StatusRequest),
+//! each of which must be answered before the next is sent. A peer that misses too many polls in a row is lost, and
+//! is restored as soon as it answers again.
+
+/// A change in a peer's liveness
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LivenessChange {
+    /// The peer missed too many polls in a row
+    Lost,
+    /// A lost peer answered a poll
+    Restored,
+}
+
+/// Tracks whether a peer is answering its polls
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Liveness {
+    /// The polls in a row the peer may miss before it is lost
+    max_missed: u32,
+    /// The polls in a row the peer has missed
+    missed: u32,
+    /// Whether the last poll is still unanswered
+    awaiting_reply: bool,
+}
+
+impl Liveness {
+    /// Creates a tracker for a peer that is lost after missing `max_missed` polls in a row.
+    ///
+    /// A `max_missed` of 0 is treated as 1, since a poll cannot be missed until the next one is due.
+    #[must_use]
+    pub fn new(max_missed: u32) -> Self {
+        Self {
+            max_missed: max_missed.max(1),
+            missed: 0,
+            awaiting_reply: false,
+        }
+    }
+
+    /// Records that a poll is being sent, which is the deadline for the one before it.
+    ///
+    /// Returns [`LivenessChange::Lost`] if the peer has now missed too many polls in a row.
+    pub fn poll(&mut self) -> Option<LivenessChange> {
+        let was_lost = self.is_lost();
+        if self.awaiting_reply {
+            self.missed = self.missed.saturating_add(1);
+        }
+        self.awaiting_reply = true;
+
+        (!was_lost && self.is_lost()).then_some(LivenessChange::Lost)
+    }
+
+    /// Records that the peer answered.
+    ///
+    /// Returns [`LivenessChange::Restored`] if the peer was lost.
+    pub fn reply(&mut self) -> Option<LivenessChange> {
+        let was_lost = self.is_lost();
+        self.missed = 0;
+        self.awaiting_reply = false;
+
+        was_lost.then_some(LivenessChange::Restored)
+    }
+
+    /// Whether the peer has missed too many polls in a row
+    #[must_use]
+    pub fn is_lost(&self) -> bool {
+        self.missed >= self.max_missed
+    }
+
+    /// The polls in a row the peer has missed
+    #[must_use]
+    pub fn missed(&self) -> u32 {
+        self.missed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    fn test_lost_and_restored() {
+        let mut liveness = Liveness::new(3);
+
+        // Answered polls never count against the peer
+        for _ in 0..5 {
+            assert_eq!(liveness.poll(), None);
+            assert_eq!(liveness.reply(), None);
+        }
+
+        // The first poll has no deadline yet, and each one after it is the deadline for the last.
+        assert_eq!(liveness.poll(), None);
+        assert_eq!(liveness.poll(), None);
+        assert_eq!(liveness.poll(), None);
+        assert!(!liveness.is_lost());
+        assert_eq!(liveness.poll(), Some(LivenessChange::Lost));
+        assert!(liveness.is_lost());
+
+        // Lost is only reported once
+        assert_eq!(liveness.poll(), None);
+        assert_eq!(liveness.missed(), 4);
+
+        assert_eq!(liveness.reply(), Some(LivenessChange::Restored));
+        assert!(!liveness.is_lost());
+        assert_eq!(liveness.missed(), 0);
+    }
+
+    #[test]
+    fn test_zero_max_missed() {
+        let mut liveness = Liveness::new(0);
+
+        assert_eq!(liveness.poll(), None);
+        assert_eq!(liveness.poll(), Some(LivenessChange::Lost));
+    }
+
+    proptest! {
+        #[test]
+        fn test_lost_only_after_max_missed(max_missed in 1u32..10, replies in prop::collection::vec(any::<bool>(), 0..64)) {
+            let mut liveness = Liveness::new(max_missed);
+            // Polls sent since the last reply
+            let mut unanswered = 0;
+
+            for replied in replies {
+                unanswered += 1;
+                // Every unanswered poll but the latest has passed its deadline
+                let missed = unanswered - 1;
+                prop_assert_eq!(liveness.poll() == Some(LivenessChange::Lost), missed == max_missed);
+                prop_assert_eq!(liveness.is_lost(), missed >= max_missed);
+
+                if replied {
+                    prop_assert_eq!(liveness.reply() == Some(LivenessChange::Restored), missed >= max_missed);
+                    prop_assert!(!liveness.is_lost());
+                    unanswered = 0;
+                }
+            }
+        }
+    }
+}
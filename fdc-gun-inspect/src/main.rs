@@ -0,0 +1,379 @@
+//! An FDC - Gun capture inspector
+//!
+//! This crate provides a command-line tool for debugging the [`fdc_gun_comms`] interface.
+//! `decode` splits a raw byte stream into frames and prints each message, with its offset in the stream and any
+//! decoding error, as text or as JSON. `encode` turns JSON messages back into a byte stream, for crafting test inputs.
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use anyhow::{bail, Context, Result};
+use bytes::BytesMut;
+use clap::{Parser, Subcommand};
+use fdc_gun_comms::{
+    codec::{FdcGunMessageDecoder, FdcGunMessageEncoder},
+    FdcGunMessage, ProtocolVersion,
+};
+use serde::Serialize;
+use tokio_util::codec::{Decoder, Encoder};
+
+/// Decodes and encodes captures of the FDC - Gun interface
+#[derive(Debug, Parser)]
+#[clap(version, about)]
+struct Cli {
+    #[clap(subcommand)]
+    command: Command,
+}
+
+/// What to do with the input
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Splits a capture into frames, and prints each message
+    Decode {
+        /// The capture to read, or stdin if not given
+        input: Option<PathBuf>,
+        /// Reads the capture as hex digits, ignoring whitespace, rather than raw bytes
+        #[clap(long)]
+        hex: bool,
+        /// Prints each frame as a line of JSON, rather than text
+        #[clap(long)]
+        json: bool,
+        /// The protocol version the capture starts out in, until a Version Accept switches it
+        #[clap(long, default_value_t = 1)]
+        protocol_version: u8,
+    },
+    /// Encodes JSON messages, separated by whitespace, into a capture
+    Encode {
+        /// The messages to read, or stdin if not given
+        input: Option<PathBuf>,
+        /// Writes the capture as hex digits, rather than raw bytes
+        #[clap(long)]
+        hex: bool,
+        /// The protocol version the capture starts out in, until a Version Accept switches it
+        #[clap(long, default_value_t = 1)]
+        protocol_version: u8,
+    },
+}
+
+/// A frame of a capture, and what it decoded to
+#[derive(Debug, Serialize)]
+struct Frame {
+    /// Bytes into the capture that the frame starts
+    offset: usize,
+    /// Bytes in the frame
+    length: usize,
+    /// The message in the frame, if it decoded
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<FdcGunMessage>,
+    /// Why the frame did not decode, if it did not
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+/// Entry point for the capture inspector
+fn main() -> Result<()> {
+    match Cli::parse().command {
+        Command::Decode {
+            input,
+            hex,
+            json,
+            protocol_version,
+        } => {
+            let mut capture = read_input(input)?;
+            if hex {
+                capture = decode_hex(&capture)?;
+            }
+
+            let mut stdout = io::stdout().lock();
+            for frame in decode(&capture, parse_version(protocol_version)?) {
+                if json {
+                    writeln!(stdout, "{}", serde_json::to_string(&frame)?)?;
+                } else {
+                    match (&frame.message, &frame.error) {
+                        (Some(message), _) => writeln!(
+                            stdout,
+                            "{:#010x} {:>5} bytes  {:?}",
+                            frame.offset, frame.length, message
+                        )?,
+                        (None, error) => writeln!(
+                            stdout,
+                            "{:#010x} {:>5} bytes  error: {}",
+                            frame.offset,
+                            frame.length,
+                            error.as_deref().unwrap_or_default()
+                        )?,
+                    }
+                }
+            }
+        }
+        Command::Encode {
+            input,
+            hex,
+            protocol_version,
+        } => {
+            let messages = read_input(input)?;
+            let capture = encode(&messages, parse_version(protocol_version)?)?;
+
+            let mut stdout = io::stdout().lock();
+            if hex {
+                writeln!(stdout, "{}", hex::encode(capture))?;
+            } else {
+                stdout.write_all(&capture)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Reads all of `input`, or of stdin if there is none
+fn read_input(input: Option<PathBuf>) -> Result<Vec<u8>> {
+    match input {
+        Some(path) => fs::read(&path).with_context(|| format!("Cannot read {}", path.display())),
+        None => {
+            let mut bytes = Vec::new();
+            io::stdin().lock().read_to_end(&mut bytes)?;
+            Ok(bytes)
+        }
+    }
+}
+
+/// Reads hex digits, ignoring any whitespace between them
+fn decode_hex(text: &[u8]) -> Result<Vec<u8>> {
+    let digits: Vec<u8> = text
+        .iter()
+        .copied()
+        .filter(|byte| !byte.is_ascii_whitespace())
+        .collect();
+    hex::decode(digits).context("Invalid hex capture")
+}
+
+/// Looks up the protocol `version` given on the command line
+fn parse_version(version: u8) -> Result<ProtocolVersion> {
+    match ProtocolVersion::try_from(version) {
+        Ok(version) => Ok(version),
+        Err(_) => bail!(
+            "Unknown protocol version {}, the latest is {}",
+            version,
+            u8::from(ProtocolVersion::LATEST)
+        ),
+    }
+}
+
+/// Splits a `capture` into frames, decoding each from `version` on.
+///
+/// A frame whose contents fail to decode is reported and skipped, but decoding stops at a frame that cannot be
+/// split off, since the frames after it can no longer be found.
+fn decode(capture: &[u8], version: ProtocolVersion) -> Vec<Frame> {
+    let mut decoder = FdcGunMessageDecoder::new(version);
+    let mut src = BytesMut::from(capture);
+    let mut frames = Vec::new();
+    while !src.is_empty() {
+        let offset = capture.len() - src.len();
+        let result = decoder.decode(&mut src);
+        let length = capture.len() - src.len() - offset;
+        match result {
+            Ok(Some(message)) => {
+                if let FdcGunMessage::VersionAccept { version } = message {
+                    decoder.set_version(version);
+                }
+                frames.push(Frame {
+                    offset,
+                    length,
+                    message: Some(message),
+                    error: None,
+                });
+            }
+            Ok(None) => {
+                frames.push(Frame {
+                    offset,
+                    length: src.len(),
+                    message: None,
+                    error: Some("Capture ends partway through the frame".to_string()),
+                });
+                break;
+            }
+            Err(err) => {
+                let split_off = length > 0;
+                frames.push(Frame {
+                    offset,
+                    length,
+                    message: None,
+                    error: Some(err.to_string()),
+                });
+                if !split_off {
+                    break;
+                }
+            }
+        }
+    }
+    frames
+}
+
+/// Encodes JSON `messages` into a capture, from `version` on.
+///
+/// # Errors
+///
+/// Returns an error if a message is not valid JSON, or cannot be laid out in the protocol version in use.
+fn encode(messages: &[u8], version: ProtocolVersion) -> Result<Vec<u8>> {
+    let mut encoder = FdcGunMessageEncoder::new(version);
+    let mut dst = BytesMut::new();
+    for (index, message) in serde_json::Deserializer::from_slice(messages)
+        .into_iter::<FdcGunMessage>()
+        .enumerate()
+    {
+        let message = message.with_context(|| format!("Invalid message {}", index + 1))?;
+        let accepted = match message {
+            FdcGunMessage::VersionAccept { version } => Some(version),
+            _ => None,
+        };
+        encoder
+            .encode(message, &mut dst)
+            .with_context(|| format!("Cannot encode message {}", index + 1))?;
+        if let Some(version) = accepted {
+            encoder.set_version(version);
+        }
+    }
+    Ok(dst.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use fdc_gun_comms::{Ammunition, Fuze, TargetLocation, NO_COMMAND_ID};
+
+    /// The frames of a `capture` given as hex digits, as (offset, length, message) tuples
+    fn frames(
+        capture: &str,
+        version: ProtocolVersion,
+    ) -> Vec<(usize, usize, Option<FdcGunMessage>)> {
+        decode(&decode_hex(capture.as_bytes()).unwrap(), version)
+            .into_iter()
+            .map(|frame| (frame.offset, frame.length, frame.message))
+            .collect()
+    }
+
+    #[test]
+    fn test_decode_offsets() {
+        let capture = "00000000 02  00000001 09 02  00000000 06";
+
+        assert_eq!(
+            frames(capture, ProtocolVersion::V1),
+            [
+                (0, 5, Some(FdcGunMessage::StatusRequest)),
+                (
+                    5,
+                    6,
+                    Some(FdcGunMessage::VersionAccept {
+                        version: ProtocolVersion::V2
+                    })
+                ),
+                (11, 5, Some(FdcGunMessage::CheckFire)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_decode_skips_malformed_contents() {
+        // An unknown message ID, then a Version Accept for an unknown version, both framed correctly.
+        let capture = "00000000 FF  00000001 09 7F  00000000 02";
+
+        let frames = decode(
+            &decode_hex(capture.as_bytes()).unwrap(),
+            ProtocolVersion::V1,
+        );
+
+        assert_eq!(frames.len(), 3);
+        assert!(frames[..2]
+            .iter()
+            .all(|frame| frame.message.is_none() && frame.error.is_some()));
+        assert_eq!((frames[1].offset, frames[1].length), (5, 6));
+        assert_eq!((frames[2].offset, frames[2].length), (11, 5));
+        assert_eq!(frames[2].message, Some(FdcGunMessage::StatusRequest));
+    }
+
+    #[test]
+    fn test_decode_stops_at_broken_framing() {
+        // The capture ends partway through the second frame.
+        let truncated = "00000000 02  0000000D 05 00000004";
+        // The length marker of the second frame is too large to trust.
+        let too_large = "00000000 02  00FFFFFF 05  00000000 02";
+
+        for capture in [truncated, too_large] {
+            let frames = decode(
+                &decode_hex(capture.as_bytes()).unwrap(),
+                ProtocolVersion::V1,
+            );
+
+            assert_eq!(frames.len(), 2, "{}", capture);
+            assert_eq!(frames[0].message, Some(FdcGunMessage::StatusRequest));
+            assert_eq!(frames[1].offset, 5);
+            assert!(frames[1].message.is_none() && frames[1].error.is_some());
+        }
+    }
+
+    #[test]
+    fn test_decode_switches_version() {
+        // The same Fire Command, laid out before and after the Version Accept.
+        let capture = "0000000D 05 00000004 01 00000FA0 00000640  \
+                       00000001 09 03  \
+                       0000000E 05 00000004 01 02 00000FA0 00000640";
+        let fire_command = |fuze| FdcGunMessage::FireCommand {
+            command_id: NO_COMMAND_ID,
+            rounds: 4,
+            ammunition: Ammunition::Smoke,
+            fuze,
+            target_location: TargetLocation::new(4000, 1600),
+        };
+
+        let messages: Vec<_> = frames(capture, ProtocolVersion::V1)
+            .into_iter()
+            .map(|(_, _, message)| message)
+            .collect();
+
+        assert_eq!(
+            messages,
+            [
+                Some(fire_command(Fuze::PointDetonating)),
+                Some(FdcGunMessage::VersionAccept {
+                    version: ProtocolVersion::V3
+                }),
+                Some(fire_command(Fuze::Delay)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let messages = r#"
+            {"version_offer": {"max_version": 4}}
+            {"version_accept": {"version": "v4"}}
+            {"fire_command": {"command_id": 7, "rounds": 4, "ammunition": "high_explosive", "fuze": "variable_time", "target_location": {"range": 8000, "direction": 400}}}
+            "check_fire"
+        "#;
+
+        let capture = encode(messages.as_bytes(), ProtocolVersion::V1).unwrap();
+        let decoded: Vec<_> = decode(&capture, ProtocolVersion::V1)
+            .into_iter()
+            .map(|frame| frame.message.unwrap())
+            .collect();
+        let expected: Vec<FdcGunMessage> = serde_json::Deserializer::from_str(messages)
+            .into_iter()
+            .collect::<Result<_, _>>()
+            .unwrap();
+
+        assert_eq!(decoded, expected);
+    }
+
+    #[test]
+    fn test_encode_errors() {
+        // A Fire Command has no room for a fuze before version 3.
+        let fire_command = r#"{"fire_command": {"command_id": 0, "rounds": 4, "ammunition": "high_explosive", "fuze": "variable_time", "target_location": {"range": 8000, "direction": 400}}}"#;
+
+        assert!(encode(fire_command.as_bytes(), ProtocolVersion::V2).is_err());
+        assert!(encode(fire_command.as_bytes(), ProtocolVersion::V3).is_ok());
+        assert!(encode(b"{\"not_a_message\": {}}", ProtocolVersion::V1).is_err());
+    }
+}
@@ -0,0 +1,415 @@
+//! Contains the TCP server the FDC sim uses to talk to its Guns, as well as the per-gun sessions.
+//!
+//! Each Gun that connects may first agree on a [`ProtocolVersion`], and must then register with its [`GunId`], after which it is given a [`GunSession`],
+//! which is kept in the shared [`GunSessions`] table for as long as the connection is up. The state machine talks to a specific gun by sending a [`ToGun`] to the
+//! commhandler, and hears back from each gun through [`GunEvent`]s.
+//!
+//! Each Gun is polled with a [`FdcGunMessage::StatusRequest`] every [`POLL_INTERVAL`], and is reported lost if it misses
+//! [`MAX_MISSED_POLLS`] in a row, even if its connection is still up.
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{bail, Result};
+use fdc_gun_comms::{
+    codec::{FdcGunMessageDecoder, FdcGunMessageEncoder},
+    error::Error,
+    liveness::{Liveness, LivenessChange},
+    Ammunition, Capabilities, FdcGunMessage, GunPosition, ProtocolVersion, Status,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpListener, TcpStream,
+    },
+    sync::mpsc::{self, UnboundedReceiver, UnboundedSender},
+    time::Duration,
+    try_join,
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::{debug, info, trace, warn};
+
+/// How often each Gun is polled for its status, which is also how long it has to answer.
+//TODO: Get from config
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(2);
+
+/// The polls in a row a Gun may miss before it is lost.
+//TODO: Get from config
+pub(crate) const MAX_MISSED_POLLS: u32 = 3;
+
+/// Identifier a Gun registers with
+pub(crate) type GunId = u32;
+
+/// Table of every connected Gun, shared between the commhandler and the state machine
+pub(crate) type GunSessions = Arc<Mutex<HashMap<GunId, GunSession>>>;
+
+/// The last status a Gun reported with a [`FdcGunMessage::StatusReply`]
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct GunStatus {
+    /// High-level status
+    pub(crate) status: Status,
+    /// Rounds available
+    pub(crate) rounds: HashMap<Ammunition, u32>,
+}
+
+/// A connected Gun
+#[derive(Debug)]
+pub(crate) struct GunSession {
+    /// The address the Gun connected from
+    pub(crate) addr: SocketAddr,
+    /// The protocol version agreed with the Gun
+    pub(crate) version: ProtocolVersion,
+    /// Where the Gun is emplaced
+    pub(crate) position: GunPosition,
+    /// What the Gun registered as able to do
+    pub(crate) capabilities: Capabilities,
+    /// The last status reported by the Gun, if any
+    pub(crate) last_status: Option<GunStatus>,
+    /// Whether the Gun is answering its status polls
+    pub(crate) liveness: Liveness,
+    /// The send side of the Gun's send loop
+    to_gun_tx: UnboundedSender<FdcGunMessage>,
+}
+
+/// A message from the state machine for a specific Gun
+#[derive(Debug)]
+pub(crate) struct ToGun {
+    /// The Gun to send to
+    pub(crate) gun: GunId,
+    /// The message to send
+    pub(crate) message: FdcGunMessage,
+}
+
+/// Events from the Guns, for the state machine
+#[derive(Debug)]
+pub(crate) enum GunEvent {
+    /// A Gun registered with the FDC
+    Connected(GunId),
+    /// A Gun sent a message to the FDC
+    Message {
+        /// The Gun that sent the message
+        gun: GunId,
+        /// The message from the Gun
+        message: FdcGunMessage,
+    },
+    /// A Gun stopped answering its status polls, though its connection is still up
+    Lost(GunId),
+    /// A lost Gun answered a status poll again
+    Restored(GunId),
+    /// A Gun's connection closed
+    Disconnected(GunId),
+}
+
+/// Provides the TCP server for the Guns, accepting connections and routing messages from `to_gun_rx` to the right Gun.
+/// Everything received from any Gun is placed in `from_gun_tx`.
+pub(crate) async fn fdc_gun_commhandler_loop(
+    to_gun_rx: UnboundedReceiver<ToGun>,
+    from_gun_tx: UnboundedSender<GunEvent>,
+    sessions: GunSessions,
+) -> Result<()> {
+    let listener = TcpListener::bind("127.0.0.1:49154").await?;
+    debug!("Listening on {}", listener.local_addr()?);
+
+    // Spin off the listener
+    let accept_handle = {
+        let sessions = Arc::clone(&sessions);
+        tokio::task::Builder::new()
+            .name("accept loop")
+            .spawn(async move { accept_loop(listener, from_gun_tx, sessions).await })
+    };
+
+    // Spin off the router
+    let route_handle = tokio::task::Builder::new()
+        .name("route loop")
+        .spawn(async move { route_loop(to_gun_rx, sessions).await });
+
+    let (left, right) = try_join!(accept_handle, route_handle)?;
+    left?;
+    right?;
+
+    Ok(())
+}
+
+/// Accept loop, for starting a session for each Gun that connects
+async fn accept_loop(
+    listener: TcpListener,
+    from_gun_tx: UnboundedSender<GunEvent>,
+    sessions: GunSessions,
+) -> Result<()> {
+    trace!("Started the accept loop");
+    loop {
+        let (stream, addr) = listener.accept().await?;
+        info!("Connection from {}", addr);
+
+        let from_gun_tx = from_gun_tx.clone();
+        let sessions = Arc::clone(&sessions);
+        tokio::task::Builder::new()
+            .name("gun session")
+            .spawn(async move {
+                if let Err(err) = session_loop(stream, addr, &from_gun_tx, &sessions).await {
+                    warn!("Session for {} ended with an error: {}", addr, err);
+                }
+            });
+    }
+}
+
+/// Route loop, for handing messages from the state machine to the right Gun session
+async fn route_loop(mut to_gun_rx: UnboundedReceiver<ToGun>, sessions: GunSessions) -> Result<()> {
+    trace!("Started the route loop");
+    while let Some(ToGun { gun, message }) = to_gun_rx.recv().await {
+        match sessions.lock().unwrap().get(&gun) {
+            Some(session) => {
+                if session.to_gun_tx.send(message).is_err() {
+                    warn!("Gun {} is disconnecting, dropping message", gun);
+                }
+            }
+            None => warn!("No session for Gun {}, dropping {:?}", gun, message),
+        }
+    }
+    trace!("Leaving the route loop");
+    Ok(())
+}
+
+/// Session loop for a single Gun, running until the connection closes
+///
+/// The Gun may offer a protocol version first, otherwise [`ProtocolVersion::V1`] is used.
+/// The Gun must register before anything else is exchanged. A Gun whose identifier is already in use is rejected.
+async fn session_loop(
+    stream: TcpStream,
+    addr: SocketAddr,
+    from_gun_tx: &UnboundedSender<GunEvent>,
+    sessions: &GunSessions,
+) -> Result<()> {
+    let (reader, writer) = stream.into_split();
+    let mut reader = FramedRead::new(reader, FdcGunMessageDecoder::default());
+    let mut writer = FramedWrite::new(writer, FdcGunMessageEncoder::default());
+
+    let mut first = reader.next().await.transpose()?;
+    if let Some(FdcGunMessage::VersionOffer { max_version }) = first {
+        let version = match ProtocolVersion::negotiate(max_version) {
+            Some(version) => version,
+            None => bail!(
+                "{} offered unsupported protocol version {}",
+                addr,
+                max_version
+            ),
+        };
+        writer
+            .send(FdcGunMessage::VersionAccept { version })
+            .await?;
+        reader.decoder_mut().set_version(version);
+        writer.encoder_mut().set_version(version);
+        debug!("Agreed on {:?} with {}", version, addr);
+
+        first = reader.next().await.transpose()?;
+    }
+
+    let (gun, position, capabilities) = match first {
+        Some(FdcGunMessage::Registration {
+            gun_id,
+            position,
+            capabilities,
+        }) => (gun_id, position, capabilities),
+        Some(message) => bail!("Expected a Registration from {}, got {:?}", addr, message),
+        None => bail!("{} closed the connection before registering", addr),
+    };
+
+    let (to_gun_tx, to_gun_rx) = mpsc::unbounded_channel();
+    let accepted = match sessions.lock().unwrap().entry(gun) {
+        Entry::Occupied(_) => false,
+        Entry::Vacant(entry) => {
+            entry.insert(GunSession {
+                addr,
+                version: reader.decoder().version(),
+                position,
+                capabilities,
+                last_status: None,
+                liveness: Liveness::new(MAX_MISSED_POLLS),
+                to_gun_tx: to_gun_tx.clone(),
+            });
+            true
+        }
+    };
+    writer
+        .send(FdcGunMessage::RegistrationAck {
+            gun_id: gun,
+            accepted,
+        })
+        .await?;
+    if !accepted {
+        bail!("Gun {} is already registered, rejected {}", gun, addr);
+    }
+    info!("Gun {} registered from {}", gun, addr);
+
+    from_gun_tx.send(GunEvent::Connected(gun))?;
+
+    // The poll side finishes once the session is removed from the table, but the session ends if either the send or receive side fails.
+    let mut send_handle = tokio::task::Builder::new()
+        .name("gun send loop")
+        .spawn(async move { send_loop(gun, to_gun_rx, writer).await });
+    // The first poll goes out right away, so the session is populated before any fire commands.
+    let poll_handle = {
+        let from_gun_tx = from_gun_tx.clone();
+        let sessions = Arc::clone(sessions);
+        tokio::task::Builder::new()
+            .name("gun poll loop")
+            .spawn(async move { poll_loop(gun, to_gun_tx, from_gun_tx, sessions).await })
+    };
+
+    let result = tokio::select! {
+        result = recv_loop(gun, reader, from_gun_tx, sessions) => result,
+        result = &mut send_handle => result.map_err(anyhow::Error::from).and_then(|result| result),
+    };
+    send_handle.abort();
+    poll_handle.abort();
+
+    sessions.lock().unwrap().remove(&gun);
+    info!("Gun {} disconnected", gun);
+    // The state machine may already be gone if the FDC is shutting down.
+    let _ = from_gun_tx.send(GunEvent::Disconnected(gun));
+
+    result
+}
+
+/// Receive loop, for handling receipt from a single Gun over TCP
+async fn recv_loop(
+    gun: GunId,
+    mut gun_reader: FramedRead<OwnedReadHalf, FdcGunMessageDecoder>,
+    from_gun_tx: &UnboundedSender<GunEvent>,
+    sessions: &GunSessions,
+) -> Result<()> {
+    trace!("Started the recv loop for Gun {}", gun);
+    while let Some(message) = gun_reader.next().await {
+        let message = match message {
+            Ok(message) => message,
+            // The whole frame was taken off the stream before its contents were read, so the stream is still in sync.
+            Err(
+                err @ (Error::UnknownMessageId(_)
+                | Error::InvalidAmmunition(_)
+                | Error::InvalidFuze(_)
+                | Error::InvalidFault(_)
+                | Error::InvalidStatus(_)
+                | Error::InvalidCompliance(_)
+                | Error::InvalidVersion(_)
+                | Error::InvalidFlag(_)
+                | Error::DuplicateAmmunition(_)
+                | Error::Truncated { .. }
+                | Error::LengthMismatch { .. }
+                | Error::TrailingBytes(_)),
+            ) => {
+                warn!("Dropping a malformed message from Gun {}: {}", gun, err);
+                // The reader yields a single `None` after a decoder error before it resumes reading.
+                gun_reader.next().await;
+                continue;
+            }
+            // A frame too large to take off the stream, or a failed read, leaves nothing to resume from.
+            Err(err) => return Err(err.into()),
+        };
+        debug!("Received {:?} from Gun {}", message, gun);
+
+        if let FdcGunMessage::StatusReply { status, rounds } = &message {
+            let change = sessions.lock().unwrap().get_mut(&gun).and_then(|session| {
+                session.last_status = Some(GunStatus {
+                    status: *status,
+                    rounds: rounds.clone(),
+                });
+                session.liveness.reply()
+            });
+            if change == Some(LivenessChange::Restored) {
+                info!("Gun {} is answering again", gun);
+                from_gun_tx.send(GunEvent::Restored(gun))?;
+            }
+        }
+        // The counts are current, though the status waits for the next poll.
+        if let FdcGunMessage::ResupplyAck { rounds } = &message {
+            if let Some(status) = sessions
+                .lock()
+                .unwrap()
+                .get_mut(&gun)
+                .and_then(|session| session.last_status.as_mut())
+            {
+                status.rounds = rounds.clone();
+            }
+        }
+
+        from_gun_tx.send(GunEvent::Message { gun, message })?;
+    }
+    trace!("Leaving the recv loop for Gun {}", gun);
+    Ok(())
+}
+
+/// Poll loop, for asking a single Gun for its status every [`POLL_INTERVAL`] and noticing when it stops answering
+async fn poll_loop(
+    gun: GunId,
+    to_gun_tx: UnboundedSender<FdcGunMessage>,
+    from_gun_tx: UnboundedSender<GunEvent>,
+    sessions: GunSessions,
+) -> Result<()> {
+    trace!("Started the poll loop for Gun {}", gun);
+    let mut interval = tokio::time::interval(POLL_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let change = match sessions.lock().unwrap().get_mut(&gun) {
+            Some(session) => session.liveness.poll(),
+            None => break,
+        };
+        if change == Some(LivenessChange::Lost) {
+            warn!(
+                "Gun {} missed {} status polls in a row, marking it lost",
+                gun, MAX_MISSED_POLLS
+            );
+            from_gun_tx.send(GunEvent::Lost(gun))?;
+        }
+
+        if to_gun_tx.send(FdcGunMessage::StatusRequest).is_err() {
+            break;
+        }
+    }
+    trace!("Leaving the poll loop for Gun {}", gun);
+    Ok(())
+}
+
+/// Send loop, for sending data to a single Gun over TCP
+///
+/// A message that cannot be encoded for the Gun is dropped, but any other failure ends the loop, and with it the Gun's session.
+async fn send_loop(
+    gun: GunId,
+    mut to_gun_rx: UnboundedReceiver<FdcGunMessage>,
+    mut gun_writer: FramedWrite<OwnedWriteHalf, FdcGunMessageEncoder>,
+) -> Result<()> {
+    trace!("Started the send loop for Gun {}", gun);
+    while let Some(message_to_gun) = to_gun_rx.recv().await {
+        debug!("Sending {:?}", message_to_gun);
+        match message_to_gun {
+            FdcGunMessage::StatusRequest
+            | FdcGunMessage::FireCommand { .. }
+            | FdcGunMessage::CheckFire
+            | FdcGunMessage::Resupply { .. }
+            | FdcGunMessage::RegistrationAck { .. } => {
+                match gun_writer.send(message_to_gun).await {
+                    Ok(()) => (),
+                    Err(err @ (Error::Unrepresentable { .. } | Error::FrameTooLarge(_))) => {
+                        warn!(
+                            "Dropped a message that cannot be encoded for Gun {}: {}",
+                            gun, err
+                        )
+                    }
+                    Err(err) => {
+                        warn!("Failed to send to Gun {}: {}", gun, err);
+                        return Err(err.into());
+                    }
+                }
+            }
+            _ => panic!(
+                "Unsupported message was sent for transmission to a Gun: {:?}",
+                message_to_gun
+            ),
+        }
+    }
+    trace!("Leaving the send loop for Gun {}", gun);
+    Ok(())
+}
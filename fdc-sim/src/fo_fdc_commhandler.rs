@@ -1,60 +1,240 @@
 //! Contains the message types that the FDC sim can send/receive, as well as the communication tasks (send and receive)
 //!
-use std::sync::Arc;
+//! The FDC listens for any number of FOs on one socket. Each FO is kept in the [`FoDirectory`] by its callsign, with the
+//! address to answer it at, which is the `response_addr` of its Request for Fire, or else the address it sent from.
+//! The state machine addresses each message to an FO by sending a [`ToFo`] to the commhandler.
+//!
+//! Every message sent to an FO is held by that FO's [`Delivery`] tracker until the FO answers it, and is resent every
+//! [`RETRANSMIT_TIMEOUT`] until then. A message still unanswered after [`RETRY_LIMIT`] resends, or when the FO starts
+//! over, is reported to the state machine as a [`FoEvent::DeliveryFailed`].
+//!
+//! Messages travel in [`Envelope`]s, numbered in sequence for each FO, so that a message received twice is only passed
+//! to the state machine once. Should an FO send a message again, the answer already sent to it is sent again in its
+//! place. Envelopes are passed to the state machine whole, so it can tell which FO, and which mission, a message is for.
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    io::ErrorKind,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use fo_fdc_comms::FoFdcMessage;
+use fo_fdc_comms::{
+    correction::Correction,
+    delivery::{Delivery, Overdue},
+    envelope::{Arrival, Envelope, Inbox, Outbox},
+    message_to_observer::TargetNumber,
+    FoFdcMessage,
+};
 use tokio::{
     net::UdpSocket,
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
     try_join,
 };
-use tracing::{debug, trace};
+use tracing::{debug, error, info, trace, warn};
+
+/// The address the FDC listens on for its FOs.
+//TODO: Get from config
+const FDC_ADDR: &str = "127.0.0.1:49153";
+
+/// How long the FO has to answer a message before it is resent.
+//TODO: Get from config
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many times a message is resent before it is given up.
+//TODO: Get from config
+const RETRY_LIMIT: u32 = 3;
+
+/// The callsign the FDC seals its envelopes with.
+//TODO: Get from config
+const CALLSIGN: &str = "FDC";
+
+/// How often the held messages are checked for overdue answers
+const RETRANSMIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// An FO the FDC has heard from
+#[derive(Debug)]
+struct Observer {
+    /// The address to send the FO's messages to
+    addr: SocketAddr,
+    /// Seals the messages sent to the FO, in a sequence of their own
+    outbox: Outbox,
+    /// The messages sent to the FO that it has yet to answer, and the answers sent to it
+    delivery: Delivery,
+}
 
-/// Provides a reader/writer loop, sending data from `to_fo_rx` to the FO over UDP,
-/// and placing data received from the FO in the `from_fo_tx`.
+impl Observer {
+    /// An FO at `addr` that has yet to be sent anything
+    fn new(addr: SocketAddr) -> Self {
+        Self {
+            addr,
+            outbox: Outbox::new(CALLSIGN),
+            delivery: Delivery::new(RETRANSMIT_TIMEOUT, RETRY_LIMIT),
+        }
+    }
+}
+
+/// Table of every FO the FDC has heard from, by callsign, shared between the commhandler tasks
+type FoDirectory = Arc<Mutex<HashMap<String, Observer>>>;
+
+/// Events from the FO, for the state machine
+#[derive(Debug)]
+pub(crate) enum FoEvent {
+    /// The FO sent a message to the FDC
+    Message(Envelope),
+    /// The FO never answered a message, even after it was resent [`RETRY_LIMIT`] times, or started over before it did
+    DeliveryFailed(Envelope),
+}
+
+/// A message for an FO, from the state machine
+#[derive(Debug)]
+pub(crate) struct ToFo {
+    /// The callsign of the FO to send to
+    pub(crate) observer: String,
+    /// The target number of the fire mission the message is for, if it has one
+    pub(crate) mission: Option<TargetNumber>,
+    /// The message to send
+    pub(crate) message: FoFdcMessage,
+}
+
+/// Provides a reader/writer loop, sending data from `to_fo_rx` to each FO over UDP,
+/// and placing data received from the FOs, and messages an FO never answered, in the `from_fo_tx`.
 pub(crate) async fn fo_fdc_commhandler_loop(
-    to_fo_rx: UnboundedReceiver<FoFdcMessage>,
-    from_fo_tx: UnboundedSender<FoFdcMessage>,
+    to_fo_rx: UnboundedReceiver<ToFo>,
+    from_fo_tx: UnboundedSender<FoEvent>,
 ) -> Result<()> {
-    let socket = UdpSocket::bind("127.0.0.1:49153").await?;
-    socket.connect("127.0.0.1:49152").await?;
+    let socket = UdpSocket::bind(FDC_ADDR).await?;
     debug!("Bound to {}", socket.local_addr()?);
 
+    let directory = FoDirectory::default();
+
     // Spin off listener thread
     let socket = Arc::new(socket);
     let recv_handle = {
         let socket = Arc::clone(&socket);
+        let directory = Arc::clone(&directory);
+        let from_fo_tx = from_fo_tx.clone();
         tokio::task::Builder::new()
             .name("receive loop")
-            .spawn(async move { recv_loop(from_fo_tx, socket).await })
+            .spawn(async move { recv_loop(from_fo_tx, socket, directory).await })
+    };
+
+    // Spin off retransmit thread
+    let retransmit_handle = {
+        let socket = Arc::clone(&socket);
+        let directory = Arc::clone(&directory);
+        tokio::task::Builder::new()
+            .name("retransmit loop")
+            .spawn(async move { retransmit_loop(from_fo_tx, socket, directory).await })
     };
 
     // Spin off writer thread
     let send_handle = tokio::task::Builder::new()
         .name("send loop")
-        .spawn(async move { send_loop(to_fo_rx, socket).await });
+        .spawn(async move { send_loop(to_fo_rx, socket, directory).await });
 
-    let (left, right) = try_join!(recv_handle, send_handle)?;
-    left?;
-    right?;
+    let (recv, retransmit, send) = try_join!(recv_handle, retransmit_handle, send_handle)?;
+    recv?;
+    retransmit?;
+    send?;
 
     Ok(())
 }
 
 /// Receive loop, for handling receipt over UDP
 async fn recv_loop(
-    from_fo_tx: UnboundedSender<FoFdcMessage>,
+    from_fo_tx: UnboundedSender<FoEvent>,
     fo_socket: Arc<UdpSocket>,
+    directory: FoDirectory,
 ) -> Result<()> {
     let mut buffer = vec![0; 24 * 1024];
+    let mut inbox = Inbox::new();
     trace!("Started the recv loop");
     loop {
-        match fo_socket.recv(&mut buffer).await {
-            Ok(bytes_read) => {
-                let value: FoFdcMessage = serde_json::from_slice(&buffer[..bytes_read])?;
-                debug!("Received {:?}", value);
-                from_fo_tx.send(value)?;
+        match fo_socket.recv_from(&mut buffer).await {
+            Ok((bytes_read, source)) => {
+                // Anyone may send to the socket, so a datagram that is not an envelope is dropped rather than fatal.
+                let envelope: Envelope = match serde_json::from_slice(&buffer[..bytes_read]) {
+                    Ok(envelope) => envelope,
+                    Err(err) => {
+                        warn!(
+                            "Dropping a datagram from {} that is not an envelope: {}",
+                            source, err
+                        );
+                        continue;
+                    }
+                };
+                debug!("Received {:?} from {}", envelope, source);
+                learn_addr(&directory, &envelope, source);
+                match inbox.arrive(&envelope) {
+                    Arrival::New { missed: 0 } => (),
+                    Arrival::New { missed } => warn!(
+                        "Missed {} messages from {} before message {}",
+                        missed, envelope.src, envelope.sequence
+                    ),
+                    Arrival::Late => debug!(
+                        "Message {} from {} arrived late",
+                        envelope.sequence, envelope.src
+                    ),
+                    Arrival::Restarted => {
+                        info!("{} started over, resequencing", envelope.src);
+                        // Whatever was sent to the FO before it started over is for missions it no longer knows of.
+                        let given_up = directory
+                            .lock()
+                            .unwrap()
+                            .get_mut(&envelope.src)
+                            .map(|observer| observer.delivery.forget(&envelope.src))
+                            .unwrap_or_default();
+                        for given_up in given_up {
+                            from_fo_tx.send(FoEvent::DeliveryFailed(given_up))?;
+                        }
+                    }
+                    Arrival::Stale => {
+                        debug!(
+                            "Dropping message {} from {}, sent before it started over",
+                            envelope.sequence, envelope.src
+                        );
+                        continue;
+                    }
+                    Arrival::Duplicate => {
+                        // An answer sent again, as what it answers was resent, may still be the first to release it.
+                        let answer =
+                            directory
+                                .lock()
+                                .unwrap()
+                                .get_mut(&envelope.src)
+                                .and_then(|observer| {
+                                    observer.delivery.received(&envelope);
+                                    observer
+                                        .delivery
+                                        .answer_to(&envelope)
+                                        .map(|answer| (observer.addr, answer.clone()))
+                                });
+                        match answer {
+                            Some((addr, answer)) => {
+                                warn!(
+                                    "Received message {} from {} again, resending our answer",
+                                    envelope.sequence, envelope.src
+                                );
+                                send_to_fo(&fo_socket, &serde_json::to_vec(&answer)?, addr).await?;
+                            }
+                            None => debug!(
+                                "Dropping message {} from {}, already received",
+                                envelope.sequence, envelope.src
+                            ),
+                        }
+                        continue;
+                    }
+                }
+                if let Some(observer) = directory.lock().unwrap().get_mut(&envelope.src) {
+                    observer.delivery.received(&envelope);
+                }
+                from_fo_tx.send(FoEvent::Message(envelope))?;
+            }
+            // An answer may yet come once the FO is listening, and any message it missed is resent.
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                warn!("An FO is not listening")
             }
             Err(err) => return Err(err.into()),
         };
@@ -64,31 +244,148 @@ async fn recv_loop(
     // Ok(())
 }
 
+/// Records where to answer the FO that sent `envelope` from `source`.
+///
+/// An FO heard from for the first time is answered at the address it sent from, unless it says otherwise. An FO already
+/// known keeps its address until its next Request for Fire.
+fn learn_addr(directory: &FoDirectory, envelope: &Envelope, source: SocketAddr) {
+    let requested = match &envelope.message {
+        FoFdcMessage::RequestForFire(rff)
+        | FoFdcMessage::Correction(Correction::RequestForFire(rff)) => {
+            Some(rff.response_addr.unwrap_or(source))
+        }
+        _ => None,
+    };
+    match directory.lock().unwrap().entry(envelope.src.clone()) {
+        Entry::Occupied(mut entry) => {
+            if let Some(addr) = requested.filter(|addr| *addr != entry.get().addr) {
+                info!("{} now answers at {}", envelope.src, addr);
+                entry.get_mut().addr = addr;
+            }
+        }
+        Entry::Vacant(entry) => {
+            let addr = requested.unwrap_or(source);
+            info!("{} is now known, answering at {}", envelope.src, addr);
+            entry.insert(Observer::new(addr));
+        }
+    }
+}
+
+/// Retransmit loop, for resending each message an FO has not answered in time, and reporting those it never answers
+async fn retransmit_loop(
+    from_fo_tx: UnboundedSender<FoEvent>,
+    fo_socket: Arc<UdpSocket>,
+    directory: FoDirectory,
+) -> Result<()> {
+    trace!("Started the retransmit loop");
+    let mut interval = tokio::time::interval(RETRANSMIT_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let now = Instant::now();
+        let overdue: Vec<_> = directory
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .flat_map(|(callsign, observer)| {
+                let addr = observer.addr;
+                observer
+                    .delivery
+                    .poll(now)
+                    .into_iter()
+                    .map(move |overdue| (callsign.clone(), addr, overdue))
+            })
+            .collect();
+        for (callsign, addr, overdue) in overdue {
+            match overdue {
+                Overdue::Resend(envelope) => {
+                    warn!(
+                        "{} has not answered message {}, resending {:?}",
+                        callsign, envelope.sequence, envelope.message
+                    );
+                    let bytes = serde_json::to_vec(&envelope)?;
+                    send_to_fo(&fo_socket, &bytes, addr).await?;
+                }
+                Overdue::Failed(envelope) => {
+                    error!(
+                        "{} never answered message {}, giving up after {} resends of {:?}",
+                        callsign, envelope.sequence, RETRY_LIMIT, envelope.message
+                    );
+                    from_fo_tx.send(FoEvent::DeliveryFailed(envelope))?;
+                }
+            }
+        }
+    }
+}
+
 /// Send loop, for sending data over UDP
 async fn send_loop(
-    mut to_fo_rx: UnboundedReceiver<FoFdcMessage>,
+    mut to_fo_rx: UnboundedReceiver<ToFo>,
     fo_socket: Arc<UdpSocket>,
+    directory: FoDirectory,
 ) -> Result<()> {
     trace!("Started the send loop");
-    while let Some(message_to_fo) = to_fo_rx.recv().await {
-        debug!("Sending {:?}", message_to_fo);
-        let bytes = match message_to_fo {
+    while let Some(ToFo {
+        observer,
+        mission,
+        message,
+    }) = to_fo_rx.recv().await
+    {
+        debug!("Sending {:?} to {}", message, observer);
+        match message {
             FoFdcMessage::RequestForFireConfirm(..)
             | FoFdcMessage::MessageToObserver(..)
+            | FoFdcMessage::CannotComply(..)
             | FoFdcMessage::Shot(..)
             | FoFdcMessage::Splash(..)
             | FoFdcMessage::RoundsComplete(..)
             | FoFdcMessage::BattleDamageAssessmentConfirm(..)
-            | FoFdcMessage::SolidReadback(..) => serde_json::to_vec(&message_to_fo),
+            | FoFdcMessage::SubsequentCorrectionConfirm(..)
+            | FoFdcMessage::RepeatConfirm(..)
+            | FoFdcMessage::RecordAsTargetConfirm(..)
+            | FoFdcMessage::EndOfMissionConfirm(..)
+            | FoFdcMessage::CheckFireConfirm(..)
+            | FoFdcMessage::CheckFireComplete(..)
+            | FoFdcMessage::SolidReadback(..)
+            | FoFdcMessage::Correction(..) => (),
             _ => panic!(
                 "Unsupported message was sent for transmission to the FO: {:?}",
-                message_to_fo
+                message
             ),
-        }?;
+        }
 
-        let bytes_sent = fo_socket.send(&bytes).await?;
-        debug!("Sent {} bytes of {} ", bytes_sent, bytes.len());
+        let sealed = directory.lock().unwrap().get_mut(&observer).map(|fo| {
+            let mut envelope = fo.outbox.seal_for(mission, message);
+            fo.delivery.reply(&mut envelope);
+            fo.delivery.sent(&envelope, Instant::now());
+            (fo.addr, envelope)
+        });
+        match sealed {
+            Some((addr, envelope)) => {
+                let bytes = serde_json::to_vec(&envelope)?;
+                let bytes_sent = send_to_fo(&fo_socket, &bytes, addr).await?;
+                debug!("Sent {} bytes of {} ", bytes_sent, bytes.len());
+            }
+            None => warn!(
+                "{} has never been heard from, so cannot be sent to",
+                observer
+            ),
+        }
     }
     trace!("Leaving the send loop");
     Ok(())
 }
+
+/// Sends `bytes` to the FO at `addr`, returning the bytes sent.
+///
+/// A FO that is not listening has sent nothing, rather than failed, as the message is resent until it answers.
+async fn send_to_fo(fo_socket: &UdpSocket, bytes: &[u8], addr: SocketAddr) -> Result<usize> {
+    match fo_socket.send_to(bytes, addr).await {
+        Ok(bytes_sent) => Ok(bytes_sent),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            warn!("The FO at {} is not listening", addr);
+            Ok(0)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
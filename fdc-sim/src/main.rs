@@ -1,9 +1,17 @@
+use std::sync::Arc;
+
 use anyhow::Result;
-use tokio::{select, sync::mpsc, try_join};
+use fire_direction::firing_table::FiringTables;
+use tokio::{sync::mpsc, try_join};
 use tracing::info;
 
-use crate::{fo_fdc_commhandler::fo_fdc_commhandler_loop, state_machine::state_machine_loop};
+use crate::{
+    fdc_gun_commhandler::{fdc_gun_commhandler_loop, GunSessions},
+    fo_fdc_commhandler::fo_fdc_commhandler_loop,
+    state_machine::state_machine_loop,
+};
 
+mod fdc_gun_commhandler;
 mod fo_fdc_commhandler;
 mod state_machine;
 
@@ -17,24 +25,53 @@ async fn main() -> Result<()> {
 
     let (from_fo_tx, from_fo_rx) = mpsc::unbounded_channel();
     let (to_fo_tx, to_fo_rx) = mpsc::unbounded_channel();
+    let (from_gun_tx, from_gun_rx) = mpsc::unbounded_channel();
+    let (to_gun_tx, to_gun_rx) = mpsc::unbounded_channel();
+    let gun_sessions = GunSessions::default();
+    let firing_tables = FiringTables::from_env()?;
 
     info!("Starting the FO-FDC Comm Handler...");
     let fo_fdc_commhandler_handle = tokio::task::Builder::new()
         .name("commhandler loop")
         .spawn(async move { fo_fdc_commhandler_loop(to_fo_rx, from_fo_tx).await });
 
+    info!("Starting the FDC-Gun Comm Handler...");
+    let fdc_gun_commhandler_handle = {
+        let gun_sessions = Arc::clone(&gun_sessions);
+        tokio::task::Builder::new()
+            .name("gun commhandler loop")
+            .spawn(
+                async move { fdc_gun_commhandler_loop(to_gun_rx, from_gun_tx, gun_sessions).await },
+            )
+    };
+
     info!("Starting the FDC State Machine...");
     let state_machine_handle = tokio::task::Builder::new()
         .name("state machine loop")
-        .spawn(async move { state_machine_loop(from_fo_rx, to_fo_tx).await });
+        .spawn(async move {
+            state_machine_loop(
+                from_fo_rx,
+                to_fo_tx,
+                from_gun_rx,
+                to_gun_tx,
+                gun_sessions,
+                firing_tables,
+            )
+            .await
+        });
 
     // //TODO: Right now, this select creates a stop on main until ctrl_c. We need to also exit on completion of handles.
     // select! {
     //     _ = tokio::signal::ctrl_c() => {state_machine_handle.abort()}
     // }
 
-    let _results = try_join!(fo_fdc_commhandler_handle, state_machine_handle)?;
+    let _results = try_join!(
+        fo_fdc_commhandler_handle,
+        fdc_gun_commhandler_handle,
+        state_machine_handle
+    )?;
     _results.0?;
     _results.1?;
+    _results.2?;
     Ok(())
 }
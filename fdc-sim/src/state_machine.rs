@@ -1,58 +1,308 @@
 //! Provides the functions and enums for maintaining the FDC state machine.
 //!
 //! The FDC State Machine uses the top level [`FdcState`] for representing the state of the FDC.
+//! Each fire mission the FDC is working has a [`MissionState`] of its own, so several can be underway at once.
 //! The workhorse of this module, and the intended component for use is the [`state_machine_loop`],
 //! which provides an `async` function for use in a runtime.
+use std::collections::{hash_map::Entry, HashMap, HashSet, VecDeque};
+
 use anyhow::Result;
+use fdc_gun_comms::{
+    Compliance, FdcGunMessage, ProtocolVersion, Status, TargetLocation, NO_COMMAND_ID,
+};
+use fire_direction::{
+    firing_table::FiringTables,
+    geometry::{self, Position},
+};
 use fo_fdc_comms::{
     battle_damage_assessment::BattleDamageAssessment,
+    cannot_comply::{CannotComply, CannotComplyReason},
+    check_fire::CheckFireComplete,
+    correction::{self, Correction},
+    end_of_mission::{EndOfMission, Repeat},
+    envelope::Envelope,
     message_to_observer::{MessageToObserver, TargetNumber},
     readback::SolidReadback,
-    request_for_fire::{MissionType, TargetDescription, TargetLocation, WarnOrder},
-    shot_fire::{RoundsComplete, Shot, Splash},
-    Ammunition, FoFdcMessage,
+    request_for_fire::{MissionType, WarnOrder},
+    shot_fire::{self, RoundsComplete, Shot, Splash},
+    subsequent_correction::SubsequentCorrection,
+    Ammunition, FoFdcMessage, Fuze,
 };
 use tokio::{
     sync::mpsc::{error::TryRecvError, UnboundedReceiver, UnboundedSender},
-    task::yield_now,
+    time::{Duration, Instant},
+};
+use tracing::{debug, info, info_span, trace, warn};
+
+use crate::{
+    fdc_gun_commhandler::{GunEvent, GunId, GunSessions, ToGun},
+    fo_fdc_commhandler::{FoEvent, ToFo},
+};
+
+/// The letters of the block of target numbers the FDC gives out
+//TODO: Get from config
+const TARGET_NUMBER_BLOCK: &str = "AN";
+
+/// The first target number in the block
+const FIRST_TARGET_NUMBER: u32 = 2001;
+
+/// The last target number in the block, after which target numbers are given out from the first again
+const LAST_TARGET_NUMBER: u32 = 9999;
+
+/// The number of rounds each Gun fires for a mission
+//TODO: Get from the RFF method of fire
+const ROUNDS_PER_GUN: u32 = 4;
+
+/// The number of rounds the adjusting Gun fires for each adjustment
+const ADJUSTING_ROUNDS: u32 = 1;
+
+/// Where the FO is observing from
+//TODO: Get from the FO
+const OBSERVER_POSITION: Position = Position {
+    easting: 12_000.0,
+    northing: 14_000.0,
 };
-use tracing::{debug, error, info, info_span, trace, warn};
+
+/// The weapon every Gun is, for looking up firing tables.
+//TODO: Get from the Gun registration
+const WEAPON: &str = "m777";
+
+/// How long before a volley lands that the FO is warned with a Splash.
+const SPLASH_WARNING: Duration = Duration::from_secs(7);
+
+/// How long after the last round lands that the FO is told Rounds Complete.
+const ROUNDS_COMPLETE_DELAY: Duration = Duration::from_secs(7);
+
+/// The fewest rounds of an ammunition a Gun may report before a resupply of it is sent.
+//TODO: Get from config
+const RESUPPLY_THRESHOLD: u32 = 2 * ROUNDS_PER_GUN;
+
+/// The rounds of each low ammunition delivered in a resupply.
+//TODO: Get from config
+const RESUPPLY_ROUNDS: u32 = 40;
+
+/// How long a resupply takes to reach a Gun.
+//TODO: Get from config
+const RESUPPLY_DELAY: Duration = Duration::from_secs(30);
 
 /// The State of the FDC
 #[derive(Debug, Clone, Copy)]
 enum FdcState {
     /// Indicates disconnected from the FO
     Offline,
-    /// Indicates connected to the FO
-    Online { state: OnlineState },
+    /// Indicates connected to the FO, and working any missions it requests
+    Online,
 }
 
-/// Substates of the FDC when Online
-#[derive(Debug, Clone, Copy)]
-enum OnlineState {
-    /// Indicates the FDC is waiting for RFF
-    Waiting,
-    /// Indicates the FDC is processing and/or executing a RFF
+/// The State of a single fire mission
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum MissionState {
+    /// Indicates the RFF has been read back, and is waiting on the FO's Solid Readback
+    Requested,
+    /// Indicates the RFF has been answered with an MTO or a Cannot Comply, and is waiting on the FO's readback
+    Answered,
+    /// Indicates the MTO has been read back, and the mission is being fired until the FO ends it
     Firing,
 }
 
+/// A Request for Fire being worked
+#[derive(Debug)]
+struct Mission {
+    /// How far along the mission is
+    state: MissionState,
+    /// The callsign of the FO that requested the mission
+    observer: String,
+    /// The request, as the FO sent it
+    request: WarnOrder,
+    /// The target number given to the mission, sent to the FO in the MTO
+    target_number: TargetNumber,
+    /// Where the rounds are aimed, or [`None`] if the request's target could not be located
+    target: Option<Position>,
+    /// Whether the FO is still adjusting fire onto the target, rather than firing for effect
+    adjusting: bool,
+    /// The Subsequent Correction read back to the FO, to apply once the FO confirms it
+    correction: Option<SubsequentCorrection>,
+    /// The BDA the FO reported for the mission, once read back
+    assessment: Option<BattleDamageAssessment>,
+    /// The progress of the FO's Check Fire, until every Gun has answered it
+    check_fire: Option<CheckingFire>,
+    /// The MTO sent to the FO, until it is read back
+    mto: Option<MessageToObserver>,
+    /// The Cannot Comply sent to the FO in place of an MTO, until it is read back
+    cannot_comply: Option<CannotComply>,
+    /// The Shots sent to the FO that it has yet to read back
+    shots: Vec<Shot>,
+    /// The Splashes sent to the FO that it has yet to read back
+    splashes: Vec<Splash>,
+    /// The Rounds Complete sent to the FO, until it is read back
+    rounds_complete: Option<RoundsComplete>,
+    /// The Check Fire Complete sent to the FO, until it is read back
+    check_fire_complete: Option<CheckFireComplete>,
+    /// The Repeat read back to the FO, to fire once the FO confirms it
+    repeat: Option<Repeat>,
+    /// The End of Mission read back to the FO, to end the mission once the FO confirms it
+    end_of_mission: Option<EndOfMission>,
+}
+
+impl Mission {
+    /// Starts working the `request` from `observer` as mission `target_number`, aiming at the target it locates.
+    fn new(target_number: TargetNumber, observer: String, request: WarnOrder) -> Self {
+        Self {
+            state: MissionState::Requested,
+            observer,
+            target_number,
+            target: geometry::locate_target(OBSERVER_POSITION, &request.target_location).ok(),
+            adjusting: request.mission_type == MissionType::AdjustFire,
+            correction: None,
+            assessment: None,
+            check_fire: None,
+            mto: None,
+            cannot_comply: None,
+            shots: Vec::new(),
+            splashes: Vec::new(),
+            rounds_complete: None,
+            check_fire_complete: None,
+            repeat: None,
+            end_of_mission: None,
+            request,
+        }
+    }
+
+    /// Addresses `message` to the mission's FO, as part of this mission.
+    fn to_fo(&self, message: FoFdcMessage) -> ToFo {
+        ToFo {
+            observer: self.observer.clone(),
+            mission: Some(self.target_number.clone()),
+            message,
+        }
+    }
+}
+
+/// The progress of a Check Fire passed on to the Guns of a mission
+#[derive(Debug, Default)]
+struct CheckingFire {
+    /// The Guns that have yet to answer the Check Fire, with the command ID each is to answer with
+    unanswered: HashMap<GunId, u32>,
+    /// The number of Guns that ceased firing, rather than having no rounds left to fire
+    guns_ceased: u32,
+}
+
+/// The command IDs given to the Fire Commands sent to the Guns
+#[derive(Debug, Default)]
+struct CommandIds {
+    /// The ID given to the last Fire Command sent, starting from [`NO_COMMAND_ID`]
+    last: u32,
+    /// The ID of the last Fire Command sent to each Gun, which its answer to a Check Fire carries
+    given: HashMap<GunId, u32>,
+}
+
+/// The progress of the fires for a mission, as reported by the Guns
+#[derive(Debug, Default)]
+struct Fires {
+    /// Whether these are adjusting rounds, rather than fires for effect
+    adjusting: bool,
+    /// Whether every Gun that can fire is busy with other missions, so no Fire Commands have been sent yet
+    queued: bool,
+    /// The Guns that have yet to fire their last round, with the ID of the Fire Command each is firing
+    firing: HashMap<GunId, u32>,
+    /// The shots each Gun has reported, to ignore any reported twice
+    reported: HashSet<(GunId, u32)>,
+    /// The volley the first round of each Fire Command is fired in, by Gun and command ID
+    first_volleys: HashMap<(GunId, u32), u32>,
+    /// The number of volleys the rounds are fired in, which grows as Guns that take over rounds fire them
+    total_volleys: u32,
+    /// The volleys the FO has been sent a Shot for
+    volleys_shot: HashSet<u32>,
+    /// The Splash for each volley the FO has yet to be warned of, with when to send it
+    splashes: VecDeque<(Instant, Splash)>,
+    /// When the last round fired so far is expected to land
+    last_impact: Option<Instant>,
+    /// Every Gun given a Fire Command for the mission
+    commanded: HashSet<GunId>,
+}
+
+impl Fires {
+    /// Records that `gun` has been given the Fire Command with `command_id`, to fire its rounds in the volleys from
+    /// `first_volley` on.
+    fn command(&mut self, gun: GunId, command_id: u32, first_volley: u32) {
+        self.firing.insert(gun, command_id);
+        self.commanded.insert(gun);
+        self.first_volleys.insert((gun, command_id), first_volley);
+    }
+
+    /// The volley that `shot` of the Fire Command with `command_id` is fired in by `gun`
+    fn volley_of(&self, gun: GunId, command_id: u32, shot: u32) -> u32 {
+        let first_volley = self
+            .first_volleys
+            .get(&(gun, command_id))
+            .copied()
+            .unwrap_or(1);
+        first_volley + shot.saturating_sub(1)
+    }
+
+    /// The number of rounds each Gun fires
+    fn rounds_per_gun(&self) -> u32 {
+        rounds_per_gun(self.adjusting)
+    }
+}
+
+/// The number of rounds each Gun fires, when `adjusting` or else firing for effect
+fn rounds_per_gun(adjusting: bool) -> u32 {
+    if adjusting {
+        ADJUSTING_ROUNDS
+    } else {
+        ROUNDS_PER_GUN
+    }
+}
+
+/// A delivery of ammunition to a Gun
+#[derive(Debug)]
+struct Resupply {
+    /// The rounds of each ammunition being delivered
+    rounds: HashMap<fdc_gun_comms::Ammunition, u32>,
+    /// When the delivery reaches the Gun, or [`None`] once the Gun has been told of it
+    arrives_at: Option<Instant>,
+}
+
 /// Asynchronous executor loop for managing the state machine.
 ///
 /// All state manipulation happens within the context of this function.
 /// In the event a message is received that is not expected, the state machine will not change state,
 /// but will emit a [`tracing::warn!`] event.
 ///
+/// Each Request for Fire is worked as its own mission, with its own [`MissionState`], under the target number it is
+/// given on receipt. A message from the FO is for the mission of its target number, or else the mission in its
+/// envelope, or else the mission its FO has requested and yet to be sent an MTO for.
+///
 /// # Arguments
 ///
-/// * `from_fo_rx` - The receive side of a channel for processing the state machine with messages from the FO.
+/// * `from_fo_rx` - The receive side of a channel for processing the state machine with events from the FO.
 /// * `to_fo_tx` - The send side of a channel where messages to send to the FO are sent by the state machine loop.
+/// * `from_gun_rx` - The receive side of a channel for processing the state machine with events from the Guns.
+/// * `to_gun_tx` - The send side of a channel where messages to send to a specific Gun are sent by the state machine loop.
+/// * `gun_sessions` - The table of connected Guns, maintained by the FDC-Gun commhandler.
+/// * `firing_tables` - The firing tables for the Guns.
 pub(crate) async fn state_machine_loop(
-    mut from_fo_rx: UnboundedReceiver<FoFdcMessage>,
-    to_fo_tx: UnboundedSender<FoFdcMessage>,
+    mut from_fo_rx: UnboundedReceiver<FoEvent>,
+    to_fo_tx: UnboundedSender<ToFo>,
+    mut from_gun_rx: UnboundedReceiver<GunEvent>,
+    to_gun_tx: UnboundedSender<ToGun>,
+    gun_sessions: GunSessions,
+    firing_tables: FiringTables,
 ) -> Result<()> {
-    let mut state = FdcState::Online {
-        state: OnlineState::Waiting,
-    };
+    let mut state = FdcState::Offline;
+    // The Requests for Fire being worked, by target number
+    let mut missions: HashMap<TargetNumber, Mission> = HashMap::new();
+    // The fires for each mission, once commanded, by target number
+    let mut fires: HashMap<TargetNumber, Fires> = HashMap::new();
+    // The number of the last target number given out
+    let mut last_target_number = 0;
+    // The IDs given to the Fire Commands sent
+    let mut command_ids = CommandIds::default();
+    // The deliveries of ammunition to Guns that have yet to be acknowledged
+    let mut resupplies: HashMap<GunId, Resupply> = HashMap::new();
+    // The target numbers of the targets the FO has asked to record, which are not given out again
+    let mut targets: HashSet<TargetNumber> = HashSet::new();
     let message_process_span = info_span!("message_process");
     let state_machine_run_span = info_span!("state_run");
     info!("Starting state machine...");
@@ -60,8 +310,29 @@ pub(crate) async fn state_machine_loop(
         trace!("Looping...");
         debug!("State is {:?}", state);
 
-        let message = match from_fo_rx.try_recv() {
-            Ok(message) => Some(message),
+        let envelope = match from_fo_rx.try_recv() {
+            Ok(FoEvent::Message(envelope)) => Some(envelope),
+            Ok(FoEvent::DeliveryFailed(envelope)) => {
+                // The FO cannot be reached, so no one is left to observe the mission's fires.
+                match envelope
+                    .mission
+                    .as_ref()
+                    .and_then(|target_number| missions.remove(target_number))
+                {
+                    Some(mission) => {
+                        warn!(
+                            "Could not deliver {:?} to the FO, abandoning mission {:?}",
+                            envelope.message, mission.target_number
+                        );
+                        end_mission(&mission.target_number, &mut fires, &to_gun_tx)?;
+                    }
+                    None => warn!(
+                        "Could not deliver {:?} to the FO, for no mission underway",
+                        envelope.message
+                    ),
+                }
+                None
+            }
             Err(TryRecvError::Empty) => None,
             Err(TryRecvError::Disconnected) => {
                 info!("transmitter disconnected");
@@ -69,126 +340,822 @@ pub(crate) async fn state_machine_loop(
             }
         };
 
+        let gun_event = match from_gun_rx.try_recv() {
+            Ok(gun_event) => Some(gun_event),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                info!("gun commhandler disconnected");
+                break;
+            }
+        };
+
         tokio::task::yield_now().await;
 
-        trace!("Checking for messages");
-        if let Some(message) = message {
-            debug!("Received message: {:?}", message);
+        trace!("Checking for gun events");
+        if let Some(gun_event) = gun_event {
+            debug!("Received gun event: {:?}", gun_event);
             let _enter = message_process_span.enter();
-            trace!("Entering message processor");
-            match (message, state) {
-                // Request for Fire received while online
-                (
-                    FoFdcMessage::RequestForFire(rff),
-                    FdcState::Online {
-                        state: OnlineState::Waiting,
-                    },
-                ) => {
-                    info!("Received RfF, handling...");
-                    state = FdcState::Online {
-                        state: OnlineState::Firing,
-                    };
-                    to_fo_tx.send(FoFdcMessage::RequestForFireConfirm(rff))?;
+            match gun_event {
+                GunEvent::Connected(gun) => {
+                    if let Some(session) = gun_sessions.lock().unwrap().get(&gun) {
+                        info!(
+                            "Gun {} is now available from {} over {:?}, emplaced at {:?} with {:?}",
+                            gun,
+                            session.addr,
+                            session.version,
+                            session.position,
+                            session.capabilities
+                        );
+                    }
                 }
-                // Solid Readback received for our RFF Confirmation
-                (
-                    FoFdcMessage::SolidReadback(SolidReadback::RequestForFire),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Solid Readback for RFF, proceeding to fire.");
-                    let mto = MessageToObserver {
-                        src: "FDC".to_string(),
-                        receiver: "FO".to_string(),
-                        target_number: TargetNumber::new("AN2001").unwrap(),
-                        ammunition: Ammunition::HighExplosive,
-                        rounds: 4,
-                    };
-                    debug!("MTO: {:?}", mto);
-                    to_fo_tx.send(FoFdcMessage::MessageToObserver(mto))?;
+                gun_event @ (GunEvent::Lost(gun) | GunEvent::Disconnected(gun)) => {
+                    if let GunEvent::Lost(_) = gun_event {
+                        warn!("Gun {} stopped answering, it is no longer available", gun);
+                    } else {
+                        info!("Gun {} is no longer available", gun);
+                        resupplies.remove(&gun);
+                    }
+                    let busy = busy_guns(&fires);
+                    for (target_number, mission) in &mut missions {
+                        // A Gun that is gone can no longer answer a Check Fire.
+                        if let Some(checking) = &mut mission.check_fire {
+                            checking.unanswered.remove(&gun);
+                        }
+                        if let Some(mission_fires) = fires.get_mut(target_number) {
+                            if mission_fires.firing.contains_key(&gun) {
+                                // Should a lost Gun come back, it must not fire rounds handed to another.
+                                if let GunEvent::Lost(_) = gun_event {
+                                    to_gun_tx.send(ToGun {
+                                        gun,
+                                        message: FdcGunMessage::CheckFire,
+                                    })?;
+                                }
+                                relieve(
+                                    gun,
+                                    mission_fires,
+                                    mission,
+                                    &busy,
+                                    &gun_sessions,
+                                    &firing_tables,
+                                    &mut command_ids,
+                                    &to_gun_tx,
+                                )?;
+                            }
+                        }
+                    }
                 }
-                // MTO Readback received
-                (
-                    FoFdcMessage::MessageToObserverConfirm(mto_readback),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Received readback for MTO");
-                    //TODO: Verify MTO
-                    to_fo_tx.send(FoFdcMessage::SolidReadback(
-                        SolidReadback::MessageToObserver,
-                    ))?;
-                    tokio::time::sleep(tokio::time::Duration::from_secs(13)).await;
-
-                    to_fo_tx.send(FoFdcMessage::Shot(Shot {}))?;
-                    for shot_number in 0..3 {
-                        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-                        to_fo_tx.send(FoFdcMessage::Shot(Shot {}))?;
+                GunEvent::Message {
+                    gun,
+                    message:
+                        FdcGunMessage::Malfunction {
+                            command_id,
+                            fault,
+                            shot,
+                        },
+                } => {
+                    let busy = busy_guns(&fires);
+                    match mission_fired_by(gun, command_id, &fires).map(|target_number| {
+                        (fires.get_mut(&target_number), missions.get(&target_number))
+                    }) {
+                        Some((Some(mission_fires), Some(mission))) => {
+                            warn!(
+                                "Gun {} stopped firing Fire Command {} with a {:?} on shot {}",
+                                gun, command_id, fault, shot
+                            );
+                            relieve(
+                                gun,
+                                mission_fires,
+                                mission,
+                                &busy,
+                                &gun_sessions,
+                                &firing_tables,
+                                &mut command_ids,
+                                &to_gun_tx,
+                            )?;
+                        }
+                        _ => warn!(
+                            "Gun {} reported a {:?} on shot {} of Fire Command {}, which it is not firing",
+                            gun, fault, shot, command_id
+                        ),
                     }
-
-                    tokio::time::sleep(tokio::time::Duration::from_secs(13)).await;
-                    to_fo_tx.send(FoFdcMessage::Splash(Splash {}))?;
-
-                    tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
-                    to_fo_tx.send(FoFdcMessage::RoundsComplete(RoundsComplete {}))?;
                 }
-
-                // Handle Shot Readbacks
-                (
-                    FoFdcMessage::ShotConfirm(shot_readback),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Received readback for shot");
-                    //TODO: Verify Readback
-                    to_fo_tx.send(FoFdcMessage::SolidReadback(SolidReadback::Shot))?;
+                GunEvent::Restored(gun) => info!("Gun {} is available again", gun),
+                GunEvent::Message {
+                    gun,
+                    message: FdcGunMessage::StatusReply { status, rounds },
+                } => {
+                    debug!("Gun {} reports {:?}", gun, status);
+                    if let Entry::Vacant(entry) = resupplies.entry(gun) {
+                        let delivery = resupply_for(&rounds);
+                        if !delivery.is_empty() {
+                            info!(
+                                "Gun {} is low on ammunition, sending {:?} due in {:?}",
+                                gun, delivery, RESUPPLY_DELAY
+                            );
+                            entry.insert(Resupply {
+                                rounds: delivery,
+                                arrives_at: Some(Instant::now() + RESUPPLY_DELAY),
+                            });
+                        }
+                    }
                 }
-                // Handle Splash Readbacks
-                (
-                    FoFdcMessage::SplashConfirm(splash_readback),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Received readback for splash");
-                    //TODO: Verify Readback
-                    to_fo_tx.send(FoFdcMessage::SolidReadback(SolidReadback::Splash))?;
+                GunEvent::Message {
+                    gun,
+                    message: FdcGunMessage::ResupplyAck { rounds },
+                } => {
+                    if resupplies.remove(&gun).is_some() {
+                        info!("Gun {} was resupplied, now holding {:?}", gun, rounds);
+                    } else {
+                        warn!("Gun {} acknowledged a resupply that was not sent", gun);
+                    }
                 }
-                // Handle Rounds Complete Readback
-                (
-                    FoFdcMessage::RoundsCompleteConfirm(rounds_complete_readback),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Received readback for rounds complete");
-                    //TODO: Verify Readback
-                    to_fo_tx.send(FoFdcMessage::SolidReadback(SolidReadback::RoundsComplete))?;
-                    // Now we wait for BDA
+                GunEvent::Message {
+                    gun,
+                    message:
+                        FdcGunMessage::ComplianceResponse {
+                            command_id,
+                            compliance,
+                        },
+                } => {
+                    if let Some(checking) = missions.values_mut().find_map(|mission| {
+                        mission
+                            .check_fire
+                            .as_mut()
+                            .filter(|checking| checking.unanswered.get(&gun) == Some(&command_id))
+                    }) {
+                        info!("Gun {} responded {:?} to the Check Fire", gun, compliance);
+                        checking.unanswered.remove(&gun);
+                        if compliance == Compliance::WILLCO {
+                            checking.guns_ceased += 1;
+                        }
+                    } else if let Some(mission_fires) = fires
+                        .values_mut()
+                        .find(|fires| fires.firing.get(&gun) == Some(&command_id))
+                    {
+                        info!(
+                            "Gun {} responded {:?} to Fire Command {}",
+                            gun, compliance, command_id
+                        );
+                        match compliance {
+                            Compliance::WILLCO => (),
+                            // Either way, no more rounds are coming from this Gun.
+                            Compliance::CANTCO | Compliance::HAVECO => {
+                                mission_fires.firing.remove(&gun);
+                            }
+                        }
+                    } else {
+                        warn!(
+                            "Gun {} responded {:?} to Fire Command {}, which it is not firing",
+                            gun, compliance, command_id
+                        );
+                    }
                 }
-                // Handle BDA
-                (
-                    FoFdcMessage::BattleDamageAssessment(bda),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("Received BDA");
-                    to_fo_tx.send(FoFdcMessage::BattleDamageAssessmentConfirm(bda))?;
+                GunEvent::Message {
+                    gun,
+                    message:
+                        FdcGunMessage::FireReport {
+                            command_id,
+                            shot,
+                            total_shots,
+                            time_to_target,
+                            ..
+                        },
+                } => match mission_fired_by(gun, command_id, &fires).map(|target_number| {
+                    (
+                        fires.get_mut(&target_number),
+                        missions.get_mut(&target_number),
+                    )
+                }) {
+                    None => warn!(
+                        "Gun {} reported a shot for Fire Command {}, which it is not firing",
+                        gun, command_id
+                    ),
+                    Some((Some(fires), _)) if fires.reported.contains(&(gun, shot)) => {
+                        warn!("Gun {} reported shot {} again, ignoring", gun, shot);
+                    }
+                    Some((Some(fires), Some(mission))) => {
+                        info!(
+                            "Gun {} fired shot {} of {} for Fire Command {} of mission {:?}, {} ms to target",
+                            gun, shot, total_shots, command_id, mission.target_number, time_to_target
+                        );
+                        fires.reported.insert((gun, shot));
+                        let impact = Instant::now() + Duration::from_millis(time_to_target.into());
+                        // The first round of each volley out tells the FO it is on the way.
+                        let volley = fires.volley_of(gun, command_id, shot);
+                        if fires.volleys_shot.insert(volley) {
+                            let time_of_flight = Duration::from_millis(time_to_target.into());
+                            let shot = Shot {
+                                src: "FDC".to_string(),
+                                receiver: mission.observer.clone(),
+                                target_number: mission.target_number.clone(),
+                                volley,
+                                total_volleys: fires.total_volleys,
+                                time_of_flight: time_to_target,
+                                expected_impact: shot_fire::expected_impact_in(time_of_flight),
+                            };
+                            fires.splashes.push_back((
+                                impact.checked_sub(SPLASH_WARNING).unwrap_or(impact),
+                                Splash {
+                                    src: shot.src.clone(),
+                                    receiver: shot.receiver.clone(),
+                                    target_number: shot.target_number.clone(),
+                                    volley: shot.volley,
+                                    total_volleys: shot.total_volleys,
+                                    expected_impact: shot.expected_impact,
+                                },
+                            ));
+                            mission.shots.push(shot.clone());
+                            to_fo_tx.send(mission.to_fo(FoFdcMessage::Shot(shot)))?;
+                        }
+                        fires.last_impact = fires.last_impact.max(Some(impact));
+                        if shot >= total_shots {
+                            fires.firing.remove(&gun);
+                        }
+                    }
+                    Some(_) => warn!(
+                        "Gun {} reported shot {} of {} with no fires underway",
+                        gun, shot, total_shots
+                    ),
+                },
+                GunEvent::Message { gun, message } => {
+                    warn!("Gun {} sent an unexpected message: {:?}", gun, message)
                 }
-                // Handle Solid Readback for BDA
-                (
-                    FoFdcMessage::SolidReadback(SolidReadback::BattleDamageAssessment),
-                    FdcState::Online {
-                        state: OnlineState::Firing,
-                    },
-                ) => {
-                    info!("BDA Readback confirmed. Transitioning back to waiting.");
-                    state = FdcState::Online {
-                        state: OnlineState::Waiting,
+            }
+        }
+
+        trace!("Checking for messages");
+        if let Some(envelope) = envelope {
+            debug!("Received message: {:?}", envelope);
+            let _enter = message_process_span.enter();
+            trace!("Entering message processor");
+            let Envelope {
+                src,
+                mission: envelope_mission,
+                message,
+                ..
+            } = envelope;
+            match (message, state) {
+                // Request for Fire received while online, starting a new mission
+                (FoFdcMessage::RequestForFire(rff), FdcState::Online) => {
+                    let target_number =
+                        next_target_number(&mut last_target_number, &missions, &targets);
+                    info!(
+                        "Received RfF from {}, handling as mission {:?}",
+                        src, target_number
+                    );
+                    let mission = Mission::new(target_number.clone(), src, rff.clone());
+                    to_fo_tx.send(mission.to_fo(FoFdcMessage::RequestForFireConfirm(rff)))?;
+                    missions.insert(target_number, mission);
+                }
+                // Any other message is for a mission underway, which the FO names in the envelope until it has the
+                // target number from the MTO
+                (message, FdcState::Online) => {
+                    let target_number = message.target_number().cloned().or(envelope_mission);
+                    match target_number
+                        .as_ref()
+                        .and_then(|target_number| missions.get_mut(target_number))
+                    {
+                        Some(mission) => {
+                            let target_number = mission.target_number.clone();
+                            let mut ended = false;
+                            match (message, mission.state) {
+                                // Solid Readback received for our RFF Confirmation
+                                (
+                                    FoFdcMessage::SolidReadback(SolidReadback::RequestForFire),
+                                    MissionState::Requested,
+                                ) => {
+                                    info!("Solid Readback for RFF, proceeding to fire.");
+                                    mission.state = MissionState::Answered;
+                                    match plan_fires(mission, &gun_sessions, &firing_tables) {
+                                        Ok(guns) => {
+                                            let (ammunition, fuze) =
+                                                munitions_for(&mission.request);
+                                            info!("Guns {:?} can fire {:?}", guns, ammunition);
+                                            let mto = MessageToObserver {
+                                                src: "FDC".to_string(),
+                                                receiver: mission.observer.clone(),
+                                                target_number: target_number.clone(),
+                                                ammunition,
+                                                fuze,
+                                                rounds: rounds_per_gun(mission.adjusting),
+                                            };
+                                            debug!("MTO: {:?}", mto);
+                                            mission.mto = Some(mto.clone());
+                                            to_fo_tx.send(mission.to_fo(
+                                                FoFdcMessage::MessageToObserver(mto),
+                                            ))?;
+                                        }
+                                        Err(reason) => {
+                                            warn!("Cannot comply with the RFF: {:?}", reason);
+                                            let cannot_comply = CannotComply {
+                                                src: "FDC".to_string(),
+                                                receiver: mission.observer.clone(),
+                                                reason,
+                                            };
+                                            mission.cannot_comply = Some(cannot_comply.clone());
+                                            to_fo_tx.send(mission.to_fo(
+                                                FoFdcMessage::CannotComply(cannot_comply),
+                                            ))?;
+                                        }
+                                    }
+                                }
+                                // MTO Readback received
+                                (
+                                    FoFdcMessage::MessageToObserverConfirm(mto_readback),
+                                    MissionState::Answered,
+                                ) => match &mission.mto {
+                                    Some(mto) if *mto == mto_readback => {
+                                        info!("Received readback for MTO");
+                                        mission.mto = None;
+                                        mission.state = MissionState::Firing;
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(
+                                                SolidReadback::MessageToObserver,
+                                            ),
+                                        ))?;
+
+                                        let busy = busy_guns(&fires);
+                                        fires.insert(
+                                            target_number.clone(),
+                                            fire(
+                                                mission,
+                                                &busy,
+                                                &gun_sessions,
+                                                &firing_tables,
+                                                &mut command_ids,
+                                                &to_gun_tx,
+                                            )?,
+                                        );
+                                    }
+                                    Some(mto) => {
+                                        warn!(
+                                            "MTO readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(mto, &mto_readback)
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::MessageToObserver(mto.clone()),
+                                        )))?;
+                                    }
+                                    None => warn!("No MTO was sent to be read back"),
+                                },
+
+                                // Subsequent Correction received while adjusting fire
+                                (
+                                    FoFdcMessage::SubsequentCorrection(correction),
+                                    MissionState::Firing,
+                                ) => {
+                                    if mission.adjusting {
+                                        info!("Received Subsequent Correction, reading back");
+                                        mission.correction = Some(correction.clone());
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SubsequentCorrectionConfirm(correction),
+                                        ))?;
+                                    } else {
+                                        warn!("Received a Subsequent Correction while not adjusting fire");
+                                    }
+                                }
+                                // Solid Readback received for our Subsequent Correction readback
+                                (
+                                    FoFdcMessage::SolidReadback(
+                                        SolidReadback::SubsequentCorrection,
+                                    ),
+                                    MissionState::Firing,
+                                ) => match mission.correction.take() {
+                                    Some(correction) => {
+                                        mission.target = mission.target.map(|target| {
+                                            target.correct(OBSERVER_POSITION, &correction)
+                                        });
+                                        mission.adjusting = !correction.fire_for_effect;
+                                        if mission.adjusting {
+                                            info!("Solid Readback for Subsequent Correction, firing the next adjusting round.");
+                                        } else {
+                                            info!("Solid Readback for Fire for Effect, firing for effect.");
+                                        }
+                                        if let Some(last_fires) = fires.remove(&target_number) {
+                                            if !last_fires.firing.is_empty() {
+                                                warn!("The last rounds are not all fired, firing anyway");
+                                            }
+                                        }
+                                        let busy = busy_guns(&fires);
+                                        fires.insert(
+                                            target_number.clone(),
+                                            fire(
+                                                mission,
+                                                &busy,
+                                                &gun_sessions,
+                                                &firing_tables,
+                                                &mut command_ids,
+                                                &to_gun_tx,
+                                            )?,
+                                        );
+                                    }
+                                    None => warn!("No Subsequent Correction was read back"),
+                                },
+
+                                // Cannot Comply Readback received
+                                (
+                                    FoFdcMessage::CannotComplyConfirm(cantco_readback),
+                                    MissionState::Answered,
+                                ) => match &mission.cannot_comply {
+                                    Some(cantco) if *cantco == cantco_readback => {
+                                        info!("Received readback for Cannot Comply. Ending the mission.");
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(SolidReadback::CannotComply),
+                                        ))?;
+                                        ended = true;
+                                    }
+                                    Some(cantco) => {
+                                        warn!(
+                                            "Cannot Comply readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(cantco, &cantco_readback)
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::CannotComply(cantco.clone()),
+                                        )))?;
+                                    }
+                                    None => warn!("No Cannot Comply was sent to be read back"),
+                                },
+
+                                // Handle Shot Readbacks
+                                (
+                                    FoFdcMessage::ShotConfirm(shot_readback),
+                                    MissionState::Firing,
+                                ) => {
+                                    if confirm(&mut mission.shots, &shot_readback) {
+                                        info!("Received readback for shot");
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(SolidReadback::Shot),
+                                        ))?;
+                                    } else if let Some(shot) =
+                                        original_of(&mission.shots, |shot| {
+                                            shot.volley == shot_readback.volley
+                                        })
+                                    {
+                                        warn!(
+                                            "Shot readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(shot, &shot_readback)
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::Shot(shot.clone()),
+                                        )))?;
+                                    } else {
+                                        warn!("No Shot was sent to be read back");
+                                    }
+                                }
+                                // Handle Splash Readbacks
+                                (
+                                    FoFdcMessage::SplashConfirm(splash_readback),
+                                    MissionState::Firing,
+                                ) => {
+                                    if confirm(&mut mission.splashes, &splash_readback) {
+                                        info!("Received readback for splash");
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(SolidReadback::Splash),
+                                        ))?;
+                                    } else if let Some(splash) =
+                                        original_of(&mission.splashes, |splash| {
+                                            splash.volley == splash_readback.volley
+                                        })
+                                    {
+                                        warn!(
+                                            "Splash readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(splash, &splash_readback)
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::Splash(splash.clone()),
+                                        )))?;
+                                    } else {
+                                        warn!("No Splash was sent to be read back");
+                                    }
+                                }
+                                // Handle Rounds Complete Readback
+                                (
+                                    FoFdcMessage::RoundsCompleteConfirm(rounds_complete_readback),
+                                    MissionState::Firing,
+                                ) => match &mission.rounds_complete {
+                                    Some(rounds_complete)
+                                        if *rounds_complete == rounds_complete_readback =>
+                                    {
+                                        info!("Received readback for rounds complete");
+                                        mission.rounds_complete = None;
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(
+                                                SolidReadback::RoundsComplete,
+                                            ),
+                                        ))?;
+                                        // Now we wait for a Repeat, or for the FO to wrap up the mission
+                                    }
+                                    Some(rounds_complete) => {
+                                        warn!(
+                                            "Rounds Complete readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(
+                                                rounds_complete,
+                                                &rounds_complete_readback
+                                            )
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::RoundsComplete(rounds_complete.clone()),
+                                        )))?;
+                                    }
+                                    None => warn!("No Rounds Complete was sent to be read back"),
+                                },
+                                // Handle BDA
+                                (
+                                    FoFdcMessage::BattleDamageAssessment(bda),
+                                    MissionState::Firing,
+                                ) => match bda.validate() {
+                                    Ok(()) => {
+                                        info!("Received BDA, reading back");
+                                        mission.assessment = Some(bda.clone());
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::BattleDamageAssessmentConfirm(bda),
+                                        ))?;
+                                    }
+                                    Err(err) => warn!("Received an invalid BDA: {}", err),
+                                },
+                                // Handle Solid Readback for BDA
+                                (
+                                    FoFdcMessage::SolidReadback(
+                                        SolidReadback::BattleDamageAssessment,
+                                    ),
+                                    MissionState::Firing,
+                                ) => match &mission.assessment {
+                                    Some(bda) => {
+                                        info!(
+                                            "BDA Readback confirmed for {:?}: {:?}, {} casualties, {} equipment destroyed, moving {:?}, {:?}. Waiting on End of Mission.",
+                                            target_number,
+                                            bda.effect,
+                                            bda.casualties,
+                                            bda.equipment_destroyed,
+                                            bda.target_movement,
+                                            bda.remarks
+                                        );
+                                    }
+                                    None => warn!("No BDA was read back"),
+                                },
+
+                                // Check Fire received for the mission, passed on to the Guns at once
+                                (FoFdcMessage::CheckFire(check_fire), MissionState::Firing) => {
+                                    warn!("Received Check Fire, checking the fire of every Gun on the mission");
+                                    let checking =
+                                        mission.check_fire.get_or_insert_with(CheckingFire::default);
+                                    if let Some(mission_fires) = fires.remove(&target_number) {
+                                        // A Gun done with this mission may since be firing another, which it must carry on with.
+                                        let busy = busy_guns(&fires);
+                                        for gun in mission_fires
+                                            .commanded
+                                            .into_iter()
+                                            .filter(|gun| !busy.contains(gun))
+                                        {
+                                            to_gun_tx.send(ToGun {
+                                                gun,
+                                                message: FdcGunMessage::CheckFire,
+                                            })?;
+                                            let command_id = command_ids
+                                                .given
+                                                .get(&gun)
+                                                .copied()
+                                                .unwrap_or(NO_COMMAND_ID);
+                                            checking.unanswered.insert(gun, command_id);
+                                        }
+                                    }
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::CheckFireConfirm(check_fire)),
+                                    )?;
+                                }
+                                // Solid Readback received for our Check Fire readback
+                                (
+                                    FoFdcMessage::SolidReadback(SolidReadback::CheckFire),
+                                    MissionState::Firing,
+                                ) => {
+                                    info!("Solid Readback for Check Fire, waiting on the Guns to answer.");
+                                }
+                                // Check Fire Complete Readback received
+                                (
+                                    FoFdcMessage::CheckFireCompleteConfirm(
+                                        check_fire_complete_readback,
+                                    ),
+                                    MissionState::Firing,
+                                ) => match &mission.check_fire_complete {
+                                    Some(check_fire_complete)
+                                        if *check_fire_complete == check_fire_complete_readback =>
+                                    {
+                                        info!("Received readback for Check Fire Complete");
+                                        mission.check_fire_complete = None;
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::SolidReadback(
+                                                SolidReadback::CheckFireComplete,
+                                            ),
+                                        ))?;
+                                    }
+                                    Some(check_fire_complete) => {
+                                        warn!(
+                                            "Check Fire Complete readback is wrong in {:?}, sending a Correction",
+                                            correction::corrected_fields(
+                                                check_fire_complete,
+                                                &check_fire_complete_readback
+                                            )
+                                        );
+                                        to_fo_tx.send(mission.to_fo(FoFdcMessage::Correction(
+                                            Correction::CheckFireComplete(
+                                                check_fire_complete.clone(),
+                                            ),
+                                        )))?;
+                                    }
+                                    None => warn!("No Check Fire Complete was sent to be read back"),
+                                },
+
+                                // Repeat received once the fires for effect are complete
+                                (FoFdcMessage::Repeat(repeat), MissionState::Firing) => {
+                                    if !mission.adjusting && !fires.contains_key(&target_number) {
+                                        info!("Received Repeat, reading back");
+                                        mission.repeat = Some(repeat.clone());
+                                        to_fo_tx.send(
+                                            mission.to_fo(FoFdcMessage::RepeatConfirm(repeat)),
+                                        )?;
+                                    } else {
+                                        warn!(
+                                            "Received a Repeat for {:?}, which has no completed fires for effect",
+                                            repeat.target_number
+                                        );
+                                    }
+                                }
+                                // Solid Readback received for our Repeat readback
+                                (
+                                    FoFdcMessage::SolidReadback(SolidReadback::Repeat),
+                                    MissionState::Firing,
+                                ) => match mission.repeat.take() {
+                                    Some(_)
+                                        if !mission.adjusting
+                                            && !fires.contains_key(&target_number) =>
+                                    {
+                                        info!("Solid Readback for Repeat, firing for effect again.");
+                                        let busy = busy_guns(&fires);
+                                        fires.insert(
+                                            target_number.clone(),
+                                            fire(
+                                                mission,
+                                                &busy,
+                                                &gun_sessions,
+                                                &firing_tables,
+                                                &mut command_ids,
+                                                &to_gun_tx,
+                                            )?,
+                                        );
+                                    }
+                                    Some(_) => warn!(
+                                        "Solid Readback for Repeat, but the mission is firing again already"
+                                    ),
+                                    None => warn!("No Repeat was read back"),
+                                },
+
+                                // Record as Target received for the mission
+                                (FoFdcMessage::RecordAsTarget(record), MissionState::Firing) => {
+                                    info!("Received Record as Target, reading back");
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::RecordAsTargetConfirm(record)),
+                                    )?;
+                                }
+                                // Solid Readback received for our Record as Target readback
+                                (
+                                    FoFdcMessage::SolidReadback(SolidReadback::RecordAsTarget),
+                                    MissionState::Firing,
+                                ) => match mission.target {
+                                    Some(target) => {
+                                        info!("Recording target {:?} at {:?}", target_number, target);
+                                        targets.insert(target_number.clone());
+                                    }
+                                    None => warn!(
+                                        "The mission's target was never located, so cannot be recorded"
+                                    ),
+                                },
+
+                                // End of Mission received for the mission
+                                (FoFdcMessage::EndOfMission(end_of_mission), MissionState::Firing) => {
+                                    info!("Received End of Mission, reading back");
+                                    mission.end_of_mission = Some(end_of_mission.clone());
+                                    to_fo_tx.send(mission.to_fo(
+                                        FoFdcMessage::EndOfMissionConfirm(end_of_mission),
+                                    ))?;
+                                }
+                                // Solid Readback received for our End of Mission readback
+                                (
+                                    FoFdcMessage::SolidReadback(SolidReadback::EndOfMission),
+                                    MissionState::Firing,
+                                ) => match mission.end_of_mission.take() {
+                                    Some(_) => {
+                                        info!("End of Mission confirmed. Ending the mission.");
+                                        ended = true;
+                                    }
+                                    None => warn!("No End of Mission was read back"),
+                                },
+
+                                // Correction received for our RFF readback
+                                (
+                                    FoFdcMessage::Correction(Correction::RequestForFire(rff)),
+                                    MissionState::Requested,
+                                ) => {
+                                    warn!("Our RFF readback was wrong, reading back the Correction");
+                                    *mission = Mission::new(
+                                        target_number.clone(),
+                                        mission.observer.clone(),
+                                        rff.clone(),
+                                    );
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::RequestForFireConfirm(rff)),
+                                    )?;
+                                }
+                                // Correction received for our BDA readback
+                                (
+                                    FoFdcMessage::Correction(Correction::BattleDamageAssessment(
+                                        bda,
+                                    )),
+                                    MissionState::Firing,
+                                ) => match bda.validate() {
+                                    Ok(()) => {
+                                        warn!("Our BDA readback was wrong, reading back the Correction");
+                                        mission.assessment = Some(bda.clone());
+                                        to_fo_tx.send(mission.to_fo(
+                                            FoFdcMessage::BattleDamageAssessmentConfirm(bda),
+                                        ))?;
+                                    }
+                                    Err(err) => {
+                                        warn!("Received an invalid BDA in a Correction: {}", err)
+                                    }
+                                },
+                                // Correction received for our Subsequent Correction readback
+                                (
+                                    FoFdcMessage::Correction(Correction::SubsequentCorrection(
+                                        correction,
+                                    )),
+                                    MissionState::Firing,
+                                ) if mission.correction.is_some() => {
+                                    warn!("Our Subsequent Correction readback was wrong, reading back the Correction");
+                                    mission.correction = Some(correction.clone());
+                                    to_fo_tx.send(mission.to_fo(
+                                        FoFdcMessage::SubsequentCorrectionConfirm(correction),
+                                    ))?;
+                                }
+                                // Correction received for our Check Fire readback
+                                (
+                                    FoFdcMessage::Correction(Correction::CheckFire(check_fire)),
+                                    MissionState::Firing,
+                                ) => {
+                                    warn!("Our Check Fire readback was wrong, reading back the Correction");
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::CheckFireConfirm(check_fire)),
+                                    )?;
+                                }
+                                // Correction received for our Repeat readback
+                                (
+                                    FoFdcMessage::Correction(Correction::Repeat(repeat)),
+                                    MissionState::Firing,
+                                ) if mission.repeat.is_some() => {
+                                    warn!("Our Repeat readback was wrong, reading back the Correction");
+                                    mission.repeat = Some(repeat.clone());
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::RepeatConfirm(repeat)),
+                                    )?;
+                                }
+                                // Correction received for our Record as Target readback
+                                (
+                                    FoFdcMessage::Correction(Correction::RecordAsTarget(record)),
+                                    MissionState::Firing,
+                                ) => {
+                                    warn!("Our Record as Target readback was wrong, reading back the Correction");
+                                    to_fo_tx.send(
+                                        mission.to_fo(FoFdcMessage::RecordAsTargetConfirm(record)),
+                                    )?;
+                                }
+                                // Correction received for our End of Mission readback
+                                (
+                                    FoFdcMessage::Correction(Correction::EndOfMission(
+                                        end_of_mission,
+                                    )),
+                                    MissionState::Firing,
+                                ) if mission.end_of_mission.is_some() => {
+                                    warn!("Our End of Mission readback was wrong, reading back the Correction");
+                                    mission.end_of_mission = Some(end_of_mission.clone());
+                                    to_fo_tx.send(mission.to_fo(
+                                        FoFdcMessage::EndOfMissionConfirm(end_of_mission),
+                                    ))?;
+                                }
+
+                                // Unexpected messages
+                                (_, _) => {
+                                    warn!("Invalid message received, or received in invalid state");
+                                }
+                            }
+                            // The FO needs no more rounds, so any still to be fired are stopped.
+                            if ended {
+                                missions.remove(&target_number);
+                                end_mission(&target_number, &mut fires, &to_gun_tx)?;
+                            }
+                        }
+                        None => warn!(
+                            "Received {:?} from {}, which is for no mission underway",
+                            message, src
+                        ),
                     }
                 }
 
@@ -201,14 +1168,463 @@ pub(crate) async fn state_machine_loop(
 
         let _state_run_enter = state_machine_run_span.enter();
         trace!("Entering the state runner");
+        let now = Instant::now();
+        for (gun, resupply) in &mut resupplies {
+            if resupply
+                .arrives_at
+                .is_some_and(|arrives_at| arrives_at <= now)
+            {
+                info!("Resupply has reached Gun {}", gun);
+                resupply.arrives_at = None;
+                to_gun_tx.send(ToGun {
+                    gun: *gun,
+                    message: FdcGunMessage::Resupply {
+                        rounds: resupply.rounds.clone(),
+                    },
+                })?;
+            }
+        }
         match state {
-            FdcState::Offline => {
-                state = FdcState::Online {
-                    state: OnlineState::Waiting,
+            FdcState::Offline => state = FdcState::Online,
+            FdcState::Online => {
+                for (target_number, mission) in &mut missions {
+                    if let Some(checking) = &mission.check_fire {
+                        if checking.unanswered.is_empty() {
+                            info!(
+                                "Every Gun has answered the Check Fire, {} ceased firing",
+                                checking.guns_ceased
+                            );
+                            let check_fire_complete = CheckFireComplete {
+                                src: "FDC".to_string(),
+                                receiver: mission.observer.clone(),
+                                target_number: target_number.clone(),
+                                guns_ceased: checking.guns_ceased,
+                            };
+                            mission.check_fire_complete = Some(check_fire_complete.clone());
+                            to_fo_tx.send(
+                                mission.to_fo(FoFdcMessage::CheckFireComplete(check_fire_complete)),
+                            )?;
+                            mission.check_fire = None;
+                        }
+                    }
+
+                    // Fires waiting on busy Guns go ahead once a Gun is free.
+                    if fires.get(target_number).is_some_and(|fires| fires.queued) {
+                        let busy = busy_guns(&fires);
+                        let retried = fire(
+                            mission,
+                            &busy,
+                            &gun_sessions,
+                            &firing_tables,
+                            &mut command_ids,
+                            &to_gun_tx,
+                        )?;
+                        if !retried.queued {
+                            info!("Guns are free to fire mission {:?}", target_number);
+                            fires.insert(target_number.clone(), retried);
+                        }
+                    }
+
+                    if let Entry::Occupied(mut entry) = fires.entry(target_number.clone()) {
+                        let mission_fires = entry.get_mut();
+                        let now = Instant::now();
+                        while mission_fires
+                            .splashes
+                            .front()
+                            .is_some_and(|(splash_at, _)| *splash_at <= now)
+                        {
+                            let (_, splash) = mission_fires
+                                .splashes
+                                .pop_front()
+                                .expect("a Splash was due");
+                            mission.splashes.push(splash.clone());
+                            to_fo_tx.send(mission.to_fo(FoFdcMessage::Splash(splash)))?;
+                        }
+
+                        let all_landed = mission_fires
+                            .last_impact
+                            .is_none_or(|last_impact| now >= last_impact + ROUNDS_COMPLETE_DELAY);
+                        if !mission_fires.queued
+                            && mission_fires.firing.is_empty()
+                            && mission_fires.splashes.is_empty()
+                            && all_landed
+                        {
+                            // The FO follows an adjusting round with a correction, rather than waiting on Rounds Complete.
+                            if mission_fires.adjusting {
+                                info!(
+                                    "Adjusting rounds have landed, waiting on a Subsequent Correction"
+                                );
+                            } else {
+                                info!("All rounds have landed, sending Rounds Complete");
+                                let rounds_complete = RoundsComplete {
+                                    src: "FDC".to_string(),
+                                    receiver: mission.observer.clone(),
+                                    target_number: target_number.clone(),
+                                    total_volleys: mission_fires
+                                        .volleys_shot
+                                        .len()
+                                        .try_into()
+                                        .unwrap_or(u32::MAX),
+                                };
+                                mission.rounds_complete = Some(rounds_complete.clone());
+                                to_fo_tx.send(
+                                    mission.to_fo(FoFdcMessage::RoundsComplete(rounds_complete)),
+                                )?;
+                            }
+                            entry.remove();
+                        }
+                    }
                 }
             }
-            FdcState::Online { .. } => (),
         }
     }
     Ok(())
 }
+
+/// Gives out the next target number after `last_target_number`, skipping any held by one of the `missions` or a
+/// recorded target.
+fn next_target_number(
+    last_target_number: &mut u32,
+    missions: &HashMap<TargetNumber, Mission>,
+    targets: &HashSet<TargetNumber>,
+) -> TargetNumber {
+    loop {
+        *last_target_number =
+            if (FIRST_TARGET_NUMBER..LAST_TARGET_NUMBER).contains(last_target_number) {
+                *last_target_number + 1
+            } else {
+                FIRST_TARGET_NUMBER
+            };
+        let target_number =
+            TargetNumber::new(&format!("{TARGET_NUMBER_BLOCK}{last_target_number:04}"))
+                .expect("target numbers are two letters and four digits");
+        if !missions.contains_key(&target_number) && !targets.contains(&target_number) {
+            return target_number;
+        }
+    }
+}
+
+/// The Guns still firing any of the `fires`
+fn busy_guns(fires: &HashMap<TargetNumber, Fires>) -> HashSet<GunId> {
+    fires
+        .values()
+        .flat_map(|fires| fires.firing.keys().copied())
+        .collect()
+}
+
+/// The target number of the mission among the `fires` that `gun` is firing the Fire Command with `command_id` for
+fn mission_fired_by(
+    gun: GunId,
+    command_id: u32,
+    fires: &HashMap<TargetNumber, Fires>,
+) -> Option<TargetNumber> {
+    fires
+        .iter()
+        .find(|(_, fires)| fires.firing.get(&gun) == Some(&command_id))
+        .map(|(target_number, _)| target_number.clone())
+}
+
+/// Ends the mission with `target_number`, checking the fire of any Gun still firing it.
+///
+/// # Errors
+///
+/// Returns an error if the channel to the FDC-Gun commhandler is closed.
+fn end_mission(
+    target_number: &TargetNumber,
+    fires: &mut HashMap<TargetNumber, Fires>,
+    to_gun_tx: &UnboundedSender<ToGun>,
+) -> Result<()> {
+    if let Some(fires) = fires.remove(target_number) {
+        for gun in fires.firing.into_keys() {
+            info!("Checking fire of Gun {}", gun);
+            to_gun_tx.send(ToGun {
+                gun,
+                message: FdcGunMessage::CheckFire,
+            })?;
+        }
+    }
+    Ok(())
+}
+
+/// Takes the message matching the `readback` out of the `sent` messages awaiting one, returning whether there was one.
+fn confirm<T: PartialEq>(sent: &mut Vec<T>, readback: &T) -> bool {
+    match sent.iter().position(|message| message == readback) {
+        Some(index) => {
+            sent.swap_remove(index);
+            true
+        }
+        None => false,
+    }
+}
+
+/// The message among the `sent` messages that a wrong readback most likely reads back, which is the one `same` picks
+/// out, or else the oldest.
+fn original_of<T>(sent: &[T], same: impl Fn(&T) -> bool) -> Option<&T> {
+    sent.iter()
+        .find(|message| same(message))
+        .or_else(|| sent.first())
+}
+
+/// The ammunition and fuze asked for by the `rff`, defaulting to HE with a PD fuze.
+fn munitions_for(rff: &WarnOrder) -> (Ammunition, Fuze) {
+    (
+        rff.ammunition.unwrap_or(Ammunition::HighExplosive),
+        rff.fuze.unwrap_or(Fuze::PointDetonating),
+    )
+}
+
+/// The Fire Command for `rounds` of the `rff` at `target_location`
+fn fire_command(
+    rff: &WarnOrder,
+    command_id: u32,
+    rounds: u32,
+    target_location: TargetLocation,
+) -> FdcGunMessage {
+    let (ammunition, fuze) = munitions_for(rff);
+    FdcGunMessage::FireCommand {
+        command_id,
+        rounds,
+        ammunition: ammunition.into(),
+        fuze: fuze.into(),
+        target_location,
+    }
+}
+
+/// The delivery to make to a Gun reporting `rounds`, which is [`RESUPPLY_ROUNDS`] of each ammunition it is low on.
+fn resupply_for(
+    rounds: &HashMap<fdc_gun_comms::Ammunition, u32>,
+) -> HashMap<fdc_gun_comms::Ammunition, u32> {
+    rounds
+        .iter()
+        .filter(|(_, count)| **count < RESUPPLY_THRESHOLD)
+        .map(|(ammunition, _)| (*ammunition, RESUPPLY_ROUNDS))
+        .collect()
+}
+
+/// Sends the Fire Commands for the next fires of the `mission`, and returns the fires to track.
+///
+/// While the FO is adjusting, a single Gun fires [`ADJUSTING_ROUNDS`], so the FO can tell its rounds apart. Once the FO
+/// calls for effect, every Gun that can fire does.
+///
+/// Guns that are `busy` firing other missions are left out. Should every Gun that can fire be busy, nothing is sent,
+/// and the fires are returned queued.
+///
+/// # Errors
+///
+/// Returns an error if the channel to the FDC-Gun commhandler is closed.
+fn fire(
+    mission: &Mission,
+    busy: &HashSet<GunId>,
+    gun_sessions: &GunSessions,
+    firing_tables: &FiringTables,
+    command_ids: &mut CommandIds,
+    to_gun_tx: &UnboundedSender<ToGun>,
+) -> Result<Fires> {
+    let mut fires = Fires {
+        adjusting: mission.adjusting,
+        total_volleys: rounds_per_gun(mission.adjusting),
+        ..Fires::default()
+    };
+    // Guns may have run dry or dropped off since the MTO went out.
+    let (mut aimed_guns, busy_guns): (Vec<_>, Vec<_>) =
+        plan_fires(mission, gun_sessions, firing_tables)
+            .unwrap_or_default()
+            .into_iter()
+            .partition(|(gun, _)| !busy.contains(gun));
+    if aimed_guns.is_empty() && !busy_guns.is_empty() {
+        debug!("Guns {:?} are busy with other missions", busy_guns);
+        fires.queued = true;
+        return Ok(fires);
+    }
+    if fires.adjusting {
+        aimed_guns.sort_by_key(|(gun, _)| *gun);
+        aimed_guns.truncate(1);
+    }
+    info!("Sending Fire Commands to Guns {:?}", aimed_guns);
+    for (gun, target_location) in aimed_guns {
+        let command_id = command_id_for(gun, gun_sessions, command_ids);
+        fires.command(gun, command_id, 1);
+        to_gun_tx.send(ToGun {
+            gun,
+            message: fire_command(
+                &mission.request,
+                command_id,
+                fires.rounds_per_gun(),
+                target_location,
+            ),
+        })?;
+    }
+    Ok(fires)
+}
+
+/// Gives out the next command ID for a Fire Command to `gun`, after the last of the `command_ids`.
+///
+/// Older Guns have no room for the ID, so are given [`NO_COMMAND_ID`] and can only be told apart by who they are.
+fn command_id_for(gun: GunId, gun_sessions: &GunSessions, command_ids: &mut CommandIds) -> u32 {
+    let version = gun_sessions
+        .lock()
+        .unwrap()
+        .get(&gun)
+        .map(|session| session.version)
+        .unwrap_or_default();
+    let command_id = if version >= ProtocolVersion::V4 {
+        command_ids.last = command_ids.last.wrapping_add(1).max(1);
+        command_ids.last
+    } else {
+        NO_COMMAND_ID
+    };
+    command_ids.given.insert(gun, command_id);
+    command_id
+}
+
+/// Takes a `gun` that can no longer fire off of the `fires`, and hands the rounds it had left to another Gun that is not
+/// `busy` with another mission, if any can fire them.
+///
+/// # Errors
+///
+/// Returns an error if the channel to the FDC-Gun commhandler is closed.
+#[allow(clippy::too_many_arguments)]
+fn relieve(
+    gun: GunId,
+    fires: &mut Fires,
+    mission: &Mission,
+    busy: &HashSet<GunId>,
+    gun_sessions: &GunSessions,
+    firing_tables: &FiringTables,
+    command_ids: &mut CommandIds,
+    to_gun_tx: &UnboundedSender<ToGun>,
+) -> Result<()> {
+    match reassign(gun, fires, mission, busy, gun_sessions, firing_tables) {
+        Some((relief, target_location, rounds)) => {
+            info!(
+                "Reassigning {} rounds from Gun {} to Gun {}",
+                rounds, gun, relief
+            );
+            // The rounds are fired after the volleys already planned, as volleys of their own.
+            let command_id = command_id_for(relief, gun_sessions, command_ids);
+            fires.command(relief, command_id, fires.total_volleys + 1);
+            fires.total_volleys += rounds;
+            to_gun_tx.send(ToGun {
+                gun: relief,
+                message: fire_command(&mission.request, command_id, rounds, target_location),
+            })?;
+        }
+        None => warn!("No Gun can take over the fires of Gun {}", gun),
+    }
+    Ok(())
+}
+
+/// Takes a `gun` that can no longer fire off of the `fires`, and picks a Gun that has not fired the `mission` yet, and
+/// is not `busy` with another, to fire the rounds it had left.
+///
+/// Returns the Gun to fire, its aim, and the rounds to fire, or [`None`] if no Gun can.
+fn reassign(
+    gun: GunId,
+    fires: &mut Fires,
+    mission: &Mission,
+    busy: &HashSet<GunId>,
+    gun_sessions: &GunSessions,
+    firing_tables: &FiringTables,
+) -> Option<(GunId, TargetLocation, u32)> {
+    fires.firing.remove(&gun)?;
+    let fired = fires
+        .reported
+        .iter()
+        .filter(|(reporter, _)| *reporter == gun)
+        .count();
+    let rounds = fires
+        .rounds_per_gun()
+        .saturating_sub(fired.try_into().unwrap_or(u32::MAX));
+    if rounds == 0 {
+        return None;
+    }
+
+    plan_fires(mission, gun_sessions, firing_tables)
+        .ok()?
+        .into_iter()
+        .find(|(relief, _)| !fires.commanded.contains(relief) && !busy.contains(relief))
+        .map(|(relief, target_location)| (relief, target_location, rounds))
+}
+
+/// Finds the Guns that can fire the `mission`, and aims each of them at its target.
+///
+/// A Gun can fire if it is answering its status polls, is not non-operational, last reported enough of the requested
+/// ammunition for a mission, agreed a protocol version that carries the requested fuze, and the target is within its
+/// range and its firing table.
+///
+/// # Errors
+///
+/// Returns the [`CannotComplyReason`] to give the FO if no Gun can fire.
+fn plan_fires(
+    mission: &Mission,
+    gun_sessions: &GunSessions,
+    firing_tables: &FiringTables,
+) -> Result<Vec<(GunId, TargetLocation)>, CannotComplyReason> {
+    let target = mission
+        .target
+        .ok_or(CannotComplyReason::InvalidTargetLocation)?;
+    let (ammunition, fuze) = munitions_for(&mission.request);
+
+    let sessions = gun_sessions.lock().unwrap();
+    let operational: Vec<_> = sessions
+        .iter()
+        .filter(|(_, session)| {
+            !session.liveness.is_lost()
+                && session
+                    .last_status
+                    .as_ref()
+                    .is_some_and(|status| status.status != Status::NonOperational)
+        })
+        .collect();
+    if operational.is_empty() {
+        return Err(CannotComplyReason::NoGunsAvailable);
+    }
+
+    let gun_ammunition = fdc_gun_comms::Ammunition::from(ammunition);
+    let carrying: Vec<_> = operational
+        .into_iter()
+        .filter(|(_, session)| {
+            session.last_status.as_ref().is_some_and(|status| {
+                status
+                    .rounds
+                    .get(&gun_ammunition)
+                    .is_some_and(|count| *count >= ROUNDS_PER_GUN)
+            })
+        })
+        .collect();
+    if carrying.is_empty() {
+        return Err(CannotComplyReason::AmmunitionUnavailable(ammunition));
+    }
+
+    let gun_fuze = fdc_gun_comms::Fuze::from(fuze);
+    let fuzing: Vec<_> = carrying
+        .into_iter()
+        .filter(|(_, session)| session.version.carries(gun_fuze))
+        .collect();
+    if fuzing.is_empty() {
+        return Err(CannotComplyReason::FuzeUnavailable(fuze));
+    }
+
+    let in_range: Vec<_> = fuzing
+        .into_iter()
+        .map(|(gun, session)| {
+            (
+                *gun,
+                Position::from(session.position).aim_at(target),
+                session,
+            )
+        })
+        .filter(|(_, aim, session)| {
+            aim.range() <= session.capabilities.max_range
+                && firing_tables
+                    .solve(WEAPON, gun_ammunition, aim.range())
+                    .is_ok()
+        })
+        .map(|(gun, aim, _)| (gun, aim))
+        .collect();
+    if in_range.is_empty() {
+        Err(CannotComplyReason::TargetOutOfRange)
+    } else {
+        Ok(in_range)
+    }
+}
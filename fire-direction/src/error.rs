@@ -0,0 +1,49 @@
+//! Provides the error type for the fire direction computations.
+use std::io;
+
+use fdc_gun_comms::Ammunition;
+use thiserror::Error;
+
+/// Everything that can go wrong computing fire direction.
+#[derive(Debug, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// A grid coordinate has more digits than a grid reference allows
+    #[error("invalid grid {lateral} {longitudinal}")]
+    InvalidGrid {
+        /// The lateral (easting) coordinate
+        lateral: u32,
+        /// The longitudinal (northing) coordinate
+        longitudinal: u32,
+    },
+    /// No charge in the firing table reaches the range
+    #[error("range {range} m is outside the firing table's {min_range} m to {max_range} m")]
+    OutOfRange {
+        /// The range asked for, in meters
+        range: u32,
+        /// The shortest range in the table, in meters
+        min_range: u32,
+        /// The longest range in the table, in meters
+        max_range: u32,
+    },
+    /// There is no firing table for the weapon and ammunition
+    #[error("no firing table for {weapon} firing {ammunition:?}")]
+    NoFiringTable {
+        /// The weapon asked for
+        weapon: String,
+        /// The ammunition asked for
+        ammunition: Ammunition,
+    },
+    /// A firing table's file name is not an ammunition type
+    #[error("no ammunition type for firing table {0:?}")]
+    UnknownAmmunition(String),
+    /// A firing table has no rows
+    #[error("firing table has no rows")]
+    EmptyFiringTable,
+    /// A firing table is not valid CSV
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+    /// A firing table could not be read
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,456 @@
+//! Provides tabular firing tables, and the ballistic solutions interpolated from them.
+//!
+//! Each table covers one weapon firing one type of ammunition, and is loaded from a CSV file with a header row and
+//! one row per charge and range:
+//!
+//! ```text
+//! range,charge,quadrant_elevation,time_of_flight
+//! 1000,1,107,5.6
+//! 2000,1,220,11.5
+//! ```
+//!
+//! Ranges are in meters, quadrant elevations in mils, and times of flight in seconds.
+//! A directory of tables holds one subdirectory per weapon, each holding one `<ammunition>.csv` per ammunition type,
+//! named as by [`file_stem`].
+//!
+//! The tables shipped with this crate are built into it, so a binary has them wherever it is run from. Another
+//! directory of tables can be named in the [`DIR_VAR`] environment variable instead.
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io,
+    path::Path,
+    time::Duration,
+};
+
+use fdc_gun_comms::Ammunition;
+use serde::Deserialize;
+
+use crate::error::Error;
+
+/// The environment variable naming a directory of tables to use in place of those shipped with this crate
+pub const DIR_VAR: &str = "FIRING_TABLES_DIR";
+
+/// The weapon the shipped tables are for
+const SHIPPED_WEAPON: &str = "m777";
+
+/// The tables shipped with this crate, by ammunition type
+const SHIPPED: [(Ammunition, &str); 6] = [
+    (
+        Ammunition::HighExplosive,
+        include_str!("../firing-tables/m777/high_explosive.csv"),
+    ),
+    (
+        Ammunition::Smoke,
+        include_str!("../firing-tables/m777/smoke.csv"),
+    ),
+    (
+        Ammunition::Illumination,
+        include_str!("../firing-tables/m777/illumination.csv"),
+    ),
+    (
+        Ammunition::WhitePhosphorus,
+        include_str!("../firing-tables/m777/white_phosphorus.csv"),
+    ),
+    (
+        Ammunition::Dpicm,
+        include_str!("../firing-tables/m777/dpicm.csv"),
+    ),
+    (
+        Ammunition::PrecisionGuided,
+        include_str!("../firing-tables/m777/precision_guided.csv"),
+    ),
+];
+
+/// Everything needed to lay a gun for a given range
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Solution {
+    /// The propellant charge to fire with
+    pub charge: u8,
+    /// The elevation of the tube above the horizontal, in mils
+    pub quadrant_elevation: u32,
+    /// The time from firing until impact
+    pub time_of_flight: Duration,
+}
+
+/// A single row of a firing table
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+struct Row {
+    /// Range in meters
+    range: u32,
+    /// Propellant charge
+    charge: u8,
+    /// Quadrant elevation in mils
+    quadrant_elevation: u32,
+    /// Time of flight in seconds
+    time_of_flight: f64,
+}
+
+/// The firing table for one weapon and ammunition type
+#[derive(Debug, Clone, PartialEq)]
+pub struct FiringTable {
+    /// Every row, ordered by charge and then range
+    rows: Vec<Row>,
+}
+
+impl FiringTable {
+    /// Reads a firing table from CSV.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Csv`] if the CSV is malformed, or [`Error::EmptyFiringTable`] if it has no rows.
+    pub fn from_reader(reader: impl io::Read) -> Result<Self, Error> {
+        let mut rows = csv::Reader::from_reader(reader)
+            .deserialize()
+            .collect::<Result<Vec<Row>, _>>()?;
+        if rows.is_empty() {
+            return Err(Error::EmptyFiringTable);
+        }
+        rows.sort_by_key(|row| (row.charge, row.range));
+
+        Ok(Self { rows })
+    }
+
+    /// Reads a firing table from a CSV file.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::Io`] if the file cannot be read, otherwise as [`FiringTable::from_reader`].
+    pub fn from_path(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_reader(File::open(path)?)
+    }
+
+    /// The shortest range covered by any charge, in meters
+    #[must_use]
+    pub fn min_range(&self) -> u32 {
+        self.rows
+            .iter()
+            .map(|row| row.range)
+            .min()
+            .unwrap_or_default()
+    }
+
+    /// The longest range covered by any charge, in meters
+    #[must_use]
+    pub fn max_range(&self) -> u32 {
+        self.rows
+            .iter()
+            .map(|row| row.range)
+            .max()
+            .unwrap_or_default()
+    }
+
+    /// Solves for the `range` in meters, with the lowest charge that reaches it.
+    ///
+    /// Quadrant elevation and time of flight are interpolated between the two rows of that charge either side of
+    /// the range.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::OutOfRange`] if no charge covers the range.
+    pub fn solve(&self, range: u32) -> Result<Solution, Error> {
+        let bracket = self.rows.windows(2).find(|pair| {
+            pair[0].charge == pair[1].charge && (pair[0].range..=pair[1].range).contains(&range)
+        });
+        // A charge with a single row covers exactly one range.
+        let exact = || self.rows.iter().find(|row| row.range == range);
+
+        match (bracket, exact()) {
+            (Some([low, high]), _) => Ok(interpolate(low, high, range)),
+            (None, Some(row)) => Ok(interpolate(row, row, range)),
+            _ => Err(Error::OutOfRange {
+                range,
+                min_range: self.min_range(),
+                max_range: self.max_range(),
+            }),
+        }
+    }
+}
+
+/// Interpolates a [`Solution`] for a `range` between the `low` and `high` rows of the same charge.
+fn interpolate(low: &Row, high: &Row, range: u32) -> Solution {
+    let fraction = if high.range == low.range {
+        0.0
+    } else {
+        f64::from(range - low.range) / f64::from(high.range - low.range)
+    };
+    let lerp = |from: f64, to: f64| from + (to - from) * fraction;
+
+    let quadrant_elevation = lerp(
+        f64::from(low.quadrant_elevation),
+        f64::from(high.quadrant_elevation),
+    )
+    .round();
+    let time_of_flight = lerp(low.time_of_flight, high.time_of_flight).max(0.0);
+
+    Solution {
+        charge: low.charge,
+        // Between two u32 elevations, so it fits.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        quadrant_elevation: quadrant_elevation as u32,
+        time_of_flight: Duration::from_secs_f64(time_of_flight),
+    }
+}
+
+/// The firing tables for every weapon and ammunition type on hand
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FiringTables {
+    /// Tables by weapon and ammunition type
+    tables: HashMap<(String, Ammunition), FiringTable>,
+}
+
+impl FiringTables {
+    /// The tables shipped with this crate
+    ///
+    /// # Panics
+    ///
+    /// Panics if a shipped table is malformed, which the tests of this crate rule out.
+    #[must_use]
+    pub fn shipped() -> Self {
+        let mut tables = Self::default();
+        for (ammunition, csv) in SHIPPED {
+            let table = FiringTable::from_reader(csv.as_bytes())
+                .expect("the shipped firing tables are well formed");
+            tables.insert(SHIPPED_WEAPON, ammunition, table);
+        }
+        tables
+    }
+
+    /// Loads the tables in the directory named by the [`DIR_VAR`] environment variable, or else those shipped with
+    /// this crate.
+    ///
+    /// # Errors
+    ///
+    /// As [`FiringTables::load`], if a directory is named.
+    pub fn from_env() -> Result<Self, Error> {
+        match env::var_os(DIR_VAR) {
+            Some(dir) => Self::load(dir),
+            None => Ok(Self::shipped()),
+        }
+    }
+
+    /// Loads every table in `dir`, laid out as `<weapon>/<ammunition>.csv`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::UnknownAmmunition`] if a file is not named for an ammunition type,
+    /// otherwise as [`FiringTable::from_path`].
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, Error> {
+        let mut tables = Self::default();
+        for weapon_dir in fs::read_dir(dir)? {
+            let weapon_dir = weapon_dir?.path();
+            if !weapon_dir.is_dir() {
+                continue;
+            }
+            let weapon = weapon_dir.file_name().unwrap_or_default().to_string_lossy();
+
+            for table_path in fs::read_dir(&weapon_dir)? {
+                let table_path = table_path?.path();
+                if table_path
+                    .extension()
+                    .is_none_or(|extension| extension != "csv")
+                {
+                    continue;
+                }
+                let stem = table_path.file_stem().unwrap_or_default().to_string_lossy();
+                let ammunition = ammunition_for(&stem)
+                    .ok_or_else(|| Error::UnknownAmmunition(stem.to_string()))?;
+
+                tables.insert(&weapon, ammunition, FiringTable::from_path(&table_path)?);
+            }
+        }
+        Ok(tables)
+    }
+
+    /// Adds the `table` for the `weapon` firing `ammunition`, replacing any table already there.
+    pub fn insert(&mut self, weapon: &str, ammunition: Ammunition, table: FiringTable) {
+        self.tables.insert((weapon.to_string(), ammunition), table);
+    }
+
+    /// The table for the `weapon` firing `ammunition`, if any
+    #[must_use]
+    pub fn get(&self, weapon: &str, ammunition: Ammunition) -> Option<&FiringTable> {
+        self.tables.get(&(weapon.to_string(), ammunition))
+    }
+
+    /// Solves for the `range` in meters, with the table for the `weapon` firing `ammunition`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::NoFiringTable`] if there is no such table, otherwise as [`FiringTable::solve`].
+    pub fn solve(
+        &self,
+        weapon: &str,
+        ammunition: Ammunition,
+        range: u32,
+    ) -> Result<Solution, Error> {
+        self.get(weapon, ammunition)
+            .ok_or_else(|| Error::NoFiringTable {
+                weapon: weapon.to_string(),
+                ammunition,
+            })?
+            .solve(range)
+    }
+}
+
+/// The name of the file, without extension, holding the table for `ammunition`
+#[must_use]
+pub fn file_stem(ammunition: Ammunition) -> &'static str {
+    match ammunition {
+        Ammunition::HighExplosive => "high_explosive",
+        Ammunition::Smoke => "smoke",
+        Ammunition::Illumination => "illumination",
+        Ammunition::WhitePhosphorus => "white_phosphorus",
+        Ammunition::Dpicm => "dpicm",
+        Ammunition::PrecisionGuided => "precision_guided",
+    }
+}
+
+/// The ammunition whose table is named `stem`, if any
+fn ammunition_for(stem: &str) -> Option<Ammunition> {
+    [
+        Ammunition::HighExplosive,
+        Ammunition::Smoke,
+        Ammunition::Illumination,
+        Ammunition::WhitePhosphorus,
+        Ammunition::Dpicm,
+        Ammunition::PrecisionGuided,
+    ]
+    .into_iter()
+    .find(|ammunition| file_stem(*ammunition) == stem)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const TABLE: &str = "\
+range,charge,quadrant_elevation,time_of_flight
+3000,2,200,12.0
+1000,1,100,5.0
+2000,1,300,11.0
+4000,2,300,16.0
+5000,2,500,24.0
+";
+
+    fn table() -> FiringTable {
+        FiringTable::from_reader(TABLE.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn test_solve_on_rows() {
+        assert_eq!(
+            table().solve(1000).unwrap(),
+            Solution {
+                charge: 1,
+                quadrant_elevation: 100,
+                time_of_flight: Duration::from_secs(5),
+            }
+        );
+        // The lowest charge that reaches is used
+        assert_eq!(table().solve(2000).unwrap().charge, 1);
+        assert_eq!(table().solve(5000).unwrap().quadrant_elevation, 500);
+    }
+
+    #[test]
+    fn test_solve_interpolates() {
+        assert_eq!(
+            table().solve(1500).unwrap(),
+            Solution {
+                charge: 1,
+                quadrant_elevation: 200,
+                time_of_flight: Duration::from_secs(8),
+            }
+        );
+        assert_eq!(
+            table().solve(4250).unwrap(),
+            Solution {
+                charge: 2,
+                quadrant_elevation: 350,
+                time_of_flight: Duration::from_secs(18),
+            }
+        );
+    }
+
+    #[test]
+    fn test_solve_out_of_range() {
+        for range in [0, 999, 2500, 5001] {
+            assert!(matches!(
+                table().solve(range),
+                Err(Error::OutOfRange {
+                    range: out_of_range,
+                    min_range: 1000,
+                    max_range: 5000,
+                }) if out_of_range == range
+            ));
+        }
+    }
+
+    #[test]
+    fn test_malformed_tables() {
+        assert!(matches!(
+            FiringTable::from_reader("range,charge,quadrant_elevation,time_of_flight\n".as_bytes()),
+            Err(Error::EmptyFiringTable)
+        ));
+        assert!(matches!(
+            FiringTable::from_reader(
+                "range,charge,quadrant_elevation,time_of_flight\n1000,one,100,5.0\n".as_bytes()
+            ),
+            Err(Error::Csv(_))
+        ));
+    }
+
+    #[test]
+    fn test_shipped_tables() {
+        let tables = FiringTables::shipped();
+        assert_eq!(
+            FiringTables::load(concat!(env!("CARGO_MANIFEST_DIR"), "/firing-tables")).unwrap(),
+            tables
+        );
+
+        for ammunition in [
+            Ammunition::HighExplosive,
+            Ammunition::Smoke,
+            Ammunition::Illumination,
+            Ammunition::WhitePhosphorus,
+            Ammunition::Dpicm,
+            Ammunition::PrecisionGuided,
+        ] {
+            assert!(tables.get("m777", ammunition).is_some(), "{ammunition:?}");
+            assert_eq!(ammunition_for(file_stem(ammunition)), Some(ammunition));
+        }
+        assert!(matches!(
+            tables.solve("m119", Ammunition::HighExplosive, 5000),
+            Err(Error::NoFiringTable { .. })
+        ));
+    }
+
+    proptest! {
+        #[test]
+        fn test_solutions_within_bracket(range in 1000u32..=5000) {
+            if let Ok(solution) = table().solve(range) {
+                prop_assert!((100..=500).contains(&solution.quadrant_elevation));
+                prop_assert!(solution.time_of_flight >= Duration::from_secs(5));
+                prop_assert!(solution.time_of_flight <= Duration::from_secs(24));
+            } else {
+                // Only the gap between the charges is unreachable
+                prop_assert!((2001..3000).contains(&range));
+            }
+        }
+
+        #[test]
+        fn test_shipped_tables_monotonic(range in 1000u32..22_000) {
+            // Within a charge, further targets take longer to reach.
+            let tables = FiringTables::shipped();
+            let near = tables.solve("m777", Ammunition::HighExplosive, range).unwrap();
+            let far = tables.solve("m777", Ammunition::HighExplosive, range + 1).unwrap();
+
+            if near.charge == far.charge {
+                prop_assert!(far.time_of_flight >= near.time_of_flight);
+                prop_assert!(far.quadrant_elevation >= near.quadrant_elevation);
+            }
+        }
+    }
+}
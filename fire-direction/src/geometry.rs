@@ -0,0 +1,336 @@
+//! Turns the target locations an FO reports into the aim of each Gun.
+//!
+//! All positions are grid positions in meters, with easting increasing to the east and northing to the north.
+//! All directions are grid directions in mils, clockwise from grid north.
+use std::f64::consts::TAU;
+
+use fdc_gun_comms::{GunPosition, TargetLocation};
+use fo_fdc_comms::{request_for_fire, subsequent_correction::SubsequentCorrection};
+
+use crate::error::Error;
+
+/// Mils in a full circle
+pub const MILS_PER_CIRCLE: u32 = 6400;
+
+/// The fewest digits in a grid coordinate, locating to 100 meters
+const MIN_GRID_DIGITS: u32 = 3;
+
+/// The most digits in a grid coordinate, locating to the meter
+const MAX_GRID_DIGITS: u32 = 5;
+
+/// A grid position in meters
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Position {
+    /// Meters east of the grid origin
+    pub easting: f64,
+    /// Meters north of the grid origin
+    pub northing: f64,
+}
+
+impl Position {
+    /// Constructs a new [`Position`]
+    ///
+    /// # Arguments
+    /// * `easting` - Meters east of the grid origin
+    /// * `northing` - Meters north of the grid origin
+    #[must_use]
+    pub fn new(easting: f64, northing: f64) -> Self {
+        Self { easting, northing }
+    }
+
+    /// Reads a grid reference, as given in a [`request_for_fire::TargetLocation::Grid`].
+    ///
+    /// Both coordinates are given to the same precision, 3 digits locating to 100 meters and 5 digits to the meter.
+    /// The precision is taken from the longer coordinate, since leading zeros are lost.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidGrid`] if a coordinate has more than 5 digits.
+    pub fn from_grid(lateral: u32, longitudinal: u32) -> Result<Self, Error> {
+        let digits = digits(lateral.max(longitudinal)).max(MIN_GRID_DIGITS);
+        if digits > MAX_GRID_DIGITS {
+            return Err(Error::InvalidGrid {
+                lateral,
+                longitudinal,
+            });
+        }
+
+        let meters_per_unit = f64::from(10u32.pow(MAX_GRID_DIGITS - digits));
+        Ok(Self::new(
+            f64::from(lateral) * meters_per_unit,
+            f64::from(longitudinal) * meters_per_unit,
+        ))
+    }
+
+    /// The position `distance` meters away in `direction` mils.
+    #[must_use]
+    pub fn offset(self, direction: u32, distance: u32) -> Self {
+        let angle = f64::from(direction) * TAU / f64::from(MILS_PER_CIRCLE);
+        let distance = f64::from(distance);
+        Self::new(
+            self.easting + distance * angle.sin(),
+            self.northing + distance * angle.cos(),
+        )
+    }
+
+    /// The range and direction from this position to the `target`, rounded to the nearest meter and mil.
+    #[must_use]
+    pub fn aim_at(self, target: Self) -> TargetLocation {
+        let east = target.easting - self.easting;
+        let north = target.northing - self.northing;
+
+        // Clockwise from north, so east is the first axis.
+        let mils = east.atan2(north) * f64::from(MILS_PER_CIRCLE) / TAU;
+        let direction = mils.round().rem_euclid(f64::from(MILS_PER_CIRCLE));
+        let range = east.hypot(north).round();
+
+        // Both are whole, non-negative, and well within range of a u32.
+        #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+        TargetLocation::new(range as u32, direction as u32)
+    }
+
+    /// Where a Subsequent Correction from an `observer` moves a burst at this position.
+    ///
+    /// Rounds are modeled as bursting on the ground, so a change to the height of burst does not move them. A burst on
+    /// top of the observer is corrected as if the observer were facing grid north.
+    #[must_use]
+    pub fn correct(self, observer: Self, correction: &SubsequentCorrection) -> Self {
+        let east = self.easting - observer.easting;
+        let north = self.northing - observer.northing;
+        let distance = east.hypot(north);
+        // The unit vector along the observer-target line, and the one to its right
+        let (along_east, along_north) = if distance > 0.0 {
+            (east / distance, north / distance)
+        } else {
+            (0.0, 1.0)
+        };
+        let (right_east, right_north) = (along_north, -along_east);
+
+        Self::new(
+            self.easting + correction.add() * along_east + correction.right() * right_east,
+            self.northing + correction.add() * along_north + correction.right() * right_north,
+        )
+    }
+}
+
+impl From<GunPosition> for Position {
+    fn from(position: GunPosition) -> Self {
+        Self::new(f64::from(position.easting), f64::from(position.northing))
+    }
+}
+
+/// Locates the target of a Request for Fire on the grid.
+///
+/// # Arguments
+/// * `observer` - Where the observer who sent the request is
+/// * `target_location` - Where the observer reported the target
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidGrid`] if a grid target location is not a valid grid reference.
+pub fn locate_target(
+    observer: Position,
+    target_location: &request_for_fire::TargetLocation,
+) -> Result<Position, Error> {
+    match *target_location {
+        request_for_fire::TargetLocation::Grid {
+            lateral,
+            longitudinal,
+        } => Position::from_grid(lateral, longitudinal),
+        request_for_fire::TargetLocation::Polar {
+            direction,
+            distance,
+        } => Ok(observer.offset(direction, distance)),
+    }
+}
+
+/// Aims a Gun at the target of a Request for Fire.
+///
+/// # Arguments
+/// * `observer` - Where the observer who sent the request is
+/// * `gun` - Where the Gun is emplaced
+/// * `target_location` - Where the observer reported the target
+///
+/// # Errors
+///
+/// Returns [`Error::InvalidGrid`] if a grid target location is not a valid grid reference.
+pub fn aim_gun(
+    observer: Position,
+    gun: GunPosition,
+    target_location: &request_for_fire::TargetLocation,
+) -> Result<TargetLocation, Error> {
+    let target = locate_target(observer, target_location)?;
+    Ok(Position::from(gun).aim_at(target))
+}
+
+/// The number of decimal digits in `value`
+fn digits(value: u32) -> u32 {
+    value.checked_ilog10().map_or(1, |log| log + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fo_fdc_comms::subsequent_correction::{Deviation, HeightCorrection, RangeCorrection};
+    use proptest::prelude::*;
+
+    /// The difference between two directions, the short way around the circle
+    fn mils_apart(left: u32, right: u32) -> u32 {
+        let apart = left.abs_diff(right) % MILS_PER_CIRCLE;
+        apart.min(MILS_PER_CIRCLE - apart)
+    }
+
+    #[test]
+    fn test_cardinal_directions() {
+        let gun = Position::new(10_000.0, 10_000.0);
+
+        for (target, direction) in [
+            (Position::new(10_000.0, 12_000.0), 0),
+            (Position::new(12_000.0, 10_000.0), 1600),
+            (Position::new(10_000.0, 8_000.0), 3200),
+            (Position::new(8_000.0, 10_000.0), 4800),
+        ] {
+            assert_eq!(gun.aim_at(target), TargetLocation::new(2000, direction));
+        }
+    }
+
+    #[test]
+    fn test_grid_precision() {
+        assert_eq!(
+            Position::from_grid(321, 654).unwrap(),
+            Position::new(32_100.0, 65_400.0)
+        );
+        assert_eq!(
+            Position::from_grid(3215, 6548).unwrap(),
+            Position::new(32_150.0, 65_480.0)
+        );
+        assert_eq!(
+            Position::from_grid(32_153, 65_487).unwrap(),
+            Position::new(32_153.0, 65_487.0)
+        );
+        // A leading zero takes its precision from the other coordinate
+        assert_eq!(
+            Position::from_grid(3215, 548).unwrap(),
+            Position::new(32_150.0, 5_480.0)
+        );
+        assert!(matches!(
+            Position::from_grid(123_456, 654),
+            Err(Error::InvalidGrid {
+                lateral: 123_456,
+                longitudinal: 654
+            })
+        ));
+    }
+
+    #[test]
+    fn test_aim_gun() {
+        let observer = Position::new(12_000.0, 14_000.0);
+        let gun = GunPosition {
+            easting: 10_000,
+            northing: 10_000,
+        };
+
+        // The observer is 2 km east and 4 km north of the gun, and the target is 3 km due north of the observer.
+        let polar = request_for_fire::TargetLocation::Polar {
+            direction: 0,
+            distance: 3000,
+        };
+        let grid = request_for_fire::TargetLocation::Grid {
+            lateral: 120,
+            longitudinal: 170,
+        };
+
+        let expected = TargetLocation::new(7280, 283);
+        assert_eq!(aim_gun(observer, gun, &polar).unwrap(), expected);
+        assert_eq!(aim_gun(observer, gun, &grid).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_correct() {
+        let observer = Position::new(10_000.0, 10_000.0);
+        let correction = SubsequentCorrection {
+            deviation: Some(Deviation::Right(100)),
+            range: Some(RangeCorrection::Add(200)),
+            height: Some(HeightCorrection::Up(50)),
+            ..SubsequentCorrection::default()
+        };
+
+        // Looking north, right is east
+        assert_eq!(
+            Position::new(10_000.0, 12_000.0).correct(observer, &correction),
+            Position::new(10_100.0, 12_200.0)
+        );
+        // Looking east, right is south
+        assert_eq!(
+            Position::new(12_000.0, 10_000.0).correct(observer, &correction),
+            Position::new(12_200.0, 9_900.0)
+        );
+        // On top of the observer, as if looking north
+        assert_eq!(
+            observer.correct(observer, &correction),
+            Position::new(10_100.0, 10_200.0)
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_correct_along_line(
+            direction in 0..MILS_PER_CIRCLE,
+            distance in 1000u32..10_000,
+            add_meters in 0u32..1000,
+            drop_meters in 0u32..1000,
+            left_meters in 0u32..1000,
+        ) {
+            let observer = Position::new(50_000.0, 50_000.0);
+            let burst = observer.offset(direction, distance);
+
+            let add = SubsequentCorrection {
+                range: Some(RangeCorrection::Add(add_meters)),
+                ..SubsequentCorrection::default()
+            };
+            let farther = burst.correct(observer, &add);
+            prop_assert!(observer.aim_at(farther).range().abs_diff(distance + add_meters) <= 1);
+
+            let drop = SubsequentCorrection {
+                range: Some(RangeCorrection::Drop(drop_meters)),
+                ..SubsequentCorrection::default()
+            };
+            let nearer = burst.correct(observer, &drop);
+            prop_assert!(observer.aim_at(nearer).range().abs_diff(distance - drop_meters) <= 1);
+
+            // A deviation moves the burst square to the line, so only as far as it says
+            let left = SubsequentCorrection {
+                deviation: Some(Deviation::Left(left_meters)),
+                ..SubsequentCorrection::default()
+            };
+            let across = burst.correct(observer, &left);
+            let moved = (across.easting - burst.easting).hypot(across.northing - burst.northing);
+            prop_assert!((moved - f64::from(left_meters)).abs() < 1e-6);
+        }
+
+        #[test]
+        fn test_offset_then_aim(
+            easting in 0u32..100_000,
+            northing in 0u32..100_000,
+            direction in 0..MILS_PER_CIRCLE,
+            distance in 1u32..30_000,
+        ) {
+            let observer = Position::new(f64::from(easting), f64::from(northing));
+
+            let aim = observer.aim_at(observer.offset(direction, distance));
+
+            prop_assert!(aim.range().abs_diff(distance) <= 1);
+            prop_assert!(aim.direction() < MILS_PER_CIRCLE);
+            // Short distances can't resolve a direction to the mil
+            prop_assert!(mils_apart(aim.direction(), direction) <= 1 + 1000 / distance);
+        }
+
+        #[test]
+        fn test_grid_within_square(lateral in 0u32..100_000, longitudinal in 0u32..100_000) {
+            let position = Position::from_grid(lateral, longitudinal).unwrap();
+
+            prop_assert!((0.0..100_000.0).contains(&position.easting));
+            prop_assert!((0.0..100_000.0).contains(&position.northing));
+        }
+    }
+}
@@ -0,0 +1,15 @@
+#![warn(
+    missing_docs,
+    clippy::doc_markdown,
+    clippy::missing_errors_doc,
+    clippy::missing_panics_doc
+)]
+//! Provides the technical fire direction an FDC performs between the FO - FDC and FDC - Gun interfaces.
+//!
+//! A Request for Fire locates a target from the observer's point of view, while each Gun needs a range and
+//! direction from its own emplacement. The [`geometry`] module bridges the two, and the [`firing_table`] module
+//! turns that range into a ballistic solution for the Gun to fire.
+
+pub mod error;
+pub mod firing_table;
+pub mod geometry;
@@ -4,12 +4,90 @@ use serde::{Deserialize, Serialize};
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
+use crate::{error::Error, message_to_observer::TargetNumber};
+
+/// The number of mils in a full circle, which every direction must be less than
+const MILS_PER_CIRCLE: u32 = 6400;
+
+/// The most characters the remarks of a BDA may hold, to keep the message within a single datagram
+pub const MAX_REMARKS_LENGTH: usize = 256;
+
 /// A Battle Damage Assessment, providing feedback to an FDC of the effect of a fire mission
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct BattleDamageAssessment {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission assessed
+    pub target_number: TargetNumber,
+    /// The estimated number of casualties
+    pub casualties: u32,
+    /// The estimated number of vehicles and equipment destroyed
+    pub equipment_destroyed: u32,
+    /// The effect the fires achieved on the target
+    pub effect: Effect,
+    /// The movement of what remains of the target, or [`None`] if it is not moving
+    pub target_movement: Option<TargetMovement>,
+    /// Anything else the FO observed, for human interpretation
+    pub remarks: String,
+}
+
+impl BattleDamageAssessment {
+    /// Checks that the assessment is consistent, and fits in a message.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidDirection`] if the target is moving in a direction of a full circle or more,
+    /// [`Error::InconsistentEffect`] if the fires had no effect yet caused casualties or destroyed equipment, or
+    /// [`Error::RemarksTooLong`] if the remarks are over [`MAX_REMARKS_LENGTH`] characters.
+    pub fn validate(&self) -> Result<(), Error> {
+        if let Some(movement) = self.target_movement {
+            if movement.direction >= MILS_PER_CIRCLE {
+                return Err(Error::InvalidDirection(movement.direction));
+            }
+        }
+        if self.effect == Effect::None && (self.casualties > 0 || self.equipment_destroyed > 0) {
+            return Err(Error::InconsistentEffect {
+                casualties: self.casualties,
+                equipment_destroyed: self.equipment_destroyed,
+            });
+        }
+        let remarks_length = self.remarks.chars().count();
+        if remarks_length > MAX_REMARKS_LENGTH {
+            return Err(Error::RemarksTooLong(remarks_length));
+        }
+        Ok(())
+    }
+}
+
+/// The effect fires achieved on a target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum Effect {
+    /// The target is out of action for good
+    Destroyed,
+    /// The target is out of action until it is repaired or reinforced
+    Neutralized,
+    /// The target was kept from acting while the fires fell
+    Suppressed,
+    /// The fires had no observed effect on the target
+    None,
+}
+
+/// The movement of a target, as observed by the FO
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(test, derive(Arbitrary))]
-pub struct BattleDamageAssessment {}
-//TODO: Fill in the struct.
+pub struct TargetMovement {
+    /// The direction the target is moving in, in mils grid
+    pub direction: u32,
+    /// The speed the target is moving at, in kilometers per hour
+    pub speed: u32,
+}
 
 #[cfg(test)]
 mod tests {
@@ -17,15 +95,79 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    /// An assessment of neutralized infantry, withdrawing to the north east
+    fn neutralized() -> BattleDamageAssessment {
+        BattleDamageAssessment {
+            src: "FO".to_string(),
+            receiver: "FDC".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+            casualties: 12,
+            equipment_destroyed: 1,
+            effect: Effect::Neutralized,
+            target_movement: Some(TargetMovement {
+                direction: 800,
+                speed: 5,
+            }),
+            remarks: "Remnants withdrawing".to_string(),
+        }
+    }
+
     #[test]
     #[ignore = "Available for debugging only"]
     fn view_json() {
-        let message = BattleDamageAssessment {};
+        let message = neutralized();
 
         let json = serde_json::to_string_pretty(&message).unwrap();
         println!("{message:?} : {json}");
     }
 
+    #[test]
+    fn test_validate() {
+        assert_eq!(neutralized().validate(), Ok(()));
+
+        let no_effect = BattleDamageAssessment {
+            casualties: 0,
+            equipment_destroyed: 0,
+            effect: Effect::None,
+            target_movement: None,
+            ..neutralized()
+        };
+        assert_eq!(no_effect.validate(), Ok(()));
+
+        let backwards = BattleDamageAssessment {
+            target_movement: Some(TargetMovement {
+                direction: MILS_PER_CIRCLE,
+                speed: 5,
+            }),
+            ..neutralized()
+        };
+        assert_eq!(
+            backwards.validate(),
+            Err(Error::InvalidDirection(MILS_PER_CIRCLE))
+        );
+
+        let casualties_without_effect = BattleDamageAssessment {
+            casualties: 1,
+            ..no_effect
+        };
+        assert_eq!(
+            casualties_without_effect.validate(),
+            Err(Error::InconsistentEffect {
+                casualties: 1,
+                equipment_destroyed: 0
+            })
+        );
+
+        let rambling = BattleDamageAssessment {
+            remarks: "a".repeat(MAX_REMARKS_LENGTH + 1),
+            ..neutralized()
+        };
+        assert_eq!(
+            rambling.validate(),
+            Err(Error::RemarksTooLong(MAX_REMARKS_LENGTH + 1))
+        );
+    }
+
     proptest! {
         #[test]
         fn test_serde(message in any::<BattleDamageAssessment>()) {
@@ -35,5 +177,15 @@ mod tests {
 
             assert_eq!(message, verified, "{}", json);
         }
+
+        #[test]
+        fn test_validate_direction(direction in any::<u32>(), speed in any::<u32>()) {
+            let moving = BattleDamageAssessment {
+                target_movement: Some(TargetMovement { direction, speed }),
+                ..neutralized()
+            };
+
+            prop_assert_eq!(moving.validate().is_ok(), direction < MILS_PER_CIRCLE);
+        }
     }
 }
@@ -0,0 +1,55 @@
+//! Container module for Cannot Comply (CANTCO) definitions
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+use crate::{Ammunition, Fuze};
+
+/// A Cannot Comply, sent by a FDC in place of a Message to Observer when a Request for Fire cannot be fired
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct CannotComply {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// Why the request cannot be fired
+    pub reason: CannotComplyReason,
+}
+
+/// The reasons a FDC cannot comply with a Request for Fire
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum CannotComplyReason {
+    /// No gun is ready to fire
+    NoGunsAvailable,
+    /// No ready gun carries the requested ammunition
+    AmmunitionUnavailable(Ammunition),
+    /// No ready gun carrying the requested ammunition can set the requested fuze
+    FuzeUnavailable(Fuze),
+    /// The target location could not be located on the grid
+    InvalidTargetLocation,
+    /// No ready gun carrying the requested ammunition can reach the target
+    TargetOutOfRange,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_serde(message in any::<CannotComply>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: CannotComply = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+    }
+}
@@ -0,0 +1,78 @@
+//! Container module for Check Fire messages
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+use crate::message_to_observer::TargetNumber;
+
+/// A Check Fire, sent by a FO to stop the fires of a mission at once, such as when rounds are falling unsafely.
+///
+/// The FDC passes the Check Fire on to every Gun on the mission as soon as it is received, before reading it back.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct CheckFire {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission to stop
+    pub target_number: TargetNumber,
+}
+
+/// A Check Fire Complete, sent by a FDC once every Gun on a mission has answered the FO's Check Fire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct CheckFireComplete {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission that was stopped
+    pub target_number: TargetNumber,
+    /// The number of Guns that ceased firing, rather than having already fired all their rounds
+    pub guns_ceased: u32,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = CheckFireComplete {
+            src: "FDC".to_string(),
+            receiver: "FO".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+            guns_ceased: 2,
+        };
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    proptest! {
+        #[test]
+        fn test_check_fire_serde(message in any::<CheckFire>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: CheckFire = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+
+        #[test]
+        fn test_check_fire_complete_serde(message in any::<CheckFireComplete>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: CheckFireComplete = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+//! Container module for Correction definitions
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+use crate::{
+    battle_damage_assessment::BattleDamageAssessment,
+    cannot_comply::CannotComply,
+    check_fire::{CheckFire, CheckFireComplete},
+    end_of_mission::{EndOfMission, RecordAsTarget, Repeat},
+    message_to_observer::MessageToObserver,
+    request_for_fire::WarnOrder,
+    shot_fire::{RoundsComplete, Shot, Splash},
+    subsequent_correction::SubsequentCorrection,
+};
+
+/// A Correction, sent in answer to a readback that does not match the message it reads back.
+///
+/// It carries the message as it should have been read back, and the receiver reads that back in turn. The sender keeps
+/// correcting until a readback matches.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum Correction {
+    /// Corrects a FDC's readback of a Request for Fire
+    RequestForFire(WarnOrder),
+    /// Corrects a FO's readback of a Message to Observer
+    MessageToObserver(MessageToObserver),
+    /// Corrects a FO's readback of a Cannot Comply
+    CannotComply(CannotComply),
+    /// Corrects a FO's readback of a Shot
+    Shot(Shot),
+    /// Corrects a FO's readback of a Splash
+    Splash(Splash),
+    /// Corrects a FO's readback of a Rounds Complete
+    RoundsComplete(RoundsComplete),
+    /// Corrects a FDC's readback of a Subsequent Correction
+    SubsequentCorrection(SubsequentCorrection),
+    /// Corrects a FDC's readback of a Battle Damage Assessment
+    BattleDamageAssessment(BattleDamageAssessment),
+    /// Corrects a FDC's readback of a Check Fire
+    CheckFire(CheckFire),
+    /// Corrects a FO's readback of a Check Fire Complete
+    CheckFireComplete(CheckFireComplete),
+    /// Corrects a FDC's readback of a Repeat
+    Repeat(Repeat),
+    /// Corrects a FDC's readback of a Record as Target
+    RecordAsTarget(RecordAsTarget),
+    /// Corrects a FDC's readback of an End of Mission
+    EndOfMission(EndOfMission),
+}
+
+/// The names of the fields of the `readback` that differ from the `original` message it reads back, or none if either
+/// is not a struct.
+#[must_use]
+pub fn corrected_fields<T: Serialize>(original: &T, readback: &T) -> Vec<String> {
+    match (
+        serde_json::to_value(original),
+        serde_json::to_value(readback),
+    ) {
+        (Ok(Value::Object(original)), Ok(Value::Object(readback))) => original
+            .into_iter()
+            .filter(|(field, value)| readback.get(field) != Some(value))
+            .map(|(field, _)| field)
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{message_to_observer::TargetNumber, shot_fire::first_shot};
+    use proptest::prelude::*;
+
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = Correction::Shot(first_shot());
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    #[test]
+    fn test_corrected_fields() {
+        let shot = first_shot();
+        assert!(corrected_fields(&shot, &shot).is_empty());
+
+        let misheard = Shot {
+            volley: 2,
+            target_number: TargetNumber::new("AN2010").unwrap(),
+            ..first_shot()
+        };
+        let mut fields = corrected_fields(&shot, &misheard);
+        fields.sort();
+        assert_eq!(fields, vec!["target_number", "volley"]);
+    }
+
+    proptest! {
+        #[test]
+        fn test_serde(message in any::<Correction>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: Correction = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+    }
+}
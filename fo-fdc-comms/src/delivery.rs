@@ -0,0 +1,630 @@
+//! Provides a delivery tracker, for delivering messages reliably over the FO-FDC link, which is UDP and may drop them.
+//!
+//! Every message but a [`SolidReadback`] is answered by the other side: an original message by its readback, a
+//! readback by a [`SolidReadback`] or a [`Correction`], and a [`Correction`] by a readback in turn. The tracker holds
+//! each message sent until its answer is received, has it resent whenever the answer is overdue, and gives it up as
+//! undeliverable once it has been resent too many times.
+//!
+//! Each answer names the message it answers by its sequence number, in [`Envelope::in_reply_to`], so that an answer
+//! releases the very message it answers even when several of the same kind are awaiting theirs.
+//!
+//! The tracker also remembers the answers it has sent, so that a message received again, because the answer to it was
+//! lost, can be answered again without the state machine handling it twice.
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    correction::Correction, envelope::Envelope, message_to_observer::TargetNumber,
+    readback::SolidReadback, FoFdcMessage,
+};
+
+/// How long the answer to a message may take before the message is resent
+pub const DEFAULT_RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many times a message is resent before it is given up as undeliverable
+pub const DEFAULT_RETRY_LIMIT: u32 = 3;
+
+/// How many of the answers sent, and of the messages received awaiting one, are remembered
+const ANSWER_HISTORY: usize = 64;
+
+/// What is to be done with an overdue message
+#[derive(Debug, Clone)]
+pub enum Overdue {
+    /// The message is to be sent again
+    Resend(Envelope),
+    /// The message was resent as many times as allowed and still went unanswered
+    Failed(Envelope),
+}
+
+/// Where a message stands in its readback exchange
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stage {
+    /// The message being read back, whether sent first or as a [`Correction`]
+    Original,
+    /// A readback of a message
+    Readback,
+    /// A [`SolidReadback`] or [`Correction`] of a readback
+    Verdict,
+}
+
+/// The answer a message awaits: a message of the given kind, at the given stage
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Answer {
+    kind: SolidReadback,
+    stage: Stage,
+}
+
+/// A sent message awaiting its answer
+#[derive(Debug, Clone)]
+struct Outstanding {
+    /// The message sent
+    envelope: Envelope,
+    /// The answer it awaits
+    awaiting: Answer,
+    /// How many times it has been resent
+    retries: u32,
+    /// When it is next due to be resent, should it still be unanswered
+    resend_at: Instant,
+}
+
+/// A received message awaiting our answer
+#[derive(Debug, Clone)]
+struct Unanswered {
+    /// The sender's callsign
+    src: String,
+    /// The message's sequence number
+    sequence: u64,
+    /// The mission the message is for
+    mission: Option<TargetNumber>,
+    /// The answer it awaits
+    awaiting: Answer,
+}
+
+/// An answer sent to a received message
+#[derive(Debug, Clone)]
+struct Answered {
+    /// The callsign of the sender of the message answered
+    src: String,
+    /// The sequence number of the message answered
+    sequence: u64,
+    /// The answer sent
+    answer: Envelope,
+}
+
+/// Tracks the messages sent over the link that are still awaiting their answer, and the answers sent
+#[derive(Debug, Clone)]
+pub struct Delivery {
+    /// How long the answer to a message may take before the message is resent
+    retransmit_timeout: Duration,
+    /// How many times a message is resent before it is given up
+    retry_limit: u32,
+    /// The messages awaiting their answer, oldest first
+    outstanding: VecDeque<Outstanding>,
+    /// The messages received that have yet to be answered, oldest first
+    unanswered: VecDeque<Unanswered>,
+    /// The answers sent, oldest first
+    answered: VecDeque<Answered>,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        Self::new(DEFAULT_RETRANSMIT_TIMEOUT, DEFAULT_RETRY_LIMIT)
+    }
+}
+
+impl Delivery {
+    /// Creates a tracker that resends a message each `retransmit_timeout` it goes unanswered, up to `retry_limit` times.
+    #[must_use]
+    pub fn new(retransmit_timeout: Duration, retry_limit: u32) -> Self {
+        Self {
+            retransmit_timeout,
+            retry_limit,
+            outstanding: VecDeque::new(),
+            unanswered: VecDeque::new(),
+            answered: VecDeque::new(),
+        }
+    }
+
+    /// Addresses `envelope` as the answer to the received message it answers, if any, naming that message in
+    /// [`Envelope::in_reply_to`] and putting `envelope` in that message's mission, should it have one.
+    ///
+    /// The message answered is the oldest awaiting an answer of its kind, preferring one for the same mission as
+    /// `envelope`.
+    pub fn reply(&self, envelope: &mut Envelope) {
+        if let Some(answered) = self.answered_by(envelope) {
+            envelope.in_reply_to = Some(answered.sequence);
+            if answered.mission.is_some() {
+                envelope.mission = answered.mission.clone();
+            }
+        }
+    }
+
+    /// The received message `envelope` answers, if any
+    fn answered_by(&self, envelope: &Envelope) -> Option<&Unanswered> {
+        let answer = answer_given_by(&envelope.message)?;
+        let mut awaiting = self
+            .unanswered
+            .iter()
+            .filter(|unanswered| unanswered.awaiting == answer);
+        awaiting
+            .clone()
+            .find(|unanswered| unanswered.mission == envelope.mission)
+            .or_else(|| awaiting.next())
+    }
+
+    /// Records that `envelope` was sent at `now`, to be held until its answer is received.
+    ///
+    /// A [`SolidReadback`] is never answered, so it is not held. An answer that does not say which message it answers is
+    /// taken to answer the one [`Delivery::reply`] picks.
+    pub fn sent(&mut self, envelope: &Envelope, now: Instant) {
+        let in_reply_to = envelope
+            .in_reply_to
+            .or_else(|| self.answered_by(envelope).map(|answered| answered.sequence));
+        if let Some(sequence) = in_reply_to {
+            if let Some(index) = self
+                .unanswered
+                .iter()
+                .position(|unanswered| unanswered.sequence == sequence)
+            {
+                let unanswered = self.unanswered.remove(index).expect("index is in bounds");
+                if self.answered.len() == ANSWER_HISTORY {
+                    self.answered.pop_front();
+                }
+                self.answered.push_back(Answered {
+                    src: unanswered.src,
+                    sequence: unanswered.sequence,
+                    answer: envelope.clone(),
+                });
+            }
+        }
+
+        if let Some(awaiting) = answer_awaited_by(&envelope.message) {
+            self.outstanding.push_back(Outstanding {
+                envelope: envelope.clone(),
+                awaiting,
+                retries: 0,
+                resend_at: now + self.retransmit_timeout,
+            });
+        }
+    }
+
+    /// Records that `envelope` was received, releasing the held message it answers.
+    ///
+    /// An answer that does not say which message it answers releases the oldest held message it could answer. An
+    /// envelope received again is not awaiting a second answer, but may still release the message it answers.
+    ///
+    /// Returns whether a held message was released.
+    pub fn received(&mut self, envelope: &Envelope) -> bool {
+        if let Some(awaiting) = answer_awaited_by(&envelope.message) {
+            let is_known =
+                |src: &str, sequence: u64| src == envelope.src && sequence == envelope.sequence;
+            if !self
+                .unanswered
+                .iter()
+                .any(|unanswered| is_known(&unanswered.src, unanswered.sequence))
+                && !self
+                    .answered
+                    .iter()
+                    .any(|answered| is_known(&answered.src, answered.sequence))
+            {
+                if self.unanswered.len() == ANSWER_HISTORY {
+                    self.unanswered.pop_front();
+                }
+                self.unanswered.push_back(Unanswered {
+                    src: envelope.src.clone(),
+                    sequence: envelope.sequence,
+                    mission: envelope.mission.clone(),
+                    awaiting,
+                });
+            }
+        }
+
+        let Some(answer) = answer_given_by(&envelope.message) else {
+            return false;
+        };
+        match self.outstanding.iter().position(|outstanding| {
+            outstanding.awaiting == answer
+                && envelope
+                    .in_reply_to
+                    .is_none_or(|sequence| outstanding.envelope.sequence == sequence)
+        }) {
+            Some(index) => {
+                self.outstanding.remove(index);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// The answer already sent to `envelope`, if any, for answering it again when it is received again.
+    #[must_use]
+    pub fn answer_to(&self, envelope: &Envelope) -> Option<&Envelope> {
+        self.answered
+            .iter()
+            .rev()
+            .find(|answered| answered.src == envelope.src && answered.sequence == envelope.sequence)
+            .map(|answered| &answered.answer)
+    }
+
+    /// Forgets the messages received from `src`, and the answers sent to them, as `src` has started over.
+    ///
+    /// The tracker is for the link with `src` alone, so every message it holds was sent to `src`, which no longer knows
+    /// what they are about. They are given up, and returned as undeliverable.
+    #[must_use]
+    pub fn forget(&mut self, src: &str) -> Vec<Envelope> {
+        self.unanswered.retain(|unanswered| unanswered.src != src);
+        self.answered.retain(|answered| answered.src != src);
+        self.outstanding
+            .drain(..)
+            .map(|outstanding| outstanding.envelope)
+            .collect()
+    }
+
+    /// Takes what is to be done at `now` with each held message whose answer is overdue.
+    ///
+    /// Messages to be resent are held again until their next timeout, and messages that failed are no longer held.
+    pub fn poll(&mut self, now: Instant) -> Vec<Overdue> {
+        let mut overdue = Vec::new();
+        let retransmit_timeout = self.retransmit_timeout;
+        let retry_limit = self.retry_limit;
+        self.outstanding.retain_mut(|outstanding| {
+            if outstanding.resend_at > now {
+                true
+            } else if outstanding.retries < retry_limit {
+                outstanding.retries += 1;
+                outstanding.resend_at = now + retransmit_timeout;
+                overdue.push(Overdue::Resend(outstanding.envelope.clone()));
+                true
+            } else {
+                overdue.push(Overdue::Failed(outstanding.envelope.clone()));
+                false
+            }
+        });
+        overdue
+    }
+
+    /// Whether any message is still awaiting its answer
+    #[must_use]
+    pub fn is_idle(&self) -> bool {
+        self.outstanding.is_empty()
+    }
+}
+
+/// The answer `message` awaits, or [`None`] for a [`SolidReadback`], which is never answered
+fn answer_awaited_by(message: &FoFdcMessage) -> Option<Answer> {
+    let (kind, stage) = classify(message);
+    match (stage, message) {
+        (_, FoFdcMessage::SolidReadback(..)) => None,
+        (Stage::Original | Stage::Verdict, _) => Some(Answer {
+            kind,
+            stage: Stage::Readback,
+        }),
+        (Stage::Readback, _) => Some(Answer {
+            kind,
+            stage: Stage::Verdict,
+        }),
+    }
+}
+
+/// The answer `message` gives, or [`None`] for an original message, which answers nothing
+fn answer_given_by(message: &FoFdcMessage) -> Option<Answer> {
+    let (kind, stage) = classify(message);
+    (stage != Stage::Original).then_some(Answer { kind, stage })
+}
+
+/// The kind of a message, named as its [`SolidReadback`] would be, and its stage in the readback exchange
+fn classify(message: &FoFdcMessage) -> (SolidReadback, Stage) {
+    use FoFdcMessage as M;
+    use SolidReadback as K;
+    match message {
+        M::RequestForFire(..) => (K::RequestForFire, Stage::Original),
+        M::RequestForFireConfirm(..) => (K::RequestForFire, Stage::Readback),
+        M::MessageToObserver(..) => (K::MessageToObserver, Stage::Original),
+        M::MessageToObserverConfirm(..) => (K::MessageToObserver, Stage::Readback),
+        M::CannotComply(..) => (K::CannotComply, Stage::Original),
+        M::CannotComplyConfirm(..) => (K::CannotComply, Stage::Readback),
+        M::Shot(..) => (K::Shot, Stage::Original),
+        M::ShotConfirm(..) => (K::Shot, Stage::Readback),
+        M::Splash(..) => (K::Splash, Stage::Original),
+        M::SplashConfirm(..) => (K::Splash, Stage::Readback),
+        M::RoundsComplete(..) => (K::RoundsComplete, Stage::Original),
+        M::RoundsCompleteConfirm(..) => (K::RoundsComplete, Stage::Readback),
+        M::SubsequentCorrection(..) => (K::SubsequentCorrection, Stage::Original),
+        M::SubsequentCorrectionConfirm(..) => (K::SubsequentCorrection, Stage::Readback),
+        M::BattleDamageAssessment(..) => (K::BattleDamageAssessment, Stage::Original),
+        M::BattleDamageAssessmentConfirm(..) => (K::BattleDamageAssessment, Stage::Readback),
+        M::CheckFire(..) => (K::CheckFire, Stage::Original),
+        M::CheckFireConfirm(..) => (K::CheckFire, Stage::Readback),
+        M::CheckFireComplete(..) => (K::CheckFireComplete, Stage::Original),
+        M::CheckFireCompleteConfirm(..) => (K::CheckFireComplete, Stage::Readback),
+        M::Repeat(..) => (K::Repeat, Stage::Original),
+        M::RepeatConfirm(..) => (K::Repeat, Stage::Readback),
+        M::RecordAsTarget(..) => (K::RecordAsTarget, Stage::Original),
+        M::RecordAsTargetConfirm(..) => (K::RecordAsTarget, Stage::Readback),
+        M::EndOfMission(..) => (K::EndOfMission, Stage::Original),
+        M::EndOfMissionConfirm(..) => (K::EndOfMission, Stage::Readback),
+        M::SolidReadback(kind) => (*kind, Stage::Verdict),
+        M::Correction(correction) => (
+            match correction {
+                Correction::RequestForFire(..) => K::RequestForFire,
+                Correction::MessageToObserver(..) => K::MessageToObserver,
+                Correction::CannotComply(..) => K::CannotComply,
+                Correction::Shot(..) => K::Shot,
+                Correction::Splash(..) => K::Splash,
+                Correction::RoundsComplete(..) => K::RoundsComplete,
+                Correction::SubsequentCorrection(..) => K::SubsequentCorrection,
+                Correction::BattleDamageAssessment(..) => K::BattleDamageAssessment,
+                Correction::CheckFire(..) => K::CheckFire,
+                Correction::CheckFireComplete(..) => K::CheckFireComplete,
+                Correction::Repeat(..) => K::Repeat,
+                Correction::RecordAsTarget(..) => K::RecordAsTarget,
+                Correction::EndOfMission(..) => K::EndOfMission,
+            },
+            Stage::Verdict,
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        end_of_mission::EndOfMission,
+        envelope::Outbox,
+        message_to_observer::TargetNumber,
+        shot_fire::{first_shot, Shot},
+    };
+    use proptest::prelude::*;
+
+    /// An End of Mission for AN2001
+    fn end_of_mission() -> EndOfMission {
+        EndOfMission {
+            src: "FO".to_string(),
+            receiver: "FDC".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_readback_exchange() {
+        let start = Instant::now();
+        let mut fo = Outbox::new("FO");
+        let mut fdc = Outbox::new("FDC");
+        let mut delivery = Delivery::default();
+
+        // The FO's side of an End of Mission, each message released by the answer to it.
+        delivery.sent(
+            &fo.seal(FoFdcMessage::EndOfMission(end_of_mission())),
+            start,
+        );
+        assert!(
+            !delivery.received(&fdc.seal(FoFdcMessage::SolidReadback(SolidReadback::EndOfMission)))
+        );
+        assert!(delivery.received(&fdc.seal(FoFdcMessage::EndOfMissionConfirm(end_of_mission()))));
+        assert!(delivery.is_idle());
+
+        delivery.sent(
+            &fo.seal(FoFdcMessage::SolidReadback(SolidReadback::EndOfMission)),
+            start,
+        );
+        assert!(delivery.is_idle());
+
+        // The FO's side of a Shot that it read back wrong.
+        delivery.sent(&fo.seal(FoFdcMessage::ShotConfirm(first_shot())), start);
+        assert!(
+            delivery.received(&fdc.seal(FoFdcMessage::Correction(Correction::Shot(first_shot()))))
+        );
+        assert!(delivery.is_idle());
+
+        // The FDC's side of the same Correction.
+        delivery.sent(
+            &fdc.seal(FoFdcMessage::Correction(Correction::Shot(first_shot()))),
+            start,
+        );
+        assert!(!delivery.received(&fo.seal(FoFdcMessage::Shot(first_shot()))));
+        assert!(delivery.received(&fo.seal(FoFdcMessage::ShotConfirm(first_shot()))));
+        assert!(delivery.is_idle());
+        assert!(delivery.poll(start + Duration::from_secs(60)).is_empty());
+    }
+
+    #[test]
+    fn test_answers_out_of_order() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(1);
+        let mut fo = Outbox::new("FO");
+        let mut fdc = Outbox::new("FDC");
+        let mut fo_delivery = Delivery::new(timeout, DEFAULT_RETRY_LIMIT);
+        let mut fdc_delivery = Delivery::new(timeout, DEFAULT_RETRY_LIMIT);
+
+        // Two Shots in flight at once, each read back in turn.
+        let second_shot = Shot {
+            volley: 2,
+            ..first_shot()
+        };
+        let shots = [
+            fdc.seal(FoFdcMessage::Shot(first_shot())),
+            fdc.seal(FoFdcMessage::Shot(second_shot.clone())),
+        ];
+        let mut readbacks = Vec::new();
+        for (shot, readback) in shots.iter().zip([first_shot(), second_shot]) {
+            fdc_delivery.sent(shot, start);
+            fo_delivery.received(shot);
+
+            let mut readback = fo.seal(FoFdcMessage::ShotConfirm(readback));
+            fo_delivery.reply(&mut readback);
+            fo_delivery.sent(&readback, start);
+            readbacks.push(readback);
+        }
+        assert_eq!(readbacks[0].in_reply_to, Some(shots[0].sequence));
+        assert_eq!(readbacks[1].in_reply_to, Some(shots[1].sequence));
+
+        // The second readback overtakes the first, and releases only the second Shot.
+        assert!(fdc_delivery.received(&readbacks[1]));
+        assert!(matches!(
+            fdc_delivery.poll(start + timeout).as_slice(),
+            [Overdue::Resend(Envelope { sequence, .. })] if *sequence == shots[0].sequence
+        ));
+
+        // Received again, the second readback releases nothing more.
+        assert!(!fdc_delivery.received(&readbacks[1]));
+        assert!(fdc_delivery.received(&readbacks[0]));
+        assert!(fdc_delivery.is_idle());
+    }
+
+    #[test]
+    fn test_reply() {
+        let mut fo = Outbox::new("FO");
+        let mut fdc = Outbox::new("FDC");
+        let mut delivery = Delivery::default();
+        let mission = TargetNumber::new("AN2002").unwrap();
+
+        let readback = fdc.seal_for(
+            Some(mission.clone()),
+            FoFdcMessage::EndOfMissionConfirm(end_of_mission()),
+        );
+        delivery.received(&readback);
+
+        // The answer names the readback, and takes on its mission.
+        let mut solid = fo.seal(FoFdcMessage::SolidReadback(SolidReadback::EndOfMission));
+        assert_eq!(solid.mission, None);
+        delivery.reply(&mut solid);
+        assert_eq!(solid.in_reply_to, Some(readback.sequence));
+        assert_eq!(solid.mission, Some(mission));
+
+        // A message that answers nothing is left as it is.
+        let mut original = fo.seal(FoFdcMessage::EndOfMission(end_of_mission()));
+        delivery.reply(&mut original);
+        assert_eq!(original.in_reply_to, None);
+    }
+
+    #[test]
+    fn test_answer_to() {
+        let start = Instant::now();
+        let mut fo = Outbox::new("FO");
+        let mut fdc = Outbox::new("FDC");
+        let mut delivery = Delivery::default();
+
+        let shot = fdc.seal(FoFdcMessage::Shot(first_shot()));
+        delivery.received(&shot);
+        assert!(delivery.answer_to(&shot).is_none());
+
+        let readback = fo.seal(FoFdcMessage::ShotConfirm(first_shot()));
+        delivery.sent(&readback, start);
+        assert_eq!(
+            delivery.answer_to(&shot).map(|answer| answer.sequence),
+            Some(readback.sequence)
+        );
+
+        // A Solid Readback is never answered
+        let solid = fdc.seal(FoFdcMessage::SolidReadback(SolidReadback::Shot));
+        delivery.received(&solid);
+        delivery.sent(
+            &fo.seal(FoFdcMessage::EndOfMission(end_of_mission())),
+            start,
+        );
+        assert!(delivery.answer_to(&solid).is_none());
+    }
+
+    #[test]
+    fn test_resend_then_fail() {
+        let start = Instant::now();
+        let timeout = Duration::from_secs(1);
+        let mut delivery = Delivery::new(timeout, 2);
+        let shot = Outbox::new("FDC").seal(FoFdcMessage::Shot(first_shot()));
+        delivery.sent(&shot, start);
+
+        assert!(delivery.poll(start + timeout / 2).is_empty());
+        assert!(matches!(
+            delivery.poll(start + timeout).as_slice(),
+            [Overdue::Resend(Envelope {
+                sequence: 1,
+                message: FoFdcMessage::Shot(..),
+                ..
+            })]
+        ));
+        assert!(delivery.poll(start + timeout * 3 / 2).is_empty());
+        assert!(matches!(
+            delivery.poll(start + timeout * 2).as_slice(),
+            [Overdue::Resend(Envelope {
+                sequence: 1,
+                message: FoFdcMessage::Shot(..),
+                ..
+            })]
+        ));
+        assert!(matches!(
+            delivery.poll(start + timeout * 3).as_slice(),
+            [Overdue::Failed(Envelope {
+                sequence: 1,
+                message: FoFdcMessage::Shot(..),
+                ..
+            })]
+        ));
+        assert!(delivery.is_idle());
+    }
+
+    #[test]
+    fn test_forget() {
+        let start = Instant::now();
+        let mut fo = Outbox::new("FO");
+        let mut fdc = Outbox::new("FDC");
+        let mut delivery = Delivery::default();
+
+        let original = fdc.seal(FoFdcMessage::EndOfMission(end_of_mission()));
+        delivery.received(&original);
+        let readback = fo.seal(FoFdcMessage::EndOfMissionConfirm(end_of_mission()));
+        delivery.sent(&readback, start);
+
+        // The FDC started over, so neither its message nor the readback it has yet to answer is of use any more.
+        let given_up = delivery.forget("FDC");
+        assert_eq!(
+            given_up
+                .iter()
+                .map(|envelope| envelope.sequence)
+                .collect::<Vec<_>>(),
+            vec![readback.sequence]
+        );
+        assert!(delivery.answer_to(&original).is_none());
+        assert!(delivery.is_idle());
+        assert!(delivery.poll(start + Duration::from_secs(60)).is_empty());
+    }
+
+    proptest! {
+        #[test]
+        fn test_resent_retry_limit_times(retry_limit in 0u32..10, answered_after in prop::option::of(1u32..12)) {
+            let start = Instant::now();
+            let timeout = Duration::from_secs(1);
+            let mut delivery = Delivery::new(timeout, retry_limit);
+            delivery.sent(&Outbox::new("FO").seal(FoFdcMessage::EndOfMission(end_of_mission())), start);
+            let readback = Outbox::new("FDC").seal(FoFdcMessage::EndOfMissionConfirm(end_of_mission()));
+
+            let mut resent = 0;
+            let mut failed = 0;
+            for tick in 1..=retry_limit + 1 {
+                if answered_after == Some(tick) {
+                    prop_assert!(delivery.received(&readback));
+                }
+                for overdue in delivery.poll(start + timeout * tick) {
+                    match overdue {
+                        Overdue::Resend(..) => resent += 1,
+                        Overdue::Failed(..) => failed += 1,
+                    }
+                }
+            }
+
+            match answered_after {
+                Some(answered_after) if answered_after <= retry_limit + 1 => {
+                    prop_assert_eq!(resent, answered_after - 1);
+                    prop_assert_eq!(failed, 0);
+                }
+                _ => {
+                    prop_assert_eq!(resent, retry_limit);
+                    prop_assert_eq!(failed, 1);
+                }
+            }
+            prop_assert!(delivery.is_idle());
+        }
+    }
+}
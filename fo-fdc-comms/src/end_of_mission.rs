@@ -0,0 +1,98 @@
+//! Container module for End of Mission, Repeat, and Record as Target messages
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+use crate::message_to_observer::TargetNumber;
+
+/// An End of Mission, sent by a FO once it needs no more fires on a target.
+///
+/// Once read back, the FDC stops any fires still underway and is free for the next Request for Fire.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct EndOfMission {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission being ended
+    pub target_number: TargetNumber,
+}
+
+/// A Repeat, sent by a FO after Rounds Complete to have the last fires for effect fired again with the same data.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct Repeat {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission to repeat
+    pub target_number: TargetNumber,
+}
+
+/// A Record as Target, sent by a FO to have the FDC keep the target of a fire mission under its target number, so
+/// that later missions can be fired on it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct RecordAsTarget {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number to record the target under
+    pub target_number: TargetNumber,
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = EndOfMission {
+            src: "FO".to_string(),
+            receiver: "FDC".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+        };
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    proptest! {
+        #[test]
+        fn test_end_of_mission_serde(message in any::<EndOfMission>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: EndOfMission = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+
+        #[test]
+        fn test_repeat_serde(message in any::<Repeat>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: Repeat = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+
+        #[test]
+        fn test_record_as_target_serde(message in any::<RecordAsTarget>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: RecordAsTarget = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+    }
+}
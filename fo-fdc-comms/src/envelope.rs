@@ -0,0 +1,353 @@
+//! Container module for the Envelope every message is wrapped in on the wire.
+//!
+//! Each side of the link numbers the envelopes it seals in sequence, starting from 1, and resends an envelope
+//! unchanged, so the receiving side can tell a message it has already received from a new one, and notice any that
+//! have gone missing. Every envelope also carries the session of the side that sealed it, which is new each time that
+//! side starts over, so the receiving side can tell a sender that has started over even if its first envelopes are lost.
+use std::{
+    collections::{BTreeSet, HashMap},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{correction::Correction, message_to_observer::TargetNumber, FoFdcMessage};
+
+/// How far behind the newest message from a sender a missing message may fall before it is no longer waited on
+const MISSING_WINDOW: u64 = 1024;
+
+/// A message as it is sent over the link
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct Envelope {
+    /// The sender's callsign
+    pub src: String,
+    /// When the sender started the sequence, in milliseconds since the Unix epoch
+    #[serde(default)]
+    pub session: u64,
+    /// The place of the message among those sent by the sender in the session, counting from 1
+    pub sequence: u64,
+    /// The target number of the fire mission the message is for, or [`None`] if the mission has not been given one
+    pub mission: Option<TargetNumber>,
+    /// The sequence number of the message from the other side that this one answers, or [`None`] if it answers none
+    #[serde(default)]
+    pub in_reply_to: Option<u64>,
+    /// When the message was first sent, in milliseconds since the Unix epoch
+    pub timestamp: u64,
+    /// The message itself
+    pub message: FoFdcMessage,
+}
+
+/// Seals the messages sent by one side of the link in envelopes
+#[derive(Debug, Clone)]
+pub struct Outbox {
+    /// The callsign of the side sending
+    src: String,
+    /// When the outbox was created, which starts its sequence
+    session: u64,
+    /// The sequence number of the last envelope sealed
+    sequence: u64,
+    /// The mission of the last envelope sealed
+    mission: Option<TargetNumber>,
+}
+
+impl Outbox {
+    /// Creates an outbox for messages sent by `src`
+    #[must_use]
+    pub fn new(src: &str) -> Self {
+        Self {
+            src: src.to_string(),
+            session: now_millis(),
+            sequence: 0,
+            mission: None,
+        }
+    }
+
+    /// Seals `message` in the next envelope in sequence.
+    ///
+    /// The envelope is for the mission of the message's target number. A message without one is for the same mission
+    /// as the last, unless it is about a Request for Fire, which starts a mission without a target number.
+    pub fn seal(&mut self, message: FoFdcMessage) -> Envelope {
+        self.mission = match (&message, message.target_number()) {
+            (_, Some(target_number)) => Some(target_number.clone()),
+            (
+                FoFdcMessage::RequestForFire(..)
+                | FoFdcMessage::RequestForFireConfirm(..)
+                | FoFdcMessage::Correction(Correction::RequestForFire(..)),
+                None,
+            ) => None,
+            (_, None) => self.mission.take(),
+        };
+        self.seal_for(self.mission.clone(), message)
+    }
+
+    /// Seals `message` in the next envelope in sequence, for the given `mission`.
+    ///
+    /// For a side running several missions at once, which knows better than the message which mission it is for.
+    pub fn seal_for(&mut self, mission: Option<TargetNumber>, message: FoFdcMessage) -> Envelope {
+        self.mission = mission;
+        self.sequence += 1;
+        Envelope {
+            src: self.src.clone(),
+            session: self.session,
+            sequence: self.sequence,
+            mission: self.mission.clone(),
+            in_reply_to: None,
+            timestamp: now_millis(),
+            message,
+        }
+    }
+}
+
+/// How an envelope arrived, compared to those received from its sender before it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Arrival {
+    /// Later in sequence than any before it, after `missed` envelopes that have yet to arrive
+    New {
+        /// The envelopes skipped over in sequence
+        missed: u64,
+    },
+    /// One that was skipped over in sequence earlier
+    Late,
+    /// The first from a sender that has started over in a new session
+    Restarted,
+    /// One already received
+    Duplicate,
+    /// One sealed before the sender started over, which is of no more use
+    Stale,
+}
+
+/// The sequence numbers received from a single sender
+#[derive(Debug, Clone, Default)]
+struct Window {
+    /// The highest sequence number received
+    highest: u64,
+    /// The sequence numbers skipped over, that have yet to arrive
+    missing: BTreeSet<u64>,
+    /// The session of the sequence, once an envelope in it is received
+    session: Option<u64>,
+}
+
+/// Checks the envelopes received over the link against those received before, by sender
+#[derive(Debug, Clone, Default)]
+pub struct Inbox {
+    /// The sequence numbers received, by sender callsign
+    senders: HashMap<String, Window>,
+}
+
+impl Inbox {
+    /// Creates an inbox that has yet to receive anything
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records that `envelope` arrived, and how it arrived.
+    pub fn arrive(&mut self, envelope: &Envelope) -> Arrival {
+        let window = self.senders.entry(envelope.src.clone()).or_default();
+        let sequence = envelope.sequence;
+
+        // An envelope from a later session is from a sender that has started over, and one from an earlier session was
+        // sent before it did.
+        let restarted = match window.session {
+            Some(session) if envelope.session < session => return Arrival::Stale,
+            Some(session) => envelope.session > session,
+            None => false,
+        };
+        if restarted {
+            *window = Window::default();
+        }
+        window.session = Some(envelope.session);
+
+        let arrival = if sequence > window.highest {
+            let missed = sequence - window.highest - 1;
+            window.missing.extend(window.highest + 1..sequence);
+            window.highest = sequence;
+            window.missing = window
+                .missing
+                .split_off(&window.highest.saturating_sub(MISSING_WINDOW));
+            Arrival::New { missed }
+        } else if window.missing.remove(&sequence) {
+            Arrival::Late
+        } else {
+            Arrival::Duplicate
+        };
+
+        if restarted {
+            Arrival::Restarted
+        } else {
+            arrival
+        }
+    }
+}
+
+/// The time now, in milliseconds since the Unix epoch
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| {
+            since_epoch.as_millis().try_into().unwrap_or(u64::MAX)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use crate::{
+        readback::SolidReadback,
+        request_for_fire::{MissionType, TargetDescription, TargetLocation, WarnOrder},
+        shot_fire::first_shot,
+    };
+    use proptest::prelude::*;
+
+    /// An adjust fire request on a grid
+    fn request() -> WarnOrder {
+        WarnOrder {
+            src: "FO".to_string(),
+            receiver: "FDC".to_string(),
+            response_addr: None,
+            mission_type: MissionType::AdjustFire,
+            target_location: TargetLocation::Grid {
+                lateral: 135,
+                longitudinal: 182,
+            },
+            target_description: TargetDescription::default(),
+            danger_close: false,
+            ammunition: None,
+            fuze: None,
+            method_of_fire: None,
+        }
+    }
+
+    /// An envelope from `src` with the given `session` and `sequence`
+    fn envelope(src: &str, session: u64, sequence: u64) -> Envelope {
+        Envelope {
+            src: src.to_string(),
+            session,
+            sequence,
+            mission: None,
+            in_reply_to: None,
+            timestamp: session + sequence,
+            message: FoFdcMessage::SolidReadback(SolidReadback::Shot),
+        }
+    }
+
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = Outbox::new("FDC").seal(FoFdcMessage::Shot(first_shot()));
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    #[test]
+    fn test_serde() {
+        let envelope = Outbox::new("FDC").seal(FoFdcMessage::Shot(first_shot()));
+        let json = serde_json::to_string_pretty(&envelope).unwrap();
+
+        let verified: Envelope = serde_json::from_str(&json).unwrap();
+
+        assert_eq!(json, serde_json::to_string_pretty(&verified).unwrap());
+    }
+
+    #[test]
+    fn test_seal() {
+        let mut outbox = Outbox::new("FDC");
+        let target_number = TargetNumber::new("AN2001").unwrap();
+
+        let readback = outbox.seal(FoFdcMessage::RequestForFireConfirm(request()));
+        assert_eq!((readback.src.as_str(), readback.sequence), ("FDC", 1));
+        assert_eq!(readback.mission, None);
+
+        let shot = outbox.seal(FoFdcMessage::Shot(first_shot()));
+        assert_eq!(shot.sequence, 2);
+        assert_eq!(shot.session, readback.session);
+        assert_eq!(shot.mission.as_ref(), Some(&target_number));
+
+        // A message without a target number stays with the mission
+        let solid = outbox.seal(FoFdcMessage::SolidReadback(SolidReadback::Splash));
+        assert_eq!(solid.sequence, 3);
+        assert_eq!(solid.mission.as_ref(), Some(&target_number));
+        assert!(solid.timestamp >= shot.timestamp);
+
+        // Until the next mission is requested
+        let next = outbox.seal(FoFdcMessage::RequestForFireConfirm(request()));
+        assert_eq!(next.sequence, 4);
+        assert_eq!(next.mission, None);
+
+        // Unless the mission is given
+        let given = outbox.seal_for(
+            Some(target_number.clone()),
+            FoFdcMessage::RequestForFireConfirm(request()),
+        );
+        assert_eq!(given.sequence, 5);
+        assert_eq!(given.mission.as_ref(), Some(&target_number));
+    }
+
+    #[test]
+    fn test_arrive() {
+        let mut inbox = Inbox::new();
+
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 100, 1)),
+            Arrival::New { missed: 0 }
+        );
+        assert_eq!(inbox.arrive(&envelope("FO", 100, 1)), Arrival::Duplicate);
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 100, 4)),
+            Arrival::New { missed: 2 }
+        );
+        assert_eq!(inbox.arrive(&envelope("FO", 100, 3)), Arrival::Late);
+        assert_eq!(inbox.arrive(&envelope("FO", 100, 3)), Arrival::Duplicate);
+        assert_eq!(inbox.arrive(&envelope("FO", 100, 2)), Arrival::Late);
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 100, 5)),
+            Arrival::New { missed: 0 }
+        );
+
+        // Each sender is sequenced on its own
+        assert_eq!(
+            inbox.arrive(&envelope("FO2", 100, 2)),
+            Arrival::New { missed: 1 }
+        );
+        assert_eq!(inbox.arrive(&envelope("FO2", 100, 1)), Arrival::Late);
+
+        // A sender that starts over is sequenced afresh
+        assert_eq!(inbox.arrive(&envelope("FO", 200, 1)), Arrival::Restarted);
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 200, 2)),
+            Arrival::New { missed: 0 }
+        );
+        assert_eq!(inbox.arrive(&envelope("FO", 200, 1)), Arrival::Duplicate);
+
+        // Even if its first envelopes are lost
+        assert_eq!(inbox.arrive(&envelope("FO", 300, 3)), Arrival::Restarted);
+        assert_eq!(inbox.arrive(&envelope("FO", 300, 1)), Arrival::Late);
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 300, 4)),
+            Arrival::New { missed: 0 }
+        );
+
+        // Anything from before it started over is dropped
+        assert_eq!(inbox.arrive(&envelope("FO", 200, 9)), Arrival::Stale);
+        assert_eq!(
+            inbox.arrive(&envelope("FO", 300, 5)),
+            Arrival::New { missed: 0 }
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn test_each_arrives_once(order in Just((1..=32).collect::<Vec<u64>>()).prop_shuffle(), duplicates in prop::collection::vec(1u64..=32, 0..32)) {
+            let mut inbox = Inbox::new();
+            let mut received = BTreeSet::new();
+
+            for sequence in order.into_iter().chain(duplicates) {
+                let arrival = inbox.arrive(&envelope("FO", 100, sequence));
+                prop_assert_eq!(arrival == Arrival::Duplicate, !received.insert(sequence), "{:?} for {}", arrival, sequence);
+            }
+        }
+    }
+}
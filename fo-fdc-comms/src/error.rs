@@ -0,0 +1,25 @@
+//! Container module for the error type of the FO-FDC message definitions
+use thiserror::Error;
+
+/// Everything that can go wrong constructing FO-FDC message contents.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[non_exhaustive]
+pub enum Error {
+    /// The input does not match the Target Number format, `[A-Z]{2}\d{4}`
+    #[error("invalid target number {0:?}")]
+    InvalidTargetNumber(String),
+    /// The direction, in mils, is not less than a full circle of 6400 mils
+    #[error("invalid direction {0:?} mils")]
+    InvalidDirection(u32),
+    /// A Battle Damage Assessment reports no effect, yet reports casualties or equipment destroyed
+    #[error("no effect reported, yet {casualties:?} casualties and {equipment_destroyed:?} equipment destroyed")]
+    InconsistentEffect {
+        /// The casualties reported
+        casualties: u32,
+        /// The equipment destroyed reported
+        equipment_destroyed: u32,
+    },
+    /// The remarks, of the given length in characters, are too long to send
+    #[error("remarks of {0:?} characters are too long")]
+    RemarksTooLong(usize),
+}
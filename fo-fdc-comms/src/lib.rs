@@ -2,21 +2,34 @@
 #![forbid(unused_imports)]
 
 use battle_damage_assessment::BattleDamageAssessment;
+use cannot_comply::CannotComply;
+use check_fire::{CheckFire, CheckFireComplete};
+use correction::Correction;
+use end_of_mission::{EndOfMission, RecordAsTarget, Repeat};
 use readback::SolidReadback;
 use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-use message_to_observer::MessageToObserver;
+use message_to_observer::{MessageToObserver, TargetNumber};
 use request_for_fire::WarnOrder;
 use shot_fire::{RoundsComplete, Shot, Splash};
+use subsequent_correction::SubsequentCorrection;
 
 pub mod battle_damage_assessment;
+pub mod cannot_comply;
+pub mod check_fire;
+pub mod correction;
+pub mod delivery;
+pub mod end_of_mission;
+pub mod envelope;
+pub mod error;
 pub mod message_to_observer;
 pub mod readback;
 pub mod request_for_fire;
 pub mod shot_fire;
+pub mod subsequent_correction;
 
 /// An enumeration over all possible message types.
 ///
@@ -36,6 +49,11 @@ pub enum FoFdcMessage {
     /// A readback originating from a FO for a Message to Observer
     MessageToObserverConfirm(MessageToObserver),
 
+    /// A refusal of a Request for Fire originating from a FDC, in place of a Message to Observer
+    CannotComply(CannotComply),
+    /// A readback originating from a FO for a Cannot Comply
+    CannotComplyConfirm(CannotComply),
+
     /// A Shot originating from a FDC
     Shot(Shot),
     /// A readback originating from a FO for a Shot
@@ -51,21 +69,212 @@ pub enum FoFdcMessage {
     /// A readback originating from a FO for a RoundsComplete
     RoundsCompleteConfirm(RoundsComplete),
 
+    /// A Subsequent Correction originating from a FO adjusting fire
+    SubsequentCorrection(SubsequentCorrection),
+    /// A readback originating from a FDC for a Subsequent Correction
+    SubsequentCorrectionConfirm(SubsequentCorrection),
+
     /// A Battle Damage Assessment originating from a FO
     BattleDamageAssessment(BattleDamageAssessment),
     /// A readback originating from a FDC for a Battle Damage Assessment
     BattleDamageAssessmentConfirm(BattleDamageAssessment),
 
+    /// A Check Fire originating from a FO
+    CheckFire(CheckFire),
+    /// A readback originating from a FDC for a Check Fire
+    CheckFireConfirm(CheckFire),
+
+    /// A Check Fire Complete originating from a FDC, once every Gun has answered a Check Fire
+    CheckFireComplete(CheckFireComplete),
+    /// A readback originating from a FO for a Check Fire Complete
+    CheckFireCompleteConfirm(CheckFireComplete),
+
+    /// A Repeat of the last fires for effect originating from a FO
+    Repeat(Repeat),
+    /// A readback originating from a FDC for a Repeat
+    RepeatConfirm(Repeat),
+
+    /// A Record as Target originating from a FO
+    RecordAsTarget(RecordAsTarget),
+    /// A readback originating from a FDC for a Record as Target
+    RecordAsTargetConfirm(RecordAsTarget),
+
+    /// An End of Mission originating from a FO
+    EndOfMission(EndOfMission),
+    /// A readback originating from a FDC for an End of Mission
+    EndOfMissionConfirm(EndOfMission),
+
     /// Indicates a solid readback in response to any readback message.
     /// Can originate from a FDC or a FO.
     SolidReadback(SolidReadback),
+    /// Indicates a readback did not match the message it reads back, carrying the message to read back instead.
+    /// Can originate from a FDC or a FO.
+    Correction(Correction),
+}
+
+impl FoFdcMessage {
+    /// The target number of the fire mission the message is for, or [`None`] if the message does not carry one
+    #[must_use]
+    pub fn target_number(&self) -> Option<&TargetNumber> {
+        match self {
+            Self::MessageToObserver(mto) | Self::MessageToObserverConfirm(mto) => {
+                Some(&mto.target_number)
+            }
+            Self::Shot(shot) | Self::ShotConfirm(shot) => Some(&shot.target_number),
+            Self::Splash(splash) | Self::SplashConfirm(splash) => Some(&splash.target_number),
+            Self::RoundsComplete(rounds_complete)
+            | Self::RoundsCompleteConfirm(rounds_complete) => Some(&rounds_complete.target_number),
+            Self::BattleDamageAssessment(bda) | Self::BattleDamageAssessmentConfirm(bda) => {
+                Some(&bda.target_number)
+            }
+            Self::CheckFire(check_fire) | Self::CheckFireConfirm(check_fire) => {
+                Some(&check_fire.target_number)
+            }
+            Self::CheckFireComplete(complete) | Self::CheckFireCompleteConfirm(complete) => {
+                Some(&complete.target_number)
+            }
+            Self::Repeat(repeat) | Self::RepeatConfirm(repeat) => Some(&repeat.target_number),
+            Self::RecordAsTarget(record) | Self::RecordAsTargetConfirm(record) => {
+                Some(&record.target_number)
+            }
+            Self::EndOfMission(eom) | Self::EndOfMissionConfirm(eom) => Some(&eom.target_number),
+            Self::Correction(correction) => match correction {
+                Correction::RequestForFire(..)
+                | Correction::CannotComply(..)
+                | Correction::SubsequentCorrection(..) => None,
+                Correction::MessageToObserver(mto) => Some(&mto.target_number),
+                Correction::Shot(shot) => Some(&shot.target_number),
+                Correction::Splash(splash) => Some(&splash.target_number),
+                Correction::RoundsComplete(rounds_complete) => Some(&rounds_complete.target_number),
+                Correction::BattleDamageAssessment(bda) => Some(&bda.target_number),
+                Correction::CheckFire(check_fire) => Some(&check_fire.target_number),
+                Correction::CheckFireComplete(complete) => Some(&complete.target_number),
+                Correction::Repeat(repeat) => Some(&repeat.target_number),
+                Correction::RecordAsTarget(record) => Some(&record.target_number),
+                Correction::EndOfMission(eom) => Some(&eom.target_number),
+            },
+            Self::RequestForFire(..)
+            | Self::RequestForFireConfirm(..)
+            | Self::CannotComply(..)
+            | Self::CannotComplyConfirm(..)
+            | Self::SubsequentCorrection(..)
+            | Self::SubsequentCorrectionConfirm(..)
+            | Self::SolidReadback(..) => None,
+        }
+    }
 }
 
 /// Ammunition types
 #[non_exhaustive]
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(test, derive(Arbitrary))]
 pub enum Ammunition {
+    /// HE rounds
     HighExplosive,
+    /// Smoke rounds, for screening
+    Smoke,
+    /// Illumination rounds
+    Illumination,
+    /// White Phosphorus (WP) rounds, for marking and incendiary effects
+    WhitePhosphorus,
+    /// Dual-Purpose Improved Conventional Munitions (DPICM), carrying submunitions
+    Dpicm,
+    /// Precision-guided rounds
+    PrecisionGuided,
+}
+
+impl From<Ammunition> for fdc_gun_comms::Ammunition {
+    fn from(ammunition: Ammunition) -> Self {
+        match ammunition {
+            Ammunition::HighExplosive => Self::HighExplosive,
+            Ammunition::Smoke => Self::Smoke,
+            Ammunition::Illumination => Self::Illumination,
+            Ammunition::WhitePhosphorus => Self::WhitePhosphorus,
+            Ammunition::Dpicm => Self::Dpicm,
+            Ammunition::PrecisionGuided => Self::PrecisionGuided,
+        }
+    }
+}
+
+impl From<fdc_gun_comms::Ammunition> for Ammunition {
+    fn from(ammunition: fdc_gun_comms::Ammunition) -> Self {
+        match ammunition {
+            fdc_gun_comms::Ammunition::HighExplosive => Self::HighExplosive,
+            fdc_gun_comms::Ammunition::Smoke => Self::Smoke,
+            fdc_gun_comms::Ammunition::Illumination => Self::Illumination,
+            fdc_gun_comms::Ammunition::WhitePhosphorus => Self::WhitePhosphorus,
+            fdc_gun_comms::Ammunition::Dpicm => Self::Dpicm,
+            fdc_gun_comms::Ammunition::PrecisionGuided => Self::PrecisionGuided,
+        }
+    }
+}
+
+/// Fuze types
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum Fuze {
+    /// Point Detonating (PD), bursting on impact
+    PointDetonating,
+    /// Variable Time (VT), bursting at a set height above the target
+    VariableTime,
+    /// Delay, bursting shortly after impact
+    Delay,
+    /// Time, bursting a set time after firing
+    Time,
+}
+
+impl From<Fuze> for fdc_gun_comms::Fuze {
+    fn from(fuze: Fuze) -> Self {
+        match fuze {
+            Fuze::PointDetonating => Self::PointDetonating,
+            Fuze::VariableTime => Self::VariableTime,
+            Fuze::Delay => Self::Delay,
+            Fuze::Time => Self::Time,
+        }
+    }
+}
+
+impl From<fdc_gun_comms::Fuze> for Fuze {
+    fn from(fuze: fdc_gun_comms::Fuze) -> Self {
+        match fuze {
+            fdc_gun_comms::Fuze::PointDetonating => Self::PointDetonating,
+            fdc_gun_comms::Fuze::VariableTime => Self::VariableTime,
+            fdc_gun_comms::Fuze::Delay => Self::Delay,
+            fdc_gun_comms::Fuze::Time => Self::Time,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_ammunition_round_trip(ammunition in any::<Ammunition>()) {
+            let gun_ammunition = fdc_gun_comms::Ammunition::from(ammunition);
+
+            prop_assert_eq!(Ammunition::from(gun_ammunition), ammunition);
+        }
+
+        #[test]
+        fn test_fuze_round_trip(fuze in any::<Fuze>()) {
+            let gun_fuze = fdc_gun_comms::Fuze::from(fuze);
+
+            prop_assert_eq!(Fuze::from(gun_fuze), fuze);
+        }
+
+        #[test]
+        fn test_ammunition_serde(ammunition in any::<Ammunition>()) {
+            let json = serde_json::to_string(&ammunition).unwrap();
+
+            let verified: Ammunition = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(ammunition, verified, "{}", json);
+        }
+    }
 }
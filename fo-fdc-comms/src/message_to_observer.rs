@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-use crate::Ammunition;
+use crate::{error::Error, Ammunition, Fuze};
 
 /// The Message to Observer (MTO), sent by a FDC once the FDC has a response to an FO's RFF
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -20,6 +20,8 @@ pub struct MessageToObserver {
     pub target_number: TargetNumber,
     /// The Ammunition in effect for this fire mission
     pub ammunition: Ammunition,
+    /// The Fuze in effect for this fire mission
+    pub fuze: Fuze,
     /// The number of rounds (a volley) for the fire mission
     ///
     /// The total number of rounds sent down range depend on the gun systems executing the fire mission
@@ -29,7 +31,7 @@ pub struct MessageToObserver {
 /// Wrapper type for Target Numbers
 ///
 /// All Target Numbers, when viewed as a string, match the regex `r"[A-Z]{2}\d{4}$"`, for example: AN2001.
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[cfg_attr(test, derive(Arbitrary))]
 pub struct TargetNumber {
     /// The underlying string
@@ -43,14 +45,18 @@ impl TargetNumber {
     ///
     /// # Arguments
     /// * `input` - a target number in raw format
-    pub fn new(input: &str) -> Result<Self, &str> {
+    ///
+    /// # Errors
+    ///
+    /// Returns [`Error::InvalidTargetNumber`] if `input` is not a valid target number
+    pub fn new(input: &str) -> Result<Self, Error> {
         let regex_pattern: Regex = regex::Regex::new(r"^[A-Z]{2}\d{4}$").unwrap();
         if regex_pattern.is_match(input) {
             Ok(Self {
                 value: input.to_string(),
             })
         } else {
-            Err("Invalid target number")
+            Err(Error::InvalidTargetNumber(input.to_string()))
         }
     }
 }
@@ -68,6 +74,7 @@ mod tests {
             receiver: "hey_you".to_string(),
             target_number: TargetNumber::new("AN2001").unwrap(),
             ammunition: Ammunition::HighExplosive,
+            fuze: Fuze::PointDetonating,
             rounds: 32,
         };
 
@@ -84,7 +91,10 @@ mod tests {
         let bad_target = TargetNumber::new(bad_number);
 
         assert!(good_target.is_ok());
-        assert!(bad_target.is_err());
+        assert_eq!(
+            bad_target,
+            Err(Error::InvalidTargetNumber(bad_number.to_string()))
+        );
     }
 
     proptest! {
@@ -15,8 +15,15 @@ pub enum SolidReadback {
 
     RequestForFire,
     BattleDamageAssessment,
+    SubsequentCorrection,
+    Repeat,
+    RecordAsTarget,
+    EndOfMission,
+    CheckFire,
 
     MessageToObserver,
+    CannotComply,
+    CheckFireComplete,
 }
 
 #[cfg(test)]
@@ -1,11 +1,13 @@
 //! Container module for Request for Fire (RFF)
 
+use std::net::SocketAddr;
+
 use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-use crate::Ammunition;
+use crate::{Ammunition, Fuze};
 
 /// A complete Request for Fire, the first message sent by a FO
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
@@ -16,6 +18,10 @@ pub struct WarnOrder {
     pub src: String,
     /// Callsign for the intended receiver of this warning order
     pub receiver: String,
+    /// Where the FDC is to send its answers, or [`None`] for wherever the warning order was sent from
+    #[serde(default)]
+    #[cfg_attr(test, proptest(strategy = "any_response_addr()"))]
+    pub response_addr: Option<SocketAddr>,
     /// The type of mission for this warning order
     pub mission_type: MissionType,
     /// The target location and method of locating
@@ -26,10 +32,21 @@ pub struct WarnOrder {
     pub danger_close: bool,
     /// Ammunition Type
     pub ammunition: Option<Ammunition>,
+    /// Fuze Type
+    #[serde(default)]
+    pub fuze: Option<Fuze>,
     /// Method of fire
     pub method_of_fire: Option<MethodOfFire>,
 }
 
+/// Any response address that reads back the same from JSON, which leaves out IPv6 flow info
+#[cfg(test)]
+fn any_response_addr() -> impl proptest::strategy::Strategy<Value = Option<SocketAddr>> {
+    use proptest::{option, prelude::*};
+
+    option::of(any::<std::net::SocketAddrV4>().prop_map(SocketAddr::V4))
+}
+
 /// Potential Mission Types for a Request for Fire
 #[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -1,30 +1,118 @@
 //! Container module for Shot and Splash messages
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
 use serde::{Deserialize, Serialize};
 
 #[cfg(test)]
 use proptest_derive::Arbitrary;
 
-/// A Shot message, used by an FDC to indicate that rounds have started going down range.
-#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+use crate::message_to_observer::TargetNumber;
+
+/// A Shot message, used by an FDC to indicate that a volley has started going down range.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(test, derive(Arbitrary))]
-pub struct Shot {}
+pub struct Shot {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission the volley is fired for
+    pub target_number: TargetNumber,
+    /// The volley fired, counting from 1
+    pub volley: u32,
+    /// The number of volleys being fired
+    pub total_volleys: u32,
+    /// The time of flight of the volley, in milliseconds
+    pub time_of_flight: u32,
+    /// When the volley is expected to impact, in milliseconds since the Unix epoch
+    pub expected_impact: u64,
+}
+
+impl Shot {
+    /// How long until the volley is expected to impact, or zero if it should have already
+    #[must_use]
+    pub fn time_to_impact(&self) -> Duration {
+        time_until(self.expected_impact)
+    }
+}
 
 /// A Splash message, used by an FDC about 7 seconds before expected impact to indicate that the rounds should start hitting.
 ///
 /// Used by the FO to ensure attentions for observing, especially if grossly wrong.
-#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(test, derive(Arbitrary))]
-pub struct Splash {}
+pub struct Splash {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission the volley is fired for
+    pub target_number: TargetNumber,
+    /// The volley about to impact, counting from 1
+    pub volley: u32,
+    /// The number of volleys being fired
+    pub total_volleys: u32,
+    /// When the volley is expected to impact, in milliseconds since the Unix epoch
+    pub expected_impact: u64,
+}
+
+impl Splash {
+    /// How long until the volley is expected to impact, or zero if it should have already
+    #[must_use]
+    pub fn time_to_impact(&self) -> Duration {
+        time_until(self.expected_impact)
+    }
+}
 
 /// A Rounds Complete message, used by an FDC about 7 seconds after the last rounds are expected to impact.
 ///
 /// Used by the FO to know when it is safe to enter the target area, as well as start BDA.
-#[derive(Debug, Clone, Copy, PartialEq, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 #[cfg_attr(test, derive(Arbitrary))]
-pub struct RoundsComplete {}
+pub struct RoundsComplete {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The target number of the fire mission that is complete
+    pub target_number: TargetNumber,
+    /// The number of volleys that were fired
+    pub total_volleys: u32,
+}
+
+/// The time `from_now` from now, in milliseconds since the Unix epoch, for an expected impact time.
+#[must_use]
+pub fn expected_impact_in(from_now: Duration) -> u64 {
+    (SystemTime::now() + from_now)
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since_epoch| {
+            since_epoch.as_millis().try_into().unwrap_or(u64::MAX)
+        })
+}
+
+/// How long from now `time` is, in milliseconds since the Unix epoch, or zero if it has passed.
+fn time_until(time: u64) -> Duration {
+    (UNIX_EPOCH + Duration::from_millis(time))
+        .duration_since(SystemTime::now())
+        .unwrap_or_default()
+}
+
+/// The first of four volleys at AN2001, for the tests of the messages that carry a Shot
+#[cfg(test)]
+pub(crate) fn first_shot() -> Shot {
+    Shot {
+        src: "FDC".to_string(),
+        receiver: "FO".to_string(),
+        target_number: TargetNumber::new("AN2001").unwrap(),
+        volley: 1,
+        total_volleys: 4,
+        time_of_flight: 21_500,
+        expected_impact: 1_792_220_400_000,
+    }
+}
 
 #[cfg(test)]
 mod tests {
@@ -32,6 +120,45 @@ mod tests {
     use super::*;
     use proptest::prelude::*;
 
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = Shot {
+            src: "FDC".to_string(),
+            receiver: "FO".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+            volley: 1,
+            total_volleys: 4,
+            time_of_flight: 21_500,
+            expected_impact: expected_impact_in(Duration::from_millis(21_500)),
+        };
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    #[test]
+    fn test_time_to_impact() {
+        let splash = Splash {
+            src: "FDC".to_string(),
+            receiver: "FO".to_string(),
+            target_number: TargetNumber::new("AN2001").unwrap(),
+            volley: 1,
+            total_volleys: 4,
+            expected_impact: expected_impact_in(Duration::from_secs(7)),
+        };
+
+        let time_to_impact = splash.time_to_impact();
+        assert!(time_to_impact <= Duration::from_secs(7));
+        assert!(time_to_impact > Duration::from_secs(6));
+
+        let landed = Splash {
+            expected_impact: 0,
+            ..splash
+        };
+        assert_eq!(landed.time_to_impact(), Duration::ZERO);
+    }
+
     proptest! {
         #[test]
         fn test_shot_serde(message in any::<Shot>()) {
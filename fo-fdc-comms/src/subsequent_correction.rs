@@ -0,0 +1,148 @@
+//! Container module for Subsequent Correction definitions
+use serde::{Deserialize, Serialize};
+
+#[cfg(test)]
+use proptest_derive::Arbitrary;
+
+/// A Subsequent Correction, sent by a FO adjusting fire once it has observed the last adjusting round.
+///
+/// Corrections are in meters, relative to the observer-target line, and move the next round from where the last one
+/// burst. A correction with `fire_for_effect` set ends the adjustment, and the FDC fires for effect after applying it.
+#[derive(Debug, Clone, PartialEq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub struct SubsequentCorrection {
+    /// The sender's callsign
+    pub src: String,
+    /// The intended receiver's callsign
+    pub receiver: String,
+    /// The correction across the observer-target line, if any
+    pub deviation: Option<Deviation>,
+    /// The correction along the observer-target line, if any
+    pub range: Option<RangeCorrection>,
+    /// The correction to the height of burst, if any
+    pub height: Option<HeightCorrection>,
+    /// Whether the FO is calling for fire for effect, rather than another adjusting round
+    pub fire_for_effect: bool,
+}
+
+impl SubsequentCorrection {
+    /// Meters the next round moves right of the observer-target line, negative for left
+    #[must_use]
+    pub fn right(&self) -> f64 {
+        match self.deviation {
+            Some(Deviation::Right(meters)) => f64::from(meters),
+            Some(Deviation::Left(meters)) => -f64::from(meters),
+            None => 0.0,
+        }
+    }
+
+    /// Meters the next round moves away from the observer, negative for toward
+    #[must_use]
+    pub fn add(&self) -> f64 {
+        match self.range {
+            Some(RangeCorrection::Add(meters)) => f64::from(meters),
+            Some(RangeCorrection::Drop(meters)) => -f64::from(meters),
+            None => 0.0,
+        }
+    }
+
+    /// Meters the next round bursts higher, negative for lower
+    #[must_use]
+    pub fn up(&self) -> f64 {
+        match self.height {
+            Some(HeightCorrection::Up(meters)) => f64::from(meters),
+            Some(HeightCorrection::Down(meters)) => -f64::from(meters),
+            None => 0.0,
+        }
+    }
+}
+
+/// A correction across the observer-target line, in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum Deviation {
+    /// Moves the next round left, as seen by the observer
+    Left(u32),
+    /// Moves the next round right, as seen by the observer
+    Right(u32),
+}
+
+/// A correction along the observer-target line, in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum RangeCorrection {
+    /// Moves the next round away from the observer
+    Add(u32),
+    /// Moves the next round toward the observer
+    Drop(u32),
+}
+
+/// A correction to the height of burst, in meters
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+#[cfg_attr(test, derive(Arbitrary))]
+pub enum HeightCorrection {
+    /// Raises the next burst
+    Up(u32),
+    /// Lowers the next burst
+    Down(u32),
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+    use proptest::prelude::*;
+
+    #[test]
+    #[ignore = "Available for debugging only"]
+    fn view_json() {
+        let message = SubsequentCorrection {
+            src: "FO".to_string(),
+            receiver: "FDC".to_string(),
+            deviation: Some(Deviation::Left(50)),
+            range: Some(RangeCorrection::Add(400)),
+            height: None,
+            fire_for_effect: false,
+        };
+
+        let json = serde_json::to_string_pretty(&message).unwrap();
+        println!("{message:?} : {json}");
+    }
+
+    #[test]
+    fn test_signed_meters() {
+        let correction = SubsequentCorrection {
+            deviation: Some(Deviation::Left(50)),
+            range: Some(RangeCorrection::Add(400)),
+            height: Some(HeightCorrection::Down(20)),
+            ..SubsequentCorrection::default()
+        };
+
+        assert_eq!(correction.right(), -50.0);
+        assert_eq!(correction.add(), 400.0);
+        assert_eq!(correction.up(), -20.0);
+
+        let fire_for_effect = SubsequentCorrection {
+            fire_for_effect: true,
+            ..SubsequentCorrection::default()
+        };
+        assert_eq!(fire_for_effect.right(), 0.0);
+        assert_eq!(fire_for_effect.add(), 0.0);
+        assert_eq!(fire_for_effect.up(), 0.0);
+    }
+
+    proptest! {
+        #[test]
+        fn test_serde(message in any::<SubsequentCorrection>()) {
+            let json = serde_json::to_string_pretty(&message).unwrap();
+
+            let verified: SubsequentCorrection = serde_json::from_str(&json).unwrap();
+
+            assert_eq!(message, verified, "{}", json);
+        }
+    }
+}
@@ -1,91 +1,276 @@
 //! Contains the message types that the FO sim can send/receive, as well as the communication tasks (send and receive)
 //!
-use std::sync::Arc;
+//! Every message sent to the FDC is held by a [`Delivery`] tracker until the FDC answers it, and is resent every
+//! [`RETRANSMIT_TIMEOUT`] until then. A message still unanswered after [`RETRY_LIMIT`] resends, or when the FDC starts
+//! over, is reported to the state machine as a [`FdcEvent::DeliveryFailed`].
+//!
+//! Messages travel in [`Envelope`]s, numbered in sequence, so that a message received twice is only passed to the
+//! state machine once. Should the FDC send a message again, the answer already sent to it is sent again in its place.
+use std::{
+    io::ErrorKind,
+    net::{Ipv4Addr, SocketAddr, SocketAddrV4},
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
 
 use anyhow::Result;
-use fo_fdc_comms::FoFdcMessage;
+use fo_fdc_comms::{
+    delivery::{Delivery, Overdue},
+    envelope::{Arrival, Envelope, Inbox, Outbox},
+    FoFdcMessage,
+};
 use tokio::{
     net::UdpSocket,
     sync::mpsc::{UnboundedReceiver, UnboundedSender},
     try_join,
 };
-use tracing::debug;
+use tracing::{debug, error, info, warn};
+
+/// How long the FDC has to answer a message before it is resent.
+//TODO: Get from config
+const RETRANSMIT_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// How many times a message is resent before it is given up.
+//TODO: Get from config
+const RETRY_LIMIT: u32 = 3;
+
+/// The callsign the FO seals its envelopes with.
+//TODO: Get from config
+const CALLSIGN: &str = "FO";
+
+/// The address the FO listens on, which it gives the FDC to answer to.
+//TODO: Get from config
+pub(crate) const FO_ADDR: SocketAddr =
+    SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 49152));
+
+/// The address of the FDC.
+//TODO: Get from config
+const FDC_ADDR: SocketAddr = SocketAddr::V4(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 49153));
+
+/// How often the held messages are checked for overdue answers
+const RETRANSMIT_CHECK_INTERVAL: Duration = Duration::from_millis(100);
+
+/// Events from the FDC, for the state machine
+#[derive(Debug)]
+pub(crate) enum FdcEvent {
+    /// The FDC sent a message to the FO
+    Message(FoFdcMessage),
+    /// The FDC never answered a message, even after it was resent [`RETRY_LIMIT`] times, or started over before it did
+    DeliveryFailed(FoFdcMessage),
+}
 
 /// Provides a reader/writer loop, handling writes from the
 pub(crate) async fn fo_fdc_commhandler_loop(
     to_fdc: UnboundedReceiver<FoFdcMessage>,
-    from_fdc: UnboundedSender<FoFdcMessage>,
+    from_fdc: UnboundedSender<FdcEvent>,
 ) -> Result<()> {
-    let socket = UdpSocket::bind("127.0.0.1:49152").await?;
+    let socket = UdpSocket::bind(FO_ADDR).await?;
 
-    socket.connect("127.0.0.1:49153").await?;
+    socket.connect(FDC_ADDR).await?;
     debug!("Bound to {}", socket.local_addr()?);
 
+    let delivery = Arc::new(Mutex::new(Delivery::new(RETRANSMIT_TIMEOUT, RETRY_LIMIT)));
+
     // Spin off listener thread
     let socket = Arc::new(socket);
     let recv_handle = {
         let socket = Arc::clone(&socket);
+        let delivery = Arc::clone(&delivery);
+        let from_fdc = from_fdc.clone();
         tokio::task::Builder::new()
             .name("receive loop")
-            .spawn(async move { recv_loop(from_fdc, socket).await })
+            .spawn(async move { recv_loop(from_fdc, socket, delivery).await })
+    };
+
+    // Spin off retransmit thread
+    let retransmit_handle = {
+        let socket = Arc::clone(&socket);
+        let delivery = Arc::clone(&delivery);
+        tokio::task::Builder::new()
+            .name("retransmit loop")
+            .spawn(async move { retransmit_loop(from_fdc, socket, delivery).await })
     };
 
     // Spin off writer thread
     let send_handle = tokio::task::Builder::new()
         .name("send loop")
-        .spawn(async move { send_loop(to_fdc, socket).await });
+        .spawn(async move { send_loop(to_fdc, socket, delivery).await });
 
-    let (left, right) = try_join!(recv_handle, send_handle)?;
-    left?;
-    right?;
+    let (recv, retransmit, send) = try_join!(recv_handle, retransmit_handle, send_handle)?;
+    recv?;
+    retransmit?;
+    send?;
 
     Ok(())
 }
 
 async fn recv_loop(
-    from_fdc_sender: UnboundedSender<FoFdcMessage>,
+    from_fdc_sender: UnboundedSender<FdcEvent>,
     socket: Arc<UdpSocket>,
+    delivery: Arc<Mutex<Delivery>>,
 ) -> Result<()> {
     let mut buffer = vec![0; 24 * 1024];
+    let mut inbox = Inbox::new();
 
     loop {
         match socket.recv(&mut buffer).await {
             Ok(bytes_read) => {
-                let value: FoFdcMessage = serde_json::from_slice(&buffer[..bytes_read])?;
-                debug!("Received {:?}", value);
-                from_fdc_sender.send(value)?;
+                // A datagram that is not an envelope is dropped rather than fatal, so the FO carries on.
+                let envelope: Envelope = match serde_json::from_slice(&buffer[..bytes_read]) {
+                    Ok(envelope) => envelope,
+                    Err(err) => {
+                        warn!("Dropping a datagram that is not an envelope: {}", err);
+                        continue;
+                    }
+                };
+                debug!("Received {:?}", envelope);
+                match inbox.arrive(&envelope) {
+                    Arrival::New { missed: 0 } => (),
+                    Arrival::New { missed } => warn!(
+                        "Missed {} messages from {} before message {}",
+                        missed, envelope.src, envelope.sequence
+                    ),
+                    Arrival::Late => debug!(
+                        "Message {} from {} arrived late",
+                        envelope.sequence, envelope.src
+                    ),
+                    Arrival::Restarted => {
+                        info!("{} started over, resequencing", envelope.src);
+                        // Whatever was sent to the FDC before it started over is for a mission it no longer knows of.
+                        let given_up = delivery.lock().unwrap().forget(&envelope.src);
+                        for given_up in given_up {
+                            from_fdc_sender.send(FdcEvent::DeliveryFailed(given_up.message))?;
+                        }
+                    }
+                    Arrival::Stale => {
+                        debug!(
+                            "Dropping message {} from {}, sent before it started over",
+                            envelope.sequence, envelope.src
+                        );
+                        continue;
+                    }
+                    Arrival::Duplicate => {
+                        // An answer sent again, as what it answers was resent, may still be the first to release it.
+                        let answer = {
+                            let mut delivery = delivery.lock().unwrap();
+                            delivery.received(&envelope);
+                            delivery.answer_to(&envelope).cloned()
+                        };
+                        match answer {
+                            Some(answer) => {
+                                warn!(
+                                    "Received message {} from {} again, resending our answer",
+                                    envelope.sequence, envelope.src
+                                );
+                                send_to_fdc(&socket, &serde_json::to_vec(&answer)?).await?;
+                            }
+                            None => debug!(
+                                "Dropping message {} from {}, already received",
+                                envelope.sequence, envelope.src
+                            ),
+                        }
+                        continue;
+                    }
+                }
+                delivery.lock().unwrap().received(&envelope);
+                from_fdc_sender.send(FdcEvent::Message(envelope.message))?;
+            }
+            // An answer may yet come once the FDC is listening, and any message it missed is resent.
+            Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+                warn!("The FDC is not listening")
             }
             Err(err) => return Err(err.into()),
         };
     }
 }
 
+/// Retransmit loop, for resending each message the FDC has not answered in time, and reporting those it never answers
+async fn retransmit_loop(
+    from_fdc_sender: UnboundedSender<FdcEvent>,
+    socket: Arc<UdpSocket>,
+    delivery: Arc<Mutex<Delivery>>,
+) -> Result<()> {
+    let mut interval = tokio::time::interval(RETRANSMIT_CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        let overdue = delivery.lock().unwrap().poll(Instant::now());
+        for overdue in overdue {
+            match overdue {
+                Overdue::Resend(envelope) => {
+                    warn!(
+                        "The FDC has not answered message {}, resending {:?}",
+                        envelope.sequence, envelope.message
+                    );
+                    let bytes = serde_json::to_vec(&envelope)?;
+                    send_to_fdc(&socket, &bytes).await?;
+                }
+                Overdue::Failed(envelope) => {
+                    error!(
+                        "The FDC never answered message {}, giving up after {} resends of {:?}",
+                        envelope.sequence, RETRY_LIMIT, envelope.message
+                    );
+                    from_fdc_sender.send(FdcEvent::DeliveryFailed(envelope.message))?;
+                }
+            }
+        }
+    }
+}
+
 async fn send_loop(
     mut to_fdc_receiver: UnboundedReceiver<FoFdcMessage>,
     socket: Arc<UdpSocket>,
+    delivery: Arc<Mutex<Delivery>>,
 ) -> Result<()> {
+    let mut outbox = Outbox::new(CALLSIGN);
     while let Some(message_to_fdc) = to_fdc_receiver.recv().await {
         debug!("Sending {:?}", message_to_fdc);
-        let (message_json, bytes) = match message_to_fdc {
+        let mut envelope = match message_to_fdc {
             FoFdcMessage::RequestForFire(..)
             | FoFdcMessage::MessageToObserverConfirm(..)
+            | FoFdcMessage::CannotComplyConfirm(..)
             | FoFdcMessage::ShotConfirm(..)
             | FoFdcMessage::SplashConfirm(..)
             | FoFdcMessage::RoundsCompleteConfirm(..)
             | FoFdcMessage::BattleDamageAssessment(..)
-            | FoFdcMessage::SolidReadback(..) => (
-                serde_json::to_string_pretty(&message_to_fdc)?,
-                serde_json::to_vec(&message_to_fdc)?,
-            ),
+            | FoFdcMessage::SubsequentCorrection(..)
+            | FoFdcMessage::Repeat(..)
+            | FoFdcMessage::RecordAsTarget(..)
+            | FoFdcMessage::EndOfMission(..)
+            | FoFdcMessage::CheckFire(..)
+            | FoFdcMessage::CheckFireCompleteConfirm(..)
+            | FoFdcMessage::SolidReadback(..)
+            | FoFdcMessage::Correction(..) => outbox.seal(message_to_fdc),
             _ => panic!(
                 "Unsupported message was sent for transmission to the FDC: {:?}",
                 message_to_fdc
             ),
         };
-        debug!("Sending: {}", message_json);
-        let bytes_sent = socket.send(&bytes).await?;
+        // Held before it is sent, so an answer that arrives while it is being sent finds it.
+        {
+            let mut delivery = delivery.lock().unwrap();
+            delivery.reply(&mut envelope);
+            delivery.sent(&envelope, Instant::now());
+        }
+        let bytes = serde_json::to_vec(&envelope)?;
+        debug!("Sending: {}", serde_json::to_string_pretty(&envelope)?);
+        let bytes_sent = send_to_fdc(&socket, &bytes).await?;
         debug!("Sent {} bytes of {} ", bytes_sent, bytes.len());
     }
 
     Ok(())
 }
+
+/// Sends `bytes` to the FDC, returning the bytes sent.
+///
+/// A FDC that is not listening has sent nothing, rather than failed, as the message is resent until it answers.
+async fn send_to_fdc(socket: &UdpSocket, bytes: &[u8]) -> Result<usize> {
+    match socket.send(bytes).await {
+        Ok(bytes_sent) => Ok(bytes_sent),
+        Err(err) if err.kind() == ErrorKind::ConnectionRefused => {
+            warn!("The FDC is not listening");
+            Ok(0)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
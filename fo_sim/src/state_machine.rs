@@ -3,17 +3,57 @@
 //! The FO State Machine uses the top level [`FoState`] for representing the state of the FO.
 //! The workhorse of this module, and the intended component for use is the [`state_machine_loop`],
 //! which provides an `async` function for use in a runtime.
-use anyhow::Result;
+use anyhow::{Context, Result};
 use fo_fdc_comms::{
-    battle_damage_assessment::BattleDamageAssessment,
+    battle_damage_assessment::{BattleDamageAssessment, Effect},
+    check_fire::CheckFire,
+    correction::{self, Correction},
+    end_of_mission::{EndOfMission, RecordAsTarget, Repeat},
+    message_to_observer::TargetNumber,
     readback::SolidReadback,
     request_for_fire::{MissionType, TargetDescription, TargetLocation, WarnOrder},
-    shot_fire::{RoundsComplete, Shot, Splash},
-    Ammunition, FoFdcMessage,
+    subsequent_correction::{Deviation, RangeCorrection, SubsequentCorrection},
+    Ammunition, FoFdcMessage, Fuze,
+};
+use tokio::{
+    sync::mpsc::{error::TryRecvError, UnboundedReceiver, UnboundedSender},
+    time::Instant,
 };
-use tokio::sync::mpsc::{error::TryRecvError, UnboundedReceiver, UnboundedSender};
 use tracing::{debug, error, info, info_span, trace, warn};
 
+use crate::fo_fdc_commhandler::{FdcEvent, FO_ADDR};
+
+/// How close an adjusting round must burst to the target, both along and across the observer-target line, for the FO
+/// to call for fire for effect.
+const EFFECT_RADIUS: u32 = 50;
+
+/// How far from the target, in meters, an adjusting round may burst before the FO checks fire, as the fires are unsafe.
+const CHECK_FIRE_DISTANCE: u32 = 1_000;
+
+/// Where the first adjusting round bursts from the target, since the FO's first location of the target is off.
+//TODO: Observe from the rounds
+const FIRST_BURST: Burst = Burst {
+    right: 120,
+    beyond: -230,
+    bracket: 400,
+};
+
+/// The number of times the FO has the fires for effect of a mission repeated before ending it.
+//TODO: Decide from the observed effect
+const REPEATS: u32 = 1;
+
+/// The volleys of fire for effect the FO must see fall on the target to assess it destroyed.
+//TODO: Observe from the target
+const DESTROYING_VOLLEYS: u32 = 8;
+
+/// The volleys of fire for effect the FO must see fall on the target to assess it neutralized.
+//TODO: Observe from the target
+const NEUTRALIZING_VOLLEYS: u32 = 4;
+
+/// The casualties the FO estimates each volley of fire for effect causes.
+//TODO: Observe from the target
+const CASUALTIES_PER_VOLLEY: u32 = 3;
+
 /// Representation of the top-level state of a Forward Observer
 ///
 /// A FO is either offline (with no FDC to talk to), or connected to an FDC.
@@ -29,62 +69,11 @@ pub(crate) enum FoState {
 }
 
 impl FoState {
-    /// Returns `true` if the fo state is [`Connected`].
-    ///
-    /// [`Connected`]: FoState::Connected
-    #[must_use]
-    pub(crate) fn is_connected(&self) -> bool {
-        matches!(self, Self::Connected { .. })
-    }
-
-    /// Returns `true` if the fo state is [`Connected`] and [`Requesting`].
-    ///
-    /// [`Connected`]: FoState::Connected
-    /// [`Requesting`]: ConnectedState::Requesting
-    #[must_use]
-    pub(crate) fn is_requesting(&self) -> bool {
-        matches!(
-            self,
-            Self::Connected {
-                state: ConnectedState::Requesting,
-                ..
-            }
-        )
-    }
-
-    /// Returns `true` if the fo state is [`Connected`] and [`Observing`].
-    ///
-    /// [`Connected`]: FoState::Connected
-    /// [`Observing`]: ConnectedState::Observing
-    #[must_use]
-    pub(crate) fn is_observing(&self) -> bool {
-        matches!(
-            self,
-            Self::Connected {
-                state: ConnectedState::Observing,
-                ..
-            }
-        )
-    }
-
-    /// Tries to change the internal [`ConnectedState`] to [`Requesting`].
-    ///
-    /// [`Requesting`]: ConnectedState::Requesting
-    pub(crate) fn try_to_requesting(self) -> Option<Self> {
-        if let Self::Connected { state } = self {
-            Some(Self::Connected {
-                state: ConnectedState::Requesting,
-            })
-        } else {
-            None
-        }
-    }
-
     /// Tries to change the internal [`ConnectedState`] to [`Observing`].
     ///
     /// [`Observing`]: ConnectedState::Observing
     pub(crate) fn try_to_observing(self) -> Option<Self> {
-        if let Self::Connected { state } = self {
+        if let Self::Connected { .. } = self {
             Some(Self::Connected {
                 state: ConnectedState::Observing,
             })
@@ -97,7 +86,7 @@ impl FoState {
     ///
     /// [`Reporting`]: ConnectedState::Reporting
     pub(crate) fn try_to_reporting(self) -> Option<Self> {
-        if let Self::Connected { state } = self {
+        if let Self::Connected { .. } = self {
             Some(Self::Connected {
                 state: ConnectedState::Reporting,
             })
@@ -110,7 +99,7 @@ impl FoState {
     ///
     /// [`Standby`]: ConnectedState::Standby
     pub(crate) fn try_to_standby(self) -> Option<Self> {
-        if let Self::Connected { state } = self {
+        if let Self::Connected { .. } = self {
             Some(Self::Connected {
                 state: ConnectedState::Standby,
             })
@@ -132,10 +121,68 @@ pub(crate) enum ConnectedState {
     Requesting,
     /// State representing when the FO is observing fires after receiving a MTO.
     Observing,
-    /// State representing when the FO has finished observing, and is reporting a BDA back to the FDC.
+    /// State representing when the FO has finished observing, and is recording the target, reporting a BDA back to the
+    /// FDC, and ending the mission.
     Reporting,
 }
 
+/// Where the adjusting rounds of a mission are bursting from the target, as seen by the FO
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Burst {
+    /// Meters the burst is right of the target, negative for left
+    right: i32,
+    /// Meters the burst is beyond the target, negative for short
+    beyond: i32,
+    /// Meters the next range correction moves the burst, which is halved each time the target is bracketed
+    bracket: u32,
+}
+
+impl Burst {
+    /// Whether the burst is so far from the target that the fires must be checked, rather than corrected
+    fn is_unsafe(&self) -> bool {
+        f64::from(self.right).hypot(f64::from(self.beyond)) > f64::from(CHECK_FIRE_DISTANCE)
+    }
+
+    /// Corrects the next adjusting round onto the target, or calls for fire for effect once the burst is close enough,
+    /// and moves the burst to where the correction puts it.
+    fn correct(&mut self) -> SubsequentCorrection {
+        let on_target = |miss: i32| miss.unsigned_abs() <= EFFECT_RADIUS;
+        let mut correction = SubsequentCorrection {
+            src: "FO".to_string(),       //TODO: Get from source
+            receiver: "FDC".to_string(), //TODO: Get from config
+            ..SubsequentCorrection::default()
+        };
+        if on_target(self.right) && on_target(self.beyond) {
+            correction.fire_for_effect = true;
+            return correction;
+        }
+
+        // Deviation is seen directly, so is corrected all at once.
+        correction.deviation = match self.right {
+            0 => None,
+            right if right > 0 => Some(Deviation::Left(right.unsigned_abs())),
+            right => Some(Deviation::Right(right.unsigned_abs())),
+        };
+        self.right = 0;
+
+        // Range is hard to judge, so the target is bracketed by halving corrections.
+        if !on_target(self.beyond) {
+            let bracket = i32::try_from(self.bracket).unwrap_or(i32::MAX);
+            let (range, beyond) = if self.beyond < 0 {
+                (RangeCorrection::Add(self.bracket), self.beyond + bracket)
+            } else {
+                (RangeCorrection::Drop(self.bracket), self.beyond - bracket)
+            };
+            correction.range = Some(range);
+            if beyond.signum() != self.beyond.signum() {
+                self.bracket = (self.bracket / 2).max(EFFECT_RADIUS);
+            }
+            self.beyond = beyond;
+        }
+        correction
+    }
+}
+
 /// Asynchronous executor loop for managing the state machine.
 ///
 /// All state manipulation happens within the context of this function.
@@ -144,13 +191,37 @@ pub(crate) enum ConnectedState {
 ///
 /// # Arguments
 ///
-/// * `message_queue` - The receive side of a channel for processing the state machine with messages from the FDC.
+/// * `message_queue` - The receive side of a channel for processing the state machine with events from the FDC.
 /// * `to_fdc` - The send side of a channel where messages to send to the FDC are sent by the state machine loop.
 pub(crate) async fn state_machine_loop(
-    mut message_queue: UnboundedReceiver<FoFdcMessage>,
+    mut message_queue: UnboundedReceiver<FdcEvent>,
     to_fdc: UnboundedSender<FoFdcMessage>,
 ) -> Result<()> {
     let mut state = FoState::Offline;
+    // Where the adjusting rounds are bursting, while adjusting fire
+    let mut burst: Option<Burst> = None;
+    // When the last adjusting round will burst, once warned by a Splash
+    let mut observe_at: Option<Instant> = None;
+    // The target number the FDC gave the mission in the MTO
+    let mut target_number: Option<TargetNumber> = None;
+    // The times the fires for effect are still to be repeated
+    let mut repeats = 0;
+    // The Request for Fire sent for the mission, to check the FDC's readback against
+    let mut request_for_fire: Option<WarnOrder> = None;
+    // The BDA sent for the mission, to check the FDC's readback against
+    let mut assessment: Option<BattleDamageAssessment> = None;
+    // The last Subsequent Correction sent, to check the FDC's readback against
+    let mut subsequent_correction: Option<SubsequentCorrection> = None;
+    // The Check Fire sent for the mission, to check the FDC's readback against
+    let mut check_fire: Option<CheckFire> = None;
+    // The last Repeat sent for the mission, to check the FDC's readback against
+    let mut repeat: Option<Repeat> = None;
+    // The Record as Target sent for the mission, to check the FDC's readback against
+    let mut record_as_target: Option<RecordAsTarget> = None;
+    // The End of Mission sent for the mission, to check the FDC's readback against
+    let mut end_of_mission: Option<EndOfMission> = None;
+    // The volleys of fire for effect the FO has seen fall on the target
+    let mut volleys_observed = 0;
     let message_process_span = info_span!("message_process");
     let state_machine_run_span = info_span!("state_run");
     info!("Starting state machine...");
@@ -159,7 +230,26 @@ pub(crate) async fn state_machine_loop(
         debug!("State is {:?}", state);
 
         let message = match message_queue.try_recv() {
-            Ok(message) => Some(message),
+            Ok(FdcEvent::Message(message)) => Some(message),
+            Ok(FdcEvent::DeliveryFailed(message)) => {
+                // The FDC cannot be reached, so the mission is dropped, to be requested afresh once back online.
+                error!(
+                    "Could not deliver {:?} to the FDC, abandoning the mission",
+                    message
+                );
+                burst = None;
+                observe_at = None;
+                target_number = None;
+                request_for_fire = None;
+                assessment = None;
+                subsequent_correction = None;
+                check_fire = None;
+                repeat = None;
+                record_as_target = None;
+                end_of_mission = None;
+                state = FoState::Offline;
+                None
+            }
             Err(TryRecvError::Empty) => None,
             Err(TryRecvError::Disconnected) => break,
         };
@@ -185,9 +275,23 @@ pub(crate) async fn state_machine_loop(
                 ) => {
                     info!("Received a readback for Request for Fire. Evaluating...");
                     debug!("RFF Readback: {:?}", rff_readback);
-                    //TODO: Proccess any errors
-                    info!("Readback confirmed, sending SolidReadback...");
-                    to_fdc.send(FoFdcMessage::SolidReadback(SolidReadback::RequestForFire))?
+                    match &request_for_fire {
+                        Some(rff) if *rff == rff_readback => {
+                            info!("Readback confirmed, sending SolidReadback...");
+                            to_fdc
+                                .send(FoFdcMessage::SolidReadback(SolidReadback::RequestForFire))?
+                        }
+                        Some(rff) => {
+                            warn!(
+                                "RFF readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(rff, &rff_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(Correction::RequestForFire(
+                                rff.clone(),
+                            )))?
+                        }
+                        None => warn!("No RFF was sent to be read back"),
+                    }
                 }
                 // MTO Received while Requesting a Fire Mission
                 (
@@ -197,8 +301,34 @@ pub(crate) async fn state_machine_loop(
                     },
                 ) => {
                     info!("Received the MTO, reading back to FDC");
+                    target_number = Some(mto.target_number.clone());
                     to_fdc.send(FoFdcMessage::MessageToObserverConfirm(mto))?;
                 }
+                // Cannot Comply received while Requesting a Fire Mission
+                (
+                    FoFdcMessage::CannotComply(cantco),
+                    FoState::Connected {
+                        state: ConnectedState::Requesting,
+                    },
+                ) => {
+                    warn!(
+                        "FDC cannot comply with the request: {:?}, reading back to FDC",
+                        cantco.reason
+                    );
+                    to_fdc.send(FoFdcMessage::CannotComplyConfirm(cantco))?;
+                }
+                // Solid Readback received for our Cannot Comply readback
+                (
+                    FoFdcMessage::SolidReadback(SolidReadback::CannotComply),
+                    FoState::Connected {
+                        state: ConnectedState::Requesting,
+                    },
+                ) => {
+                    info!("Received a solid readback message. Standing by.");
+                    state = state
+                        .try_to_standby()
+                        .expect("state was invalid for conversion");
+                }
                 // Solid Readback received while requesting a Fire Mission
                 (
                     FoFdcMessage::SolidReadback(SolidReadback::MessageToObserver),
@@ -216,37 +346,141 @@ pub(crate) async fn state_machine_loop(
 
                 // Shot was received while Observing a Fire Mission
                 (
-                    FoFdcMessage::Shot(_),
+                    FoFdcMessage::Shot(shot),
                     FoState::Connected {
                         state: ConnectedState::Observing,
                     },
                 ) => {
-                    info!("Received a Shot message, echoing...");
-                    to_fdc.send(FoFdcMessage::ShotConfirm(Shot::default()))?
+                    info!(
+                        "Received a Shot for volley {} of {}, {} ms time of flight, echoing...",
+                        shot.volley, shot.total_volleys, shot.time_of_flight
+                    );
+                    to_fdc.send(FoFdcMessage::ShotConfirm(shot))?
                 }
 
                 // Splash was received while Observing a Fire Mission
                 (
-                    FoFdcMessage::Splash(_),
+                    FoFdcMessage::Splash(splash),
                     FoState::Connected {
                         state: ConnectedState::Observing,
                     },
                 ) => {
-                    info!("Received a Splash message, echoing...");
-                    to_fdc.send(FoFdcMessage::SplashConfirm(Splash::default()))?
+                    info!(
+                        "Received a Splash for volley {} of {}, echoing...",
+                        splash.volley, splash.total_volleys
+                    );
+                    if burst.is_some() {
+                        observe_at = Some(Instant::now() + splash.time_to_impact());
+                    } else {
+                        volleys_observed += 1;
+                    }
+                    to_fdc.send(FoFdcMessage::SplashConfirm(splash))?;
+                }
+
+                // Subsequent Correction Readback received while adjusting fire
+                (
+                    FoFdcMessage::SubsequentCorrectionConfirm(correction_readback),
+                    FoState::Connected {
+                        state: ConnectedState::Observing,
+                    },
+                ) => {
+                    info!("Received a readback for Subsequent Correction. Evaluating...");
+                    debug!("Subsequent Correction Readback: {:?}", correction_readback);
+                    match &subsequent_correction {
+                        Some(correction) if *correction == correction_readback => {
+                            info!("Readback confirmed, sending SolidReadback...");
+                            to_fdc.send(FoFdcMessage::SolidReadback(
+                                SolidReadback::SubsequentCorrection,
+                            ))?
+                        }
+                        Some(correction) => {
+                            warn!(
+                                "Subsequent Correction readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(correction, &correction_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(
+                                Correction::SubsequentCorrection(correction.clone()),
+                            ))?
+                        }
+                        None => warn!("No Subsequent Correction was sent to be read back"),
+                    }
+                }
+
+                // Check Fire Readback received while observing
+                (
+                    FoFdcMessage::CheckFireConfirm(check_fire_readback),
+                    FoState::Connected {
+                        state: ConnectedState::Observing,
+                    },
+                ) => {
+                    info!("Received a readback for Check Fire. Evaluating...");
+                    debug!("Check Fire Readback: {:?}", check_fire_readback);
+                    match &check_fire {
+                        Some(check_fire) if *check_fire == check_fire_readback => {
+                            info!("Readback confirmed, sending SolidReadback...");
+                            to_fdc.send(FoFdcMessage::SolidReadback(SolidReadback::CheckFire))?
+                        }
+                        Some(check_fire) => {
+                            warn!(
+                                "Check Fire readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(check_fire, &check_fire_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(Correction::CheckFire(
+                                check_fire.clone(),
+                            )))?
+                        }
+                        None => warn!("No Check Fire was sent to be read back"),
+                    }
+                }
+                // Check Fire Complete received once the Guns have answered our Check Fire
+                (
+                    FoFdcMessage::CheckFireComplete(check_fire_complete),
+                    FoState::Connected {
+                        state: ConnectedState::Observing,
+                    },
+                ) => {
+                    info!(
+                        "Fires are checked, {} Guns ceased firing, reading back to FDC",
+                        check_fire_complete.guns_ceased
+                    );
+                    to_fdc.send(FoFdcMessage::CheckFireCompleteConfirm(check_fire_complete))?;
+                }
+                // Solid Readback received for our Check Fire Complete readback
+                (
+                    FoFdcMessage::SolidReadback(SolidReadback::CheckFireComplete),
+                    FoState::Connected {
+                        state: ConnectedState::Observing,
+                    },
+                ) => {
+                    info!("Received a solid readback for check fire complete. Ending the mission");
+
+                    state = state
+                        .try_to_reporting()
+                        .expect("state was invalid for conversion");
+
+                    let eom = EndOfMission {
+                        src: "FO".to_string(),       //TODO: Get from source
+                        receiver: "FDC".to_string(), //TODO: Get from config
+                        target_number: target_number
+                            .clone()
+                            .context("No MTO has given the mission a target number")?,
+                    };
+                    end_of_mission = Some(eom.clone());
+                    to_fdc.send(FoFdcMessage::EndOfMission(eom))?
                 }
 
                 // RoundsComplete was received while Observing a Fire Mission
                 (
-                    FoFdcMessage::RoundsComplete(_),
+                    FoFdcMessage::RoundsComplete(rounds_complete),
                     FoState::Connected {
                         state: ConnectedState::Observing,
                     },
                 ) => {
-                    info!("Received a RoundsComplete message, echoing...");
-                    to_fdc.send(FoFdcMessage::RoundsCompleteConfirm(
-                        RoundsComplete::default(),
-                    ))?
+                    info!(
+                        "Received a RoundsComplete after {} volleys, echoing...",
+                        rounds_complete.total_volleys
+                    );
+                    to_fdc.send(FoFdcMessage::RoundsCompleteConfirm(rounds_complete))?
                 }
 
                 // Solid readback was received while observing
@@ -266,19 +500,105 @@ pub(crate) async fn state_machine_loop(
                         state: ConnectedState::Observing,
                     },
                 ) => {
-                    info!("Received a solid readback for rounds complete. Reporting a BDA");
+                    let target_number = target_number
+                        .clone()
+                        .context("No MTO has given the mission a target number")?;
+                    if repeats > 0 {
+                        info!("Received a solid readback for rounds complete. Repeating");
+                        repeats -= 1;
+                        let again = Repeat {
+                            src: "FO".to_string(),       //TODO: Get from source
+                            receiver: "FDC".to_string(), //TODO: Get from config
+                            target_number,
+                        };
+                        repeat = Some(again.clone());
+                        to_fdc.send(FoFdcMessage::Repeat(again))?
+                    } else {
+                        info!("Received a solid readback for rounds complete. Recording as target");
 
-                    state = state
-                        .try_to_reporting()
-                        .expect("state was invalid for conversion");
+                        state = state
+                            .try_to_reporting()
+                            .expect("state was invalid for conversion");
+
+                        let record = RecordAsTarget {
+                            src: "FO".to_string(),       //TODO: Get from source
+                            receiver: "FDC".to_string(), //TODO: Get from config
+                            target_number,
+                        };
+                        record_as_target = Some(record.clone());
+                        to_fdc.send(FoFdcMessage::RecordAsTarget(record))?
+                    }
+                }
 
-                    to_fdc.send(FoFdcMessage::BattleDamageAssessment(
-                        BattleDamageAssessment {},
-                    ))?
+                // Repeat Readback received while observing
+                (
+                    FoFdcMessage::RepeatConfirm(repeat_readback),
+                    FoState::Connected {
+                        state: ConnectedState::Observing,
+                    },
+                ) => {
+                    info!("Received a readback for Repeat. Evaluating...");
+                    debug!("Repeat Readback: {:?}", repeat_readback);
+                    match &repeat {
+                        Some(repeat) if *repeat == repeat_readback => {
+                            info!("Readback confirmed, sending SolidReadback...");
+                            to_fdc.send(FoFdcMessage::SolidReadback(SolidReadback::Repeat))?
+                        }
+                        Some(repeat) => {
+                            warn!(
+                                "Repeat readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(repeat, &repeat_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(Correction::Repeat(
+                                repeat.clone(),
+                            )))?
+                        }
+                        None => warn!("No Repeat was sent to be read back"),
+                    }
                 }
 
                 // FDC Messages sent when the FO is reporting
 
+                // Received Record as Target Readback
+                (
+                    FoFdcMessage::RecordAsTargetConfirm(record_readback),
+                    FoState::Connected {
+                        state: ConnectedState::Reporting,
+                    },
+                ) => {
+                    info!("Received a readback for Record as Target. Evaluating...");
+                    debug!("Record as Target Readback: {:?}", record_readback);
+                    match &record_as_target {
+                        Some(record) if *record == record_readback => {
+                            info!(
+                                "Readback confirmed, sending SolidReadback and reporting a BDA..."
+                            );
+                            to_fdc
+                                .send(FoFdcMessage::SolidReadback(SolidReadback::RecordAsTarget))?;
+
+                            let bda = assess(
+                                target_number
+                                    .clone()
+                                    .context("No MTO has given the mission a target number")?,
+                                volleys_observed,
+                            );
+                            info!("Reporting a BDA: {:?}", bda);
+                            assessment = Some(bda.clone());
+                            to_fdc.send(FoFdcMessage::BattleDamageAssessment(bda))?
+                        }
+                        Some(record) => {
+                            warn!(
+                                "Record as Target readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(record, &record_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(Correction::RecordAsTarget(
+                                record.clone(),
+                            )))?
+                        }
+                        None => warn!("No Record as Target was sent to be read back"),
+                    }
+                }
+
                 // Received BDA Readback
                 (
                     FoFdcMessage::BattleDamageAssessmentConfirm(bda_readback),
@@ -288,25 +608,120 @@ pub(crate) async fn state_machine_loop(
                 ) => {
                     info!("Received a readback for Battle Damage Assessment. Evaluating...");
                     debug!("BDA Readback: {:?}", bda_readback);
-                    //TODO: Proccess any errors
-                    info!("Readback confirmed, sending SolidReadback...");
-                    to_fdc.send(FoFdcMessage::SolidReadback(
-                        SolidReadback::BattleDamageAssessment,
-                    ))?;
+                    match &assessment {
+                        Some(bda) if *bda == bda_readback => {
+                            info!("Readback confirmed, sending SolidReadback and ending the mission...");
+                            to_fdc.send(FoFdcMessage::SolidReadback(
+                                SolidReadback::BattleDamageAssessment,
+                            ))?;
 
-                    state = state
-                        .try_to_standby()
-                        .expect("state was invalid for conversion")
+                            let eom = EndOfMission {
+                                src: "FO".to_string(),       //TODO: Get from source
+                                receiver: "FDC".to_string(), //TODO: Get from config
+                                target_number: target_number
+                                    .clone()
+                                    .context("No MTO has given the mission a target number")?,
+                            };
+                            end_of_mission = Some(eom.clone());
+                            to_fdc.send(FoFdcMessage::EndOfMission(eom))?
+                        }
+                        Some(bda) => {
+                            warn!(
+                                "BDA readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(bda, &bda_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(
+                                Correction::BattleDamageAssessment(bda.clone()),
+                            ))?
+                        }
+                        None => warn!("No BDA was sent to be read back"),
+                    }
+                }
+
+                // Received End of Mission Readback
+                (
+                    FoFdcMessage::EndOfMissionConfirm(eom_readback),
+                    FoState::Connected {
+                        state: ConnectedState::Reporting,
+                    },
+                ) => {
+                    info!("Received a readback for End of Mission. Evaluating...");
+                    debug!("End of Mission Readback: {:?}", eom_readback);
+                    match &end_of_mission {
+                        Some(eom) if *eom == eom_readback => {
+                            info!("Readback confirmed, sending SolidReadback...");
+                            to_fdc
+                                .send(FoFdcMessage::SolidReadback(SolidReadback::EndOfMission))?;
+
+                            target_number = None;
+                            end_of_mission = None;
+                            state = state
+                                .try_to_standby()
+                                .expect("state was invalid for conversion")
+                        }
+                        Some(eom) => {
+                            warn!(
+                                "End of Mission readback is wrong in {:?}, sending a Correction...",
+                                correction::corrected_fields(eom, &eom_readback)
+                            );
+                            to_fdc.send(FoFdcMessage::Correction(Correction::EndOfMission(
+                                eom.clone(),
+                            )))?
+                        }
+                        None => warn!("No End of Mission was sent to be read back"),
+                    }
+                }
+
+                // Correction received for one of our readbacks
+                (FoFdcMessage::Correction(correction), FoState::Connected { .. }) => {
+                    warn!("Our readback was wrong, reading back the Correction...");
+                    match correction {
+                        Correction::MessageToObserver(mto) => {
+                            target_number = Some(mto.target_number.clone());
+                            to_fdc.send(FoFdcMessage::MessageToObserverConfirm(mto))?
+                        }
+                        Correction::Shot(shot) => to_fdc.send(FoFdcMessage::ShotConfirm(shot))?,
+                        Correction::Splash(splash) => {
+                            to_fdc.send(FoFdcMessage::SplashConfirm(splash))?
+                        }
+                        Correction::RoundsComplete(rounds_complete) => {
+                            to_fdc.send(FoFdcMessage::RoundsCompleteConfirm(rounds_complete))?
+                        }
+                        Correction::CannotComply(cantco) => {
+                            to_fdc.send(FoFdcMessage::CannotComplyConfirm(cantco))?
+                        }
+                        Correction::CheckFireComplete(check_fire_complete) => to_fdc
+                            .send(FoFdcMessage::CheckFireCompleteConfirm(check_fire_complete))?,
+                        Correction::RequestForFire(_)
+                        | Correction::SubsequentCorrection(_)
+                        | Correction::BattleDamageAssessment(_)
+                        | Correction::CheckFire(_)
+                        | Correction::Repeat(_)
+                        | Correction::RecordAsTarget(_)
+                        | Correction::EndOfMission(_) => {
+                            error!(
+                                "Received a correction intended for transmission from FO Sim only"
+                            )
+                        }
+                    }
                 }
 
                 // UNEXPECTED MESSAGES
                 (FoFdcMessage::RequestForFireConfirm(_), _)
                 | (FoFdcMessage::MessageToObserver(_), _)
+                | (FoFdcMessage::CannotComply(_), _)
                 | (FoFdcMessage::SolidReadback(_), _)
                 | (FoFdcMessage::Shot(_), _)
                 | (FoFdcMessage::Splash(_), _)
                 | (FoFdcMessage::RoundsComplete(_), _)
-                | (FoFdcMessage::BattleDamageAssessmentConfirm(_), _) => {
+                | (FoFdcMessage::BattleDamageAssessmentConfirm(_), _)
+                | (FoFdcMessage::SubsequentCorrectionConfirm(_), _)
+                | (FoFdcMessage::RepeatConfirm(_), _)
+                | (FoFdcMessage::RecordAsTargetConfirm(_), _)
+                | (FoFdcMessage::EndOfMissionConfirm(_), _)
+                | (FoFdcMessage::CheckFireConfirm(_), _)
+                | (FoFdcMessage::CheckFireComplete(_), _)
+                | (FoFdcMessage::Correction(_), _) => {
                     warn!("Received a message when in a state that doesn't expect it",);
                 }
 
@@ -314,9 +729,16 @@ pub(crate) async fn state_machine_loop(
                 (FoFdcMessage::RequestForFire(_), _)
                 | (FoFdcMessage::BattleDamageAssessment(_), _)
                 | (FoFdcMessage::MessageToObserverConfirm(_), _)
+                | (FoFdcMessage::CannotComplyConfirm(_), _)
                 | (FoFdcMessage::RoundsCompleteConfirm(_), _)
                 | (FoFdcMessage::ShotConfirm(_), _)
-                | (FoFdcMessage::SplashConfirm(_), _) => {
+                | (FoFdcMessage::SplashConfirm(_), _)
+                | (FoFdcMessage::SubsequentCorrection(_), _)
+                | (FoFdcMessage::Repeat(_), _)
+                | (FoFdcMessage::RecordAsTarget(_), _)
+                | (FoFdcMessage::EndOfMission(_), _)
+                | (FoFdcMessage::CheckFire(_), _)
+                | (FoFdcMessage::CheckFireCompleteConfirm(_), _) => {
                     error!("Received a message intended for transmission from FO Sim only",)
                 }
             }
@@ -340,28 +762,71 @@ pub(crate) async fn state_machine_loop(
                     state: ConnectedState::Requesting,
                 };
 
-                let request_for_fire = WarnOrder {
+                let rff = WarnOrder {
                     src: "FO".to_string(),       //TODO: Get from source
                     receiver: "FDC".to_string(), //TODO: Get from config
-                    mission_type: MissionType::FireForEffect,
+                    response_addr: Some(FO_ADDR),
+                    mission_type: MissionType::AdjustFire,
                     target_location: TargetLocation::Grid {
-                        lateral: 321,
-                        longitudinal: 654,
+                        lateral: 135,
+                        longitudinal: 182,
                     },
                     target_description: TargetDescription::default(),
                     danger_close: false,
                     ammunition: Some(Ammunition::HighExplosive),
+                    fuze: Some(Fuze::PointDetonating),
                     method_of_fire: None,
                 };
-                info!("Sending a RRF: {:?}", request_for_fire);
-                to_fdc.send(FoFdcMessage::RequestForFire(request_for_fire))?;
+                burst = match rff.mission_type {
+                    MissionType::AdjustFire => Some(FIRST_BURST),
+                    MissionType::FireForEffect => None,
+                };
+                repeats = REPEATS;
+                volleys_observed = 0;
+                info!("Sending a RRF: {:?}", rff);
+                request_for_fire = Some(rff.clone());
+                to_fdc.send(FoFdcMessage::RequestForFire(rff))?;
             }
             FoState::Connected {
                 state: ConnectedState::Requesting,
             } => (),
             FoState::Connected {
                 state: ConnectedState::Observing,
-            } => (),
+            } => {
+                if observe_at.is_some_and(|observe_at| Instant::now() >= observe_at) {
+                    observe_at = None;
+                    if let Some(adjusting) = &mut burst {
+                        info!(
+                            "Observed the adjusting round {} m right and {} m beyond the target",
+                            adjusting.right, adjusting.beyond
+                        );
+                        if adjusting.is_unsafe() {
+                            warn!(
+                                "Adjusting round burst unsafely far from the target, checking fire"
+                            );
+                            burst = None;
+                            let checking = CheckFire {
+                                src: "FO".to_string(),       //TODO: Get from source
+                                receiver: "FDC".to_string(), //TODO: Get from config
+                                target_number: target_number
+                                    .clone()
+                                    .context("No MTO has given the mission a target number")?,
+                            };
+                            check_fire = Some(checking.clone());
+                            to_fdc.send(FoFdcMessage::CheckFire(checking))?;
+                        } else {
+                            let correction = adjusting.correct();
+                            if correction.fire_for_effect {
+                                info!("Adjusting round is on target, calling for fire for effect");
+                                burst = None;
+                            }
+                            info!("Sending a Subsequent Correction: {:?}", correction);
+                            subsequent_correction = Some(correction.clone());
+                            to_fdc.send(FoFdcMessage::SubsequentCorrection(correction))?;
+                        }
+                    }
+                }
+            }
             FoState::Connected {
                 state: ConnectedState::Reporting,
             } => (),
@@ -369,3 +834,24 @@ pub(crate) async fn state_machine_loop(
     }
     Ok(())
 }
+
+/// Assesses the effect on the target with `target_number` of the `volleys` of fire for effect the FO saw fall on it.
+//TODO: Observe from the target
+fn assess(target_number: TargetNumber, volleys: u32) -> BattleDamageAssessment {
+    let effect = match volleys {
+        0 => Effect::None,
+        volleys if volleys >= DESTROYING_VOLLEYS => Effect::Destroyed,
+        volleys if volleys >= NEUTRALIZING_VOLLEYS => Effect::Neutralized,
+        _ => Effect::Suppressed,
+    };
+    BattleDamageAssessment {
+        src: "FO".to_string(),       //TODO: Get from source
+        receiver: "FDC".to_string(), //TODO: Get from config
+        target_number,
+        casualties: volleys.saturating_mul(CASUALTIES_PER_VOLLEY),
+        equipment_destroyed: volleys / NEUTRALIZING_VOLLEYS,
+        effect,
+        target_movement: None,
+        remarks: format!("{} volleys observed on target", volleys),
+    }
+}
@@ -0,0 +1,108 @@
+//! Contains the communication tasks (send and receive) for the Gun sim's TCP link to an FDC
+//!
+use anyhow::{bail, Result};
+use fdc_gun_comms::{
+    codec::{FdcGunMessageDecoder, FdcGunMessageEncoder},
+    FdcGunMessage, ProtocolVersion,
+};
+use futures::{SinkExt, StreamExt};
+use tokio::{
+    net::{
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+        TcpStream,
+    },
+    sync::mpsc::{UnboundedReceiver, UnboundedSender},
+    try_join,
+};
+use tokio_util::codec::{FramedRead, FramedWrite};
+use tracing::{debug, info, trace};
+
+/// Provides a reader/writer loop, sending data from `to_fdc_rx` to the FDC over TCP,
+/// and placing data received from the FDC in the `from_fdc_tx`.
+///
+/// The protocol version is agreed with the FDC before either loop starts.
+pub(crate) async fn fdc_gun_commhandler_loop(
+    to_fdc_rx: UnboundedReceiver<FdcGunMessage>,
+    from_fdc_tx: UnboundedSender<FdcGunMessage>,
+) -> Result<()> {
+    let stream = TcpStream::connect("127.0.0.1:49154").await?;
+    debug!(
+        "Connected from {} to {}",
+        stream.local_addr()?,
+        stream.peer_addr()?
+    );
+
+    let (reader, writer) = stream.into_split();
+    let mut reader = FramedRead::new(reader, FdcGunMessageDecoder::default());
+    let mut writer = FramedWrite::new(writer, FdcGunMessageEncoder::default());
+
+    writer
+        .send(FdcGunMessage::VersionOffer {
+            max_version: ProtocolVersion::LATEST.into(),
+        })
+        .await?;
+    let version = match reader.next().await.transpose()? {
+        Some(FdcGunMessage::VersionAccept { version }) => version,
+        Some(message) => bail!("Expected a Version Accept, got {:?}", message),
+        None => bail!("FDC closed the connection before agreeing on a version"),
+    };
+    reader.decoder_mut().set_version(version);
+    writer.encoder_mut().set_version(version);
+    info!("Agreed on {:?} with the FDC", version);
+
+    // Spin off listener thread
+    let recv_handle = tokio::task::Builder::new()
+        .name("receive loop")
+        .spawn(async move { recv_loop(from_fdc_tx, reader).await });
+
+    // Spin off writer thread
+    let send_handle = tokio::task::Builder::new()
+        .name("send loop")
+        .spawn(async move { send_loop(to_fdc_rx, writer).await });
+
+    let (left, right) = try_join!(recv_handle, send_handle)?;
+    left?;
+    right?;
+
+    Ok(())
+}
+
+/// Receive loop, for handling receipt over TCP
+async fn recv_loop(
+    from_fdc_tx: UnboundedSender<FdcGunMessage>,
+    mut fdc_reader: FramedRead<OwnedReadHalf, FdcGunMessageDecoder>,
+) -> Result<()> {
+    trace!("Started the recv loop");
+    while let Some(message) = fdc_reader.next().await {
+        let message = message?;
+        debug!("Received {:?}", message);
+        from_fdc_tx.send(message)?;
+    }
+    trace!("FDC closed the connection, leaving the recv loop");
+    Ok(())
+}
+
+/// Send loop, for sending data over TCP
+async fn send_loop(
+    mut to_fdc_rx: UnboundedReceiver<FdcGunMessage>,
+    mut fdc_writer: FramedWrite<OwnedWriteHalf, FdcGunMessageEncoder>,
+) -> Result<()> {
+    trace!("Started the send loop");
+    while let Some(message_to_fdc) = to_fdc_rx.recv().await {
+        debug!("Sending {:?}", message_to_fdc);
+        match message_to_fdc {
+            FdcGunMessage::StatusReply { .. }
+            | FdcGunMessage::FireReport { .. }
+            | FdcGunMessage::ComplianceResponse { .. }
+            | FdcGunMessage::Malfunction { .. }
+            | FdcGunMessage::ResupplyAck { .. }
+            | FdcGunMessage::Registration { .. } => fdc_writer.send(message_to_fdc).await?,
+            _ => panic!(
+                "Unsupported message was sent for transmission to the FDC: {:?}",
+                message_to_fdc
+            ),
+        }
+    }
+    trace!("Leaving the send loop");
+    Ok(())
+}
@@ -0,0 +1,76 @@
+//! A Gun Simulator
+//!
+//! This crate provides an `async` Gun Simulator built on `tokio`.
+//! The crate will connect to an FDC and execute fire commands over the [`fdc_gun_comms`] message interface.
+use anyhow::Result;
+use fdc_gun_comms::GunPosition;
+use fire_direction::firing_table::FiringTables;
+use tokio::{sync::mpsc, try_join};
+use tracing::info;
+
+use crate::{fdc_gun_commhandler::fdc_gun_commhandler_loop, state_machine::state_machine_loop};
+
+mod fdc_gun_commhandler;
+mod state_machine;
+
+/// Where a Gun is emplaced when no position is given.
+const DEFAULT_POSITION: GunPosition = GunPosition {
+    easting: 10_000,
+    northing: 10_000,
+};
+
+/// The port the tokio console listens on when none is given.
+const DEFAULT_CONSOLE_PORT: u16 = 7001;
+
+/// Entry point for the Gun simulator
+#[tokio::main]
+async fn main() -> Result<()> {
+    // The gun identifier, easting, northing and console port are the arguments, in that order, so several guns can
+    // register with the same FDC from positions of their own, each with its own console.
+    let mut args = std::env::args().skip(1);
+    let gun_id = args.next().map(|arg| arg.parse()).transpose()?.unwrap_or(1);
+    let position = GunPosition {
+        easting: args
+            .next()
+            .map(|arg| arg.parse())
+            .transpose()?
+            .unwrap_or(DEFAULT_POSITION.easting),
+        northing: args
+            .next()
+            .map(|arg| arg.parse())
+            .transpose()?
+            .unwrap_or(DEFAULT_POSITION.northing),
+    };
+    let console_port = args
+        .next()
+        .map(|arg| arg.parse())
+        .transpose()?
+        .unwrap_or(DEFAULT_CONSOLE_PORT);
+
+    // tracing_subscriber::fmt::init();
+    console_subscriber::ConsoleLayer::builder()
+        .server_addr(([127, 0, 0, 1], console_port))
+        .init();
+
+    let firing_tables = FiringTables::from_env()?;
+
+    let (from_fdc_tx, from_fdc_rx) = mpsc::unbounded_channel();
+    let (to_fdc_tx, to_fdc_rx) = mpsc::unbounded_channel();
+
+    info!("Starting the FDC-Gun Comm Handler...");
+    let fdc_gun_commhandler_handle = tokio::task::Builder::new()
+        .name("commhandler loop")
+        .spawn(async move { fdc_gun_commhandler_loop(to_fdc_rx, from_fdc_tx).await });
+
+    info!("Starting the Gun State Machine...");
+    let state_machine_handle = tokio::task::Builder::new()
+        .name("state machine loop")
+        .spawn(async move {
+            state_machine_loop(from_fdc_rx, to_fdc_tx, gun_id, position, firing_tables).await
+        });
+
+    let _results = try_join!(fdc_gun_commhandler_handle, state_machine_handle)?;
+    _results.0?;
+    _results.1?;
+    Ok(())
+}
@@ -0,0 +1,477 @@
+//! Provides the functions and enums for maintaining the Gun state machine.
+//!
+//! The Gun State Machine uses the top level [`GunState`] for representing the state of the Gun.
+//! The workhorse of this module, and the intended component for use is the [`state_machine_loop`],
+//! which provides an `async` function for use in a runtime.
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use fdc_gun_comms::{
+    Ammunition, Capabilities, Compliance, Fault, FdcGunMessage, GunPosition, Status,
+    TargetLocation, NO_COMMAND_ID,
+};
+use fire_direction::firing_table::{FiringTables, Solution};
+use rand::{rngs::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use tokio::{
+    sync::mpsc::{error::TryRecvError, UnboundedReceiver, UnboundedSender},
+    time::{Duration, Instant},
+};
+use tracing::{debug, error, info, info_span, trace, warn};
+
+/// The time between each shot of a fire mission.
+const SHOT_INTERVAL: Duration = Duration::from_secs(4);
+
+/// The weapon this Gun is, for looking up its firing tables.
+//TODO: Get from config
+const WEAPON: &str = "m777";
+
+/// The chance of each shot being stopped by a fault.
+//TODO: Get from config
+const FAULT_CHANCE: f64 = 0.02;
+
+/// The faults that can strike at random when a shot is fired.
+const RANDOM_FAULTS: [Fault; 3] = [Fault::Misfire, Fault::HangFire, Fault::HydraulicFailure];
+
+/// The fewest rounds of each ammunition carried for the Gun to be fully capable of firing it.
+//TODO: Get from config
+const MINIMUM_ROUNDS: u32 = 4;
+
+/// What this Gun can do.
+//TODO: Get from config
+const CAPABILITIES: Capabilities = Capabilities {
+    max_range: 22_000,
+    rounds_per_minute: 15,
+};
+
+/// The State of the Gun
+#[derive(Debug, Clone, PartialEq)]
+enum GunState {
+    /// Indicates the Gun is not yet registered with the FDC
+    Offline,
+    /// Indicates the Gun is ready to take commands
+    Online { state: OnlineState },
+}
+
+/// Substates of the Gun when Online
+#[derive(Debug, Clone, PartialEq)]
+enum OnlineState {
+    /// Indicates the Gun is waiting for a Fire Command
+    Waiting,
+    /// Indicates the Gun is executing a Fire Command
+    Firing { mission: FireMission },
+}
+
+/// The progress of a Fire Command being executed by the Gun
+#[derive(Debug, Clone, PartialEq)]
+struct FireMission {
+    /// The FDC's ID for the Fire Command
+    command_id: u32,
+    /// Total rounds to fire for this mission
+    total_shots: u32,
+    /// Rounds fired so far
+    shots_fired: u32,
+    /// The ammunition in use
+    ammunition: Ammunition,
+    /// Where the rounds are going
+    target_location: TargetLocation,
+    /// How the Gun is laid to get them there
+    solution: Solution,
+    /// When the next round will leave the tube
+    next_shot_at: Instant,
+}
+
+/// Asynchronous executor loop for managing the state machine.
+///
+/// All state manipulation happens within the context of this function.
+/// In the event a message is received that is not expected, the state machine will not change state,
+/// but will emit a [`tracing::warn!`] event.
+///
+/// # Arguments
+///
+/// * `from_fdc_rx` - The receive side of a channel for processing the state machine with messages from the FDC.
+/// * `to_fdc_tx` - The send side of a channel where messages to send to the FDC are sent by the state machine loop.
+/// * `gun_id` - The identifier this Gun registers with.
+/// * `position` - Where this Gun is emplaced.
+/// * `firing_tables` - The firing tables for laying the Gun.
+///
+/// # Errors
+///
+/// Returns an error if the FDC rejects the Gun's registration, or if a channel to the commhandler closes early.
+pub(crate) async fn state_machine_loop(
+    mut from_fdc_rx: UnboundedReceiver<FdcGunMessage>,
+    to_fdc_tx: UnboundedSender<FdcGunMessage>,
+    gun_id: u32,
+    position: GunPosition,
+    firing_tables: FiringTables,
+) -> Result<()> {
+    let mut state = GunState::Offline;
+    // The faults the crew is still clearing, with when each will be cleared
+    let mut faults: HashMap<Fault, Instant> = HashMap::new();
+    let mut rng = StdRng::from_entropy();
+    // The ID of the last Fire Command taken on, to recognize it if the FDC sends it again
+    let mut last_command_id = NO_COMMAND_ID;
+    //TODO: Get from config
+    let mut inventory = HashMap::from([
+        (Ammunition::HighExplosive, 120),
+        (Ammunition::Smoke, 30),
+        (Ammunition::Illumination, 20),
+        (Ammunition::WhitePhosphorus, 10),
+    ]);
+    let message_process_span = info_span!("message_process");
+    let state_machine_run_span = info_span!("state_run");
+    info!("Starting state machine...");
+
+    info!("Registering as Gun {} with the FDC", gun_id);
+    to_fdc_tx.send(FdcGunMessage::Registration {
+        gun_id,
+        position,
+        capabilities: CAPABILITIES,
+    })?;
+    loop {
+        trace!("Looping...");
+        debug!("State is {:?}", state);
+
+        let message = match from_fdc_rx.try_recv() {
+            Ok(message) => Some(message),
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                info!("transmitter disconnected");
+                break;
+            }
+        };
+
+        tokio::task::yield_now().await;
+
+        trace!("Checking for messages");
+        if let Some(message) = message {
+            debug!("Received message: {:?}", message);
+            let _enter = message_process_span.enter();
+            trace!("Entering message processor");
+            match (message, &state) {
+                // Registration accepted while offline
+                (
+                    FdcGunMessage::RegistrationAck {
+                        accepted: true,
+                        gun_id: ack_id,
+                    },
+                    GunState::Offline,
+                ) if ack_id == gun_id => {
+                    info!("Registration accepted, going online");
+                    state = GunState::Online {
+                        state: OnlineState::Waiting,
+                    };
+                }
+                // Registration rejected while offline
+                (
+                    FdcGunMessage::RegistrationAck {
+                        accepted: false,
+                        gun_id: ack_id,
+                    },
+                    GunState::Offline,
+                ) if ack_id == gun_id => {
+                    bail!("The FDC rejected the registration for Gun {}", gun_id);
+                }
+                // Status Request received while online
+                (FdcGunMessage::StatusRequest, GunState::Online { .. }) => {
+                    info!("Received Status Request, replying...");
+                    to_fdc_tx.send(FdcGunMessage::StatusReply {
+                        status: status_of(&inventory, &faults),
+                        rounds: inventory.clone(),
+                    })?;
+                }
+                // Fire Command received again after it was fired
+                (
+                    FdcGunMessage::FireCommand { command_id, .. },
+                    GunState::Online {
+                        state: OnlineState::Waiting,
+                    },
+                ) if command_id != NO_COMMAND_ID && command_id == last_command_id => {
+                    info!("Received Fire Command {command_id} again, already complied");
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id,
+                        compliance: Compliance::HAVECO,
+                    })?;
+                }
+                // Fire Command received while a fault keeps the Gun from firing
+                (
+                    FdcGunMessage::FireCommand { command_id, .. },
+                    GunState::Online {
+                        state: OnlineState::Waiting,
+                    },
+                ) if status_of(&inventory, &faults) == Status::NonOperational => {
+                    warn!(
+                        "Cannot fire while non-operational with {:?}",
+                        faults.keys().collect::<Vec<_>>()
+                    );
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id,
+                        compliance: Compliance::CANTCO,
+                    })?;
+                }
+                // Fire Command received while waiting
+                (
+                    FdcGunMessage::FireCommand {
+                        command_id,
+                        rounds,
+                        ammunition,
+                        fuze,
+                        target_location,
+                    },
+                    GunState::Online {
+                        state: OnlineState::Waiting,
+                    },
+                ) => {
+                    let available = inventory.get(&ammunition).copied().unwrap_or_default();
+                    let solution = firing_tables.solve(WEAPON, ammunition, target_location.range());
+                    match (rounds, solution) {
+                        (total_shots, Ok(solution))
+                            if total_shots > 0 && total_shots <= available =>
+                        {
+                            info!("Received Fire Command for {rounds} rounds of {ammunition:?} fuzed {fuze:?}, firing...");
+                            info!(
+                                "Laying charge {} at {} mils elevation, {} mils direction",
+                                solution.charge,
+                                solution.quadrant_elevation,
+                                target_location.direction()
+                            );
+                            to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                                command_id,
+                                compliance: Compliance::WILLCO,
+                            })?;
+                            last_command_id = command_id;
+                            state = GunState::Online {
+                                state: OnlineState::Firing {
+                                    mission: FireMission {
+                                        command_id,
+                                        total_shots,
+                                        shots_fired: 0,
+                                        ammunition,
+                                        target_location,
+                                        solution,
+                                        next_shot_at: Instant::now(),
+                                    },
+                                },
+                            };
+                        }
+                        (_, Err(err)) => {
+                            warn!("Cannot fire {ammunition:?}: {err}");
+                            to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                                command_id,
+                                compliance: Compliance::CANTCO,
+                            })?;
+                        }
+                        (_, Ok(_)) => {
+                            warn!(
+                                "Cannot fire {rounds} rounds of {ammunition:?} with {available} on hand"
+                            );
+                            to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                                command_id,
+                                compliance: Compliance::CANTCO,
+                            })?;
+                        }
+                    }
+                }
+                // Fire Command being fired received again
+                (
+                    FdcGunMessage::FireCommand { command_id, .. },
+                    GunState::Online {
+                        state: OnlineState::Firing { mission },
+                    },
+                ) if command_id != NO_COMMAND_ID && command_id == mission.command_id => {
+                    info!("Received Fire Command {command_id} again, already firing it");
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id,
+                        compliance: Compliance::WILLCO,
+                    })?;
+                }
+                // Fire Command received while already firing
+                (
+                    FdcGunMessage::FireCommand { command_id, .. },
+                    GunState::Online {
+                        state: OnlineState::Firing { .. },
+                    },
+                ) => {
+                    warn!("Received Fire Command while firing, cannot comply");
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id,
+                        compliance: Compliance::CANTCO,
+                    })?;
+                }
+                // Check Fire received while firing
+                (
+                    FdcGunMessage::CheckFire,
+                    GunState::Online {
+                        state: OnlineState::Firing { mission },
+                    },
+                ) => {
+                    info!("Received Check Fire, ceasing fires");
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id: mission.command_id,
+                        compliance: Compliance::WILLCO,
+                    })?;
+                    state = GunState::Online {
+                        state: OnlineState::Waiting,
+                    };
+                }
+                // Check Fire received while not firing
+                (
+                    FdcGunMessage::CheckFire,
+                    GunState::Online {
+                        state: OnlineState::Waiting,
+                    },
+                ) => {
+                    info!("Received Check Fire while not firing");
+                    to_fdc_tx.send(FdcGunMessage::ComplianceResponse {
+                        command_id: last_command_id,
+                        compliance: Compliance::HAVECO,
+                    })?;
+                }
+
+                // Resupply received while online
+                (FdcGunMessage::Resupply { rounds }, GunState::Online { .. }) => {
+                    info!("Received Resupply of {:?}, stowing...", rounds);
+                    for (ammunition, count) in rounds {
+                        let on_hand = inventory.entry(ammunition).or_default();
+                        *on_hand = on_hand.saturating_add(count);
+                    }
+                    to_fdc_tx.send(FdcGunMessage::ResupplyAck {
+                        rounds: inventory.clone(),
+                    })?;
+                }
+
+                // Unexpected messages
+                (FdcGunMessage::StatusRequest, _)
+                | (FdcGunMessage::FireCommand { .. }, _)
+                | (FdcGunMessage::CheckFire, _)
+                | (FdcGunMessage::RegistrationAck { .. }, _)
+                | (FdcGunMessage::VersionAccept { .. }, _)
+                | (FdcGunMessage::Resupply { .. }, _) => {
+                    warn!("Received a message when in a state that doesn't expect it");
+                }
+
+                // Invalid messages, these messages are not expected, since we only send these.
+                (FdcGunMessage::StatusReply { .. }, _)
+                | (FdcGunMessage::FireReport { .. }, _)
+                | (FdcGunMessage::ComplianceResponse { .. }, _)
+                | (FdcGunMessage::Registration { .. }, _)
+                | (FdcGunMessage::VersionOffer { .. }, _)
+                | (FdcGunMessage::Malfunction { .. }, _)
+                | (FdcGunMessage::ResupplyAck { .. }, _) => {
+                    error!("Received a message intended for transmission from Gun Sim only");
+                }
+            }
+        }
+
+        let _state_run_enter = state_machine_run_span.enter();
+        trace!("Entering the state runner");
+        faults.retain(|fault, cleared_at| {
+            let cleared = Instant::now() >= *cleared_at;
+            if cleared {
+                info!("Cleared {:?}", fault);
+            }
+            !cleared
+        });
+        match &mut state {
+            GunState::Offline => (),
+            GunState::Online {
+                state: OnlineState::Firing { mission },
+            } => {
+                let shot_due = Instant::now() >= mission.next_shot_at;
+                let fault = if !shot_due {
+                    None
+                } else if inventory
+                    .get(&mission.ammunition)
+                    .copied()
+                    .unwrap_or_default()
+                    == 0
+                {
+                    Some(Fault::OutOfAmmunition)
+                } else if rng.gen_bool(FAULT_CHANCE) {
+                    RANDOM_FAULTS.choose(&mut rng).copied()
+                } else {
+                    None
+                };
+
+                if let Some(fault) = fault {
+                    let shot = mission.shots_fired + 1;
+                    warn!("{:?} on shot {}, ceasing fires", fault, shot);
+                    to_fdc_tx.send(FdcGunMessage::Malfunction {
+                        command_id: mission.command_id,
+                        fault,
+                        shot,
+                    })?;
+                    if let Some(time_to_clear) = time_to_clear(fault) {
+                        faults.insert(fault, Instant::now() + time_to_clear);
+                    }
+                    state = GunState::Online {
+                        state: OnlineState::Waiting,
+                    };
+                } else if shot_due {
+                    mission.shots_fired += 1;
+                    if let Some(on_hand) = inventory.get_mut(&mission.ammunition) {
+                        *on_hand = on_hand.saturating_sub(1);
+                    }
+                    info!(
+                        "Shot {} of {} out",
+                        mission.shots_fired, mission.total_shots
+                    );
+                    to_fdc_tx.send(FdcGunMessage::FireReport {
+                        command_id: mission.command_id,
+                        shot: mission.shots_fired,
+                        total_shots: mission.total_shots,
+                        ammunition: mission.ammunition,
+                        target_location: mission.target_location.clone(),
+                        time_to_target: mission
+                            .solution
+                            .time_of_flight
+                            .as_millis()
+                            .try_into()
+                            .unwrap_or(u32::MAX),
+                    })?;
+                    mission.next_shot_at += SHOT_INTERVAL;
+
+                    if mission.shots_fired == mission.total_shots {
+                        info!("Rounds complete, waiting for the next Fire Command");
+                        state = GunState::Online {
+                            state: OnlineState::Waiting,
+                        };
+                    }
+                }
+            }
+            GunState::Online {
+                state: OnlineState::Waiting,
+            } => (),
+        }
+    }
+    Ok(())
+}
+
+/// Computes the high-level [`Status`] of the Gun from its ammunition on hand and the faults being cleared.
+///
+/// The Gun is only partially operational while any ammunition it carries is below [`MINIMUM_ROUNDS`], and is
+/// non-operational once it is out of everything.
+fn status_of(inventory: &HashMap<Ammunition, u32>, faults: &HashMap<Fault, Instant>) -> Status {
+    let ammunition_status = if inventory.values().all(|count| *count == 0) {
+        Status::NonOperational
+    } else if inventory.values().any(|count| *count < MINIMUM_ROUNDS) {
+        Status::PartialOperational
+    } else {
+        Status::Operational
+    };
+    faults
+        .keys()
+        .map(|fault| fault.status())
+        .fold(ammunition_status, Status::min)
+}
+
+/// How long the crew takes to clear a `fault`, or [`None`] if it lasts until resupplied.
+//TODO: Get from config
+fn time_to_clear(fault: Fault) -> Option<Duration> {
+    match fault {
+        Fault::Misfire => Some(Duration::from_secs(10)),
+        // Much shorter than a real hang-fire wait, to keep exercises moving.
+        Fault::HangFire => Some(Duration::from_secs(30)),
+        Fault::HydraulicFailure => Some(Duration::from_secs(60)),
+        Fault::OutOfAmmunition => None,
+    }
+}